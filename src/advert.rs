@@ -0,0 +1,421 @@
+use crate::keystore::{KeySelector, decode_private_key, decode_public_key, select_key};
+use crate::signing::{sign_with_expanded_key, verify_signature};
+use anyhow::{Context, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// Firmware limit on advert app data; longer adverts are dropped by receiving nodes.
+pub const MAX_APP_DATA_SIZE: usize = 32;
+
+const PUB_KEY_SIZE: usize = 32;
+const TIMESTAMP_SIZE: usize = 4;
+const SIGNATURE_SIZE: usize = 64;
+const APP_DATA_OFFSET: usize = PUB_KEY_SIZE + TIMESTAMP_SIZE + SIGNATURE_SIZE;
+
+/// Packet header for a flood-routed advert: payload type 0x04 in bits 2-5, route type 0x01.
+const FLOOD_ADVERT_HEADER: u8 = (0x04 << 2) | 0x01;
+
+const ADV_TYPE_MASK: u8 = 0x0F;
+const ADV_LATLON_MASK: u8 = 0x10;
+const ADV_FEAT1_MASK: u8 = 0x20;
+const ADV_FEAT2_MASK: u8 = 0x40;
+const ADV_NAME_MASK: u8 = 0x80;
+
+/// Node role announced in the advert flags, matching the firmware's `ADV_TYPE_*` values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeType {
+    Chat = 1,
+    Repeater = 2,
+    Room = 3,
+}
+
+impl NodeType {
+    fn from_flags(flags: u8) -> Result<Self> {
+        match flags & ADV_TYPE_MASK {
+            1 => Ok(NodeType::Chat),
+            2 => Ok(NodeType::Repeater),
+            3 => Ok(NodeType::Room),
+            other => anyhow::bail!("Unsupported advert node type {}", other),
+        }
+    }
+}
+
+impl FromStr for NodeType {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "chat" => Ok(NodeType::Chat),
+            "repeater" => Ok(NodeType::Repeater),
+            "room" => Ok(NodeType::Room),
+            _ => anyhow::bail!(
+                "Unknown node type '{}' (expected chat, repeater or room)",
+                value
+            ),
+        }
+    }
+}
+
+impl fmt::Display for NodeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            NodeType::Chat => "chat",
+            NodeType::Repeater => "repeater",
+            NodeType::Room => "room",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The self-describing part of an advert that follows the signature.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdvertAppData {
+    pub node_type: NodeType,
+    pub location: Option<(f64, f64)>,
+    pub name: Option<String>,
+}
+
+impl AdvertAppData {
+    /// Encodes flags, optional fixed-point lat/lon (degrees * 1e6) and the unterminated name.
+    /// Oversized data is rejected rather than truncated so the name on air is what the user typed.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut flags = self.node_type as u8;
+        let mut data = Vec::with_capacity(MAX_APP_DATA_SIZE);
+        data.push(0);
+
+        if let Some((lat, lon)) = self.location {
+            if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+                anyhow::bail!("Location {}, {} is out of range", lat, lon);
+            }
+            flags |= ADV_LATLON_MASK;
+            data.extend_from_slice(&((lat * 1e6).round() as i32).to_le_bytes());
+            data.extend_from_slice(&((lon * 1e6).round() as i32).to_le_bytes());
+        }
+
+        if let Some(name) = self.name.as_deref().filter(|n| !n.is_empty()) {
+            flags |= ADV_NAME_MASK;
+            data.extend_from_slice(name.as_bytes());
+        }
+
+        if data.len() > MAX_APP_DATA_SIZE {
+            anyhow::bail!(
+                "Advert app data is {} bytes, the limit is {} (shorten the node name)",
+                data.len(),
+                MAX_APP_DATA_SIZE
+            );
+        }
+
+        data[0] = flags;
+        Ok(data)
+    }
+
+    /// Parses app data, skipping the feature words the firmware reserves for future use.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let (&flags, mut rest) = data.split_first().context("Advert app data is empty")?;
+
+        let location = if flags & ADV_LATLON_MASK != 0 {
+            let lat = take_i32(&mut rest).context("Advert is truncated in latitude")?;
+            let lon = take_i32(&mut rest).context("Advert is truncated in longitude")?;
+            Some((lat as f64 / 1e6, lon as f64 / 1e6))
+        } else {
+            None
+        };
+
+        for mask in [ADV_FEAT1_MASK, ADV_FEAT2_MASK] {
+            if flags & mask != 0 {
+                rest = rest
+                    .get(2..)
+                    .context("Advert is truncated in feature flags")?;
+            }
+        }
+
+        let name = (flags & ADV_NAME_MASK != 0).then(|| String::from_utf8_lossy(rest).into_owned());
+
+        Ok(Self {
+            node_type: NodeType::from_flags(flags)?,
+            location,
+            name,
+        })
+    }
+}
+
+/// A decoded advert payload. `signature_valid` is reported rather than enforced so that
+/// broken frames can still be inspected field by field.
+#[derive(Debug, Clone)]
+pub struct Advert {
+    pub public_key: [u8; 32],
+    pub timestamp: u32,
+    pub signature: [u8; 64],
+    pub app_data: AdvertAppData,
+    pub signature_valid: bool,
+}
+
+/// Builds a signed self-advert payload: public key, timestamp, signature, app data.
+/// The signature covers public key || timestamp || app data, as verified by receiving nodes.
+pub fn build_advert(
+    expanded_key: &[u8; 64],
+    public_key: &[u8; 32],
+    timestamp: u32,
+    app_data: &[u8],
+) -> Vec<u8> {
+    let signed_message = [&public_key[..], &timestamp.to_le_bytes(), app_data].concat();
+    let signature = sign_with_expanded_key(expanded_key, &signed_message);
+
+    let mut payload = Vec::with_capacity(APP_DATA_OFFSET + app_data.len());
+    payload.extend_from_slice(public_key);
+    payload.extend_from_slice(&timestamp.to_le_bytes());
+    payload.extend_from_slice(&signature);
+    payload.extend_from_slice(app_data);
+    payload
+}
+
+/// Wraps an advert payload in a flood-routed packet with an empty path, ready to transmit.
+pub fn wrap_in_packet(payload: &[u8]) -> Vec<u8> {
+    [&[FLOOD_ADVERT_HEADER, 0][..], payload].concat()
+}
+
+/// Strips the packet header and path from a flood-routed advert packet.
+pub fn unwrap_packet(packet: &[u8]) -> Result<&[u8]> {
+    let (&header, rest) = packet.split_first().context("Packet is empty")?;
+    if header != FLOOD_ADVERT_HEADER {
+        anyhow::bail!(
+            "Packet header 0x{:02X} is not a flood-routed advert",
+            header
+        );
+    }
+    let (&path_len, rest) = rest
+        .split_first()
+        .context("Packet is missing its path length")?;
+    rest.get(path_len as usize..)
+        .context("Packet is shorter than its path length")
+}
+
+/// Parses an advert payload and checks its signature.
+pub fn decode_advert(payload: &[u8]) -> Result<Advert> {
+    if payload.len() <= APP_DATA_OFFSET {
+        anyhow::bail!(
+            "Advert payload is {} bytes, expected more than {}",
+            payload.len(),
+            APP_DATA_OFFSET
+        );
+    }
+
+    let mut public_key = [0u8; 32];
+    public_key.copy_from_slice(&payload[..PUB_KEY_SIZE]);
+    let mut timestamp_bytes = [0u8; 4];
+    timestamp_bytes.copy_from_slice(&payload[PUB_KEY_SIZE..PUB_KEY_SIZE + TIMESTAMP_SIZE]);
+    let mut signature = [0u8; 64];
+    signature.copy_from_slice(&payload[PUB_KEY_SIZE + TIMESTAMP_SIZE..APP_DATA_OFFSET]);
+    let app_data_bytes = &payload[APP_DATA_OFFSET..];
+
+    let signed_message = [&public_key[..], &timestamp_bytes, app_data_bytes].concat();
+    let signature_valid = verify_signature(&public_key, &signed_message, &signature).is_ok();
+
+    Ok(Advert {
+        public_key,
+        timestamp: u32::from_le_bytes(timestamp_bytes),
+        signature,
+        app_data: AdvertAppData::decode(app_data_bytes)?,
+        signature_valid,
+    })
+}
+
+/// Builds and signs an advert for a stored key, printing hex or writing a binary file.
+pub fn run_advert(
+    keys_file: &str,
+    key_selector: Option<&KeySelector>,
+    app_data: &AdvertAppData,
+    timestamp: Option<u32>,
+    as_packet: bool,
+    output: Option<&str>,
+) -> Result<()> {
+    let key = select_key(keys_file, key_selector)?;
+    let private_key = decode_private_key(&key)?;
+    let public_key = decode_public_key(&key.public_key)?;
+
+    let timestamp = match timestamp {
+        Some(timestamp) => timestamp,
+        None => std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs() as u32,
+    };
+
+    let payload = build_advert(&private_key, &public_key, timestamp, &app_data.encode()?);
+    let bytes = if as_packet {
+        wrap_in_packet(&payload)
+    } else {
+        payload
+    };
+
+    match output {
+        Some(path) => {
+            std::fs::write(path, &bytes).with_context(|| format!("Failed to write {}", path))?;
+            println!("📡💾 Wrote {}-byte advert to {}", bytes.len(), path);
+        }
+        None => println!("{}", hex::encode(&bytes).to_uppercase()),
+    }
+    Ok(())
+}
+
+/// Decodes an advert (payload or packet) and prints its fields.
+/// An invalid signature is reported and then returned as an error for scripting.
+pub fn run_decode_advert(bytes: &[u8], is_packet: bool) -> Result<()> {
+    let payload = if is_packet {
+        unwrap_packet(bytes)?
+    } else {
+        bytes
+    };
+    let advert = decode_advert(payload)?;
+
+    println!("📡🔍 Advert:");
+    println!(
+        "   🔑 Public key: {}",
+        hex::encode(advert.public_key).to_uppercase()
+    );
+    println!("   🕐 Timestamp: {}", advert.timestamp);
+    println!("   🏷️ Type: {}", advert.app_data.node_type);
+    if let Some(name) = &advert.app_data.name {
+        println!("   📛 Name: {}", name);
+    }
+    if let Some((lat, lon)) = advert.app_data.location {
+        println!("   📍 Location: {:.6}, {:.6}", lat, lon);
+    }
+
+    println!(
+        "   ✍️ Signature: {}",
+        hex::encode(advert.signature).to_uppercase()
+    );
+
+    if !advert.signature_valid {
+        anyhow::bail!("Advert signature is invalid");
+    }
+    println!("   ✅ Signature is valid");
+    Ok(())
+}
+
+fn take_i32(data: &mut &[u8]) -> Option<i32> {
+    let (bytes, rest) = data.split_first_chunk::<4>()?;
+    *data = rest;
+    Some(i32::from_le_bytes(*bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{create_meshcore_private_key, extract_public_key_from_meshcore_key};
+
+    fn test_key() -> ([u8; 64], [u8; 32]) {
+        let private_key = create_meshcore_private_key(&[42u8; 32]);
+        let public_key = extract_public_key_from_meshcore_key(&private_key).unwrap();
+        (private_key, public_key)
+    }
+
+    #[test]
+    fn test_advert_round_trip() {
+        let (private_key, public_key) = test_key();
+        let app_data = AdvertAppData {
+            node_type: NodeType::Repeater,
+            location: Some((47.606209, -122.332069)),
+            name: Some("C0FFEE Rptr".to_string()),
+        };
+
+        let payload = build_advert(
+            &private_key,
+            &public_key,
+            1_700_000_000,
+            &app_data.encode().unwrap(),
+        );
+        let advert = decode_advert(&payload).unwrap();
+
+        assert!(advert.signature_valid);
+        assert_eq!(advert.public_key, public_key);
+        assert_eq!(advert.timestamp, 1_700_000_000);
+        assert_eq!(advert.app_data, app_data);
+    }
+
+    #[test]
+    fn test_app_data_layout() {
+        let app_data = AdvertAppData {
+            node_type: NodeType::Chat,
+            location: None,
+            name: Some("Bob".to_string()),
+        };
+        assert_eq!(app_data.encode().unwrap(), vec![0x81, b'B', b'o', b'b']);
+
+        let located = AdvertAppData {
+            node_type: NodeType::Room,
+            location: Some((1.0, -1.0)),
+            name: None,
+        };
+        let encoded = located.encode().unwrap();
+        assert_eq!(encoded[0], 0x13);
+        assert_eq!(&encoded[1..5], &1_000_000i32.to_le_bytes());
+        assert_eq!(&encoded[5..9], &(-1_000_000i32).to_le_bytes());
+    }
+
+    #[test]
+    fn test_app_data_limits() {
+        let too_long = AdvertAppData {
+            node_type: NodeType::Chat,
+            location: None,
+            name: Some("N".repeat(MAX_APP_DATA_SIZE)),
+        };
+        assert!(too_long.encode().is_err());
+
+        let bad_location = AdvertAppData {
+            node_type: NodeType::Chat,
+            location: Some((91.0, 0.0)),
+            name: None,
+        };
+        assert!(bad_location.encode().is_err());
+    }
+
+    #[test]
+    fn test_decode_skips_feature_words() {
+        let decoded = AdvertAppData::decode(&[0x82 | ADV_FEAT1_MASK, 0xAA, 0xBB, b'X']).unwrap();
+        assert_eq!(decoded.node_type, NodeType::Repeater);
+        assert_eq!(decoded.name.as_deref(), Some("X"));
+    }
+
+    #[test]
+    fn test_tampered_advert_fails_signature() {
+        let (private_key, public_key) = test_key();
+        let app_data = AdvertAppData {
+            node_type: NodeType::Chat,
+            location: None,
+            name: Some("Alice".to_string()),
+        };
+
+        let mut payload = build_advert(&private_key, &public_key, 1, &app_data.encode().unwrap());
+        let last = payload.len() - 1;
+        payload[last] = b'x';
+
+        assert!(!decode_advert(&payload).unwrap().signature_valid);
+        assert!(run_decode_advert(&payload, false).is_err());
+    }
+
+    #[test]
+    fn test_packet_wrapping() {
+        let (private_key, public_key) = test_key();
+        let app_data = AdvertAppData {
+            node_type: NodeType::Chat,
+            location: None,
+            name: None,
+        };
+        let payload = build_advert(&private_key, &public_key, 5, &app_data.encode().unwrap());
+
+        let packet = wrap_in_packet(&payload);
+        assert_eq!(packet[0], 0x11);
+        assert_eq!(packet[1], 0);
+        assert_eq!(unwrap_packet(&packet).unwrap(), &payload[..]);
+        assert!(unwrap_packet(&payload).is_err());
+    }
+
+    #[test]
+    fn test_node_type_parsing() {
+        assert_eq!("Repeater".parse::<NodeType>().unwrap(), NodeType::Repeater);
+        assert_eq!(NodeType::Room.to_string(), "room");
+        assert!("sensor".parse::<NodeType>().is_err());
+        assert!(decode_advert(&[0u8; 50]).is_err());
+    }
+}
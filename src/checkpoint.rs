@@ -0,0 +1,95 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Crash-safe JSON snapshot of long-running state. Each save goes to a temporary file that is
+/// synced and then renamed over the previous checkpoint, so a crash or power cut mid-write
+/// leaves the last good checkpoint in place instead of a truncated one.
+pub struct Checkpoint {
+    path: PathBuf,
+}
+
+impl Checkpoint {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns `None` when nothing has been checkpointed yet. A checkpoint that exists but
+    /// can't be read is an error: silently starting over would throw away queued work.
+    pub fn load<T: DeserializeOwned>(&self) -> Result<Option<T>> {
+        let contents = match std::fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", self.path.display()));
+            }
+        };
+        serde_json::from_str(&contents)
+            .map(Some)
+            .with_context(|| format!("Checkpoint {} is corrupted", self.path.display()))
+    }
+
+    pub fn save<T: Serialize>(&self, state: &T) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+
+        let mut temp_name = self.path.clone().into_os_string();
+        temp_name.push(".tmp");
+        let temp_path = PathBuf::from(temp_name);
+
+        let mut options = OpenOptions::new();
+        options.create(true).write(true).truncate(true);
+        // Checkpoints may name key store paths and patterns; keep them to their owner
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(&temp_path)
+            .with_context(|| format!("Failed to create {}", temp_path.display()))?;
+        file.write_all(serde_json::to_string_pretty(state)?.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+
+        std::fs::rename(&temp_path, &self.path)
+            .with_context(|| format!("Failed to replace {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = Checkpoint::new(dir.path().join("nested").join("state.json"));
+        assert_eq!(checkpoint.load::<Vec<u32>>().unwrap(), None);
+
+        checkpoint.save(&vec![1u32, 2, 3]).unwrap();
+        checkpoint.save(&vec![4u32]).unwrap();
+        assert_eq!(checkpoint.load::<Vec<u32>>().unwrap(), Some(vec![4]));
+
+        // Only the checkpoint itself is left behind
+        let entries = std::fs::read_dir(checkpoint.path().parent().unwrap()).unwrap();
+        assert_eq!(entries.count(), 1);
+    }
+
+    #[test]
+    fn test_corrupted_checkpoint_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = Checkpoint::new(dir.path().join("state.json"));
+        std::fs::write(checkpoint.path(), "{ not json").unwrap();
+        assert!(checkpoint.load::<Vec<u32>>().is_err());
+    }
+}
@@ -0,0 +1,1094 @@
+use crate::advert::NodeType;
+use crate::config::{Config, ReservedHashes};
+use crate::contact::ContactShare;
+use crate::distributed::TOKEN_ENV;
+use crate::hook::FoundHook;
+use crate::keyformat::{ExportFormat, ImportFormat};
+use crate::keystore::KeySelector;
+use crate::output::EventFormat;
+use crate::provision::CliScriptOptions;
+use crate::secure::WipeMethod;
+use crate::splitkey::{JOB_FILE, JOB_RESULTS_FILE, JOB_SECRET_FILE};
+use crate::types::NodeHashFilter;
+use crate::utils::parse_hash_list;
+use anyhow::{Context, Result};
+use clap::builder::RangedU64ValueParser;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use std::ffi::OsString;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// High-performance Ed25519 key searcher for generating custom public key patterns
+///
+/// Searches for Ed25519 keys with specific hex patterns in the public key. Uses multi-threaded
+/// CPU processing for maximum performance. `meshcore-keygen PATTERN` is short for
+/// `meshcore-keygen search PATTERN`.
+#[derive(Debug, Parser)]
+#[command(
+    name = "meshcore-keygen",
+    version = "0.1.0",
+    arg_required_else_help = true,
+    override_usage = "meshcore-keygen [OPTIONS] <COMMAND>\n       meshcore-keygen [OPTIONS] <PATTERN> [SEARCH OPTIONS]"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Commands,
+
+    #[command(flatten)]
+    pub global: GlobalArgs,
+}
+
+impl Cli {
+    /// Parses the process arguments, accepting the bare `PATTERN` shorthand for `search`.
+    pub fn parse_args() -> Self {
+        Self::parse_from(with_default_subcommand(std::env::args_os().collect()))
+    }
+}
+
+/// Inserts `search` when the first argument after the global options isn't a subcommand, help
+/// or version, so `meshcore-keygen [OPTIONS] PATTERN` is a search. Doing this in clap would
+/// make a subcommand name after a global option parse as PATTERN.
+fn with_default_subcommand(mut args: Vec<OsString>) -> Vec<OsString> {
+    let command = Cli::command();
+    // Some(takes a separate value) for a global option, None for anything else
+    let global_option = |token: &str| {
+        let (name, inline_value) = match token.split_once('=') {
+            Some((name, _)) => (name, true),
+            None => (token, false),
+        };
+        command
+            .get_arguments()
+            .filter(|arg| arg.is_global_set())
+            .find(|arg| match name.strip_prefix("--") {
+                Some(long) => arg.get_long() == Some(long),
+                None => {
+                    let mut short = name.strip_prefix('-').unwrap_or_default().chars();
+                    short.next().is_some_and(|c| arg.get_short() == Some(c))
+                        && short.next().is_none()
+                }
+            })
+            .map(|arg| !inline_value && arg.get_action().takes_values())
+    };
+
+    let mut index = 1;
+    while let Some(takes_value) = args
+        .get(index)
+        .and_then(|arg| arg.to_str())
+        .and_then(global_option)
+    {
+        index += 1 + usize::from(takes_value);
+    }
+
+    let explicit = match args.get(index) {
+        None => true,
+        Some(arg) => arg.to_str().is_some_and(|token| {
+            matches!(token, "help" | "-h" | "--help" | "-V" | "--version")
+                || command.find_subcommand(token).is_some()
+        }),
+    };
+    if !explicit {
+        args.insert(index, "search".into());
+    }
+    args
+}
+
+/// Output and config options that every subcommand accepts.
+#[derive(Debug, Args)]
+pub struct GlobalArgs {
+    /// Read settings from FILE instead of meshcore-keygen.toml and the user config
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Ignore all config files
+    #[arg(long, global = true, conflicts_with = "config")]
+    pub no_config: bool,
+
+    /// Only print found public keys and errors
+    ///
+    /// Suppresses banners, estimates and progress. A search prints one found public key per
+    /// line on stdout; private keys still only go to the key store.
+    #[arg(long, short = 'q', global = true)]
+    pub quiet: bool,
+
+    /// Write machine-readable progress events (jsonl) to stderr
+    ///
+    /// Writes one JSON object per line for the started, benchmark, progress, found and finished
+    /// events. Found events carry the public key only. Events go to stderr unless --events-fd
+    /// is given.
+    #[arg(long, global = true, value_name = "FORMAT")]
+    pub events: Option<EventFormat>,
+
+    /// Write events to this already open file descriptor, e.g. 3 with 3>events.jsonl
+    #[arg(long, global = true, value_name = "FD", requires = "events")]
+    pub events_fd: Option<u32>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Commands {
+    /// Search for keys whose public key matches a pattern
+    Search(SearchArgs),
+    /// Generate random MeshCore keys without a vanity pattern
+    ///
+    /// Generates COUNT valid MeshCore keys using the search worker pool, e.g. to provision a
+    /// batch of repeaters. Keys are appended to the key store.
+    Generate(GenerateArgs),
+    /// Check that every key in the key store is intact
+    ///
+    /// Re-derives the public key (and checks the seed, when stored) of every key store entry,
+    /// and optionally that each public key matches a pattern. Exits with an error if any entry
+    /// is damaged.
+    Verify(VerifyArgs),
+    /// Securely delete the key store
+    ///
+    /// Securely deletes the key store (meshcore-keys.txt unless configured otherwise) using
+    /// platform specific tooling.
+    Wipe(WipeArgs),
+    /// Measure key generation speed and cache the result
+    ///
+    /// Shows the cached benchmark for this machine, or measures and caches a new one. Results
+    /// live in $XDG_CACHE_HOME/meshcore-keygen/ and are keyed by CPU model, core count, program
+    /// version and backend.
+    Benchmark(BenchmarkArgs),
+    /// Export a stored key as OpenSSH, PKCS#8 or raw binary
+    ///
+    /// Writes a key from the key store as an OpenSSH public key line, an OpenSSH or PKCS#8
+    /// private key, or a raw binary blob. OpenSSH and PKCS#8 private keys are built from the
+    /// seed, so keys stored without one can only be exported as raw, raw-public or
+    /// openssh-public.
+    Export(ExportArgs),
+    /// Import an existing Ed25519 key into the key store
+    ///
+    /// Reads an OpenSSH ed25519 private key, PKCS#8 PEM/DER, raw or hex 32-byte seed, 64-byte
+    /// expanded key or a base64 export, converts it to the MeshCore expanded form and appends it
+    /// to the key store.
+    Import(ImportArgs),
+    /// Sign a message with a MeshCore expanded private key
+    ///
+    /// Signs MESSAGE with a 64-byte expanded key from the key store (or stdin) and prints the
+    /// signature as hex. The seed is not required.
+    Sign(SignArgs),
+    /// Verify an Ed25519 signature against a public key
+    VerifySig(VerifySigArgs),
+    /// Derive the X25519 shared secret with a peer's Ed25519 public key
+    ///
+    /// Converts our expanded private key and the peer's Ed25519 public key to Montgomery form
+    /// and prints the ECDH shared secret MeshCore uses for encrypted direct messages.
+    SharedSecret(SharedSecretArgs),
+    /// Convert an Ed25519 key to its X25519 form
+    ToX25519(ToX25519Args),
+    /// Build a signed MeshCore self-advert for a found key
+    ///
+    /// Builds a self-advert payload (public key, timestamp, signature, app data) signed with the
+    /// expanded key, and prints it as hex or writes it to a file.
+    Advert(AdvertArgs),
+    /// Decode a MeshCore advert and verify its signature
+    DecodeAdvert(DecodeAdvertArgs),
+    /// Print the MeshCore contact URI for a key, optionally as a QR code
+    ///
+    /// Builds the meshcore://contact/add link the companion app understands, so a node can be
+    /// added by scanning a QR code instead of pasting its public key by hand.
+    Contact(ContactArgs),
+    /// Install a stored key on a MeshCore companion radio over serial
+    ///
+    /// Speaks the companion radio frame protocol to replace the device's identity with a key
+    /// from the key store, then reads the self-info back to confirm the new public key. The
+    /// device's previous identity is lost.
+    FlashKey(FlashKeyArgs),
+    /// Coordinate a search across several machines
+    ///
+    /// Hands PATTERN out to every worker that joins, collects attempt counts and found keys,
+    /// and stores the keys in meshcore-keys.txt. Workers authenticate with a shared token;
+    /// found private keys travel sealed to a session key only this coordinator holds. Workers
+    /// may join and leave at any time.
+    Serve(ServeArgs),
+    /// Search for a coordinator started with serve
+    ///
+    /// Connects to a coordinator, runs the local worker pool on the pattern it hands out and
+    /// reports attempts and found keys back until it says stop. Found keys are sealed to the
+    /// coordinator and never stored locally.
+    Join(JoinArgs),
+    /// Create a split-key job that others can search without learning the key
+    ///
+    /// Picks a random scalar a and publishes A = aG with the pattern in a job file. Workers
+    /// look for an offset b where A + bG matches; only combine, with the secret kept here, can
+    /// turn that into the private key a + b.
+    RequestJob(RequestJobArgs),
+    /// Search a split-key job from request-job
+    ///
+    /// Finds offsets b for someone else's job and writes them to a results file to send back.
+    /// The offsets are useless without the requester's secret.
+    SearchJob(SearchJobArgs),
+    /// Turn split-key results into keys in the key store
+    Combine(CombineArgs),
+    /// Run searches queued with the queue command in the background
+    ///
+    /// Listens on a Unix socket for jobs and runs them on one shared worker pool, highest
+    /// priority first; a new job with a higher priority pauses the running one. The queue is
+    /// checkpointed, so jobs survive a restart. Members of the socket's group can submit jobs
+    /// too.
+    Daemon(DaemonArgs),
+    /// Submit, inspect and cancel daemon jobs
+    Queue {
+        #[command(subcommand)]
+        command: QueueCommand,
+    },
+    /// Inspect the configuration
+    ///
+    /// Settings are read from $XDG_CONFIG_HOME/meshcore-keygen/config.toml, then
+    /// ./meshcore-keygen.toml on top, then command-line flags on top of both.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct SearchArgs {
+    /// Hex pattern to search for in the public key (e.g., BEEF, *CAFE, B??F, BEEF,CAFE)
+    ///
+    /// The hexadecimal pattern to search for. Characters 0-9 and A-F match themselves, ? matches
+    /// any hex digit, a leading * anchors the pattern at the end of the key, and several
+    /// comma-separated patterns match if any of them does. Optional with --avoid-hashes-from.
+    /// Examples: BEEF, 123456, 00ABC, *CAFE, B??F, BEEF,CAFE
+    #[arg(
+        value_name = "PATTERN",
+        required_unless_present_any = ["avoid_hashes_from", "profile", "delete"]
+    )]
+    pub pattern: Option<String>,
+
+    /// Search the patterns of a profile from the config file
+    #[arg(long, value_name = "NAME", conflicts_with = "pattern")]
+    pub profile: Option<String>,
+
+    /// Maximum number of keys to find before stopping (default: 1, or the profile's max-keys)
+    ///
+    /// Stop searching after finding this many keys. Use 0 for unlimited search.
+    #[arg(long, short = 'n', value_name = "NUMBER")]
+    pub max_keys: Option<usize>,
+
+    #[command(flatten)]
+    pub hashes: HashFilterArgs,
+
+    #[command(flatten)]
+    pub threads: ThreadsArgs,
+
+    #[command(flatten)]
+    pub keys: KeysFileArgs,
+
+    #[command(flatten)]
+    pub contact: ContactShareArgs,
+
+    #[command(flatten)]
+    pub cli_script: CliScriptArgs,
+
+    #[command(flatten)]
+    pub found_hook: FoundHookArgs,
+
+    /// Show a full-screen dashboard while searching
+    ///
+    /// Replaces the progress line with a full-screen dashboard: per-thread rates, a throughput
+    /// sparkline, found public keys, the closest partial match and the chance of a match so
+    /// far. Press q to stop.
+    #[arg(long)]
+    pub tui: bool,
+
+    /// Serve OpenMetrics at http://ADDR/metrics during the search, e.g. 127.0.0.1:9898
+    ///
+    /// Starts a small HTTP server for Prometheus-compatible scrapers, exposing attempts,
+    /// matches, keys/sec, per-thread rates, elapsed time and the match probability. Bind to
+    /// 127.0.0.1 unless the port is firewalled.
+    #[arg(long, value_name = "ADDR")]
+    pub metrics_listen: Option<SocketAddr>,
+
+    // The old spelling of `wipe`, kept for scripts that still use it
+    /// Securely delete the key store and exit (same as `wipe`)
+    #[arg(
+        long,
+        short = 'd',
+        hide = true,
+        conflicts_with_all = ["pattern", "profile"]
+    )]
+    pub delete: bool,
+
+    /// auto (overwrite tools, else plain delete), tools-only or unlink
+    #[arg(long, value_name = "METHOD", requires = "delete", hide = true)]
+    pub wipe_method: Option<WipeMethod>,
+}
+
+#[derive(Debug, Args)]
+pub struct GenerateArgs {
+    /// Number of keys to generate
+    #[arg(long, short = 'c', value_name = "N", default_value = "1")]
+    pub count: usize,
+
+    /// Give every key in the batch a different node hash (first byte)
+    #[arg(long)]
+    pub unique_hashes: bool,
+
+    #[command(flatten)]
+    pub hashes: HashFilterArgs,
+
+    #[command(flatten)]
+    pub threads: ThreadsArgs,
+
+    #[command(flatten)]
+    pub keys: KeysFileArgs,
+
+    #[command(flatten)]
+    pub contact: ContactShareArgs,
+
+    #[command(flatten)]
+    pub cli_script: CliScriptArgs,
+
+    #[command(flatten)]
+    pub found_hook: FoundHookArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct VerifyArgs {
+    /// Also require every public key to match this pattern (same syntax as a search)
+    #[arg(long, value_name = "PATTERN")]
+    pub pattern: Option<String>,
+
+    #[command(flatten)]
+    pub keys: KeysFileArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct WipeArgs {
+    /// auto (overwrite tools, else plain delete), tools-only or unlink
+    #[arg(long, value_name = "METHOD")]
+    pub wipe_method: Option<WipeMethod>,
+
+    #[command(flatten)]
+    pub keys: KeysFileArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct BenchmarkArgs {
+    /// Re-run the benchmark even if a valid cached result exists
+    #[arg(long, short = 'f')]
+    pub force: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    pub key: StoredKeyArgs,
+
+    /// Output format: openssh-public, openssh, pkcs8, raw, raw-seed or raw-public
+    #[arg(long, short = 'f', value_name = "FORMAT")]
+    pub format: ExportFormat,
+
+    /// File to create (private keys get 0600); stdout when omitted
+    #[arg(long, short = 'o', value_name = "FILE")]
+    pub output: Option<String>,
+
+    /// OpenSSH key comment (default: meshcore-<first 8 hex of public key>)
+    #[arg(long, value_name = "TEXT")]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ImportArgs {
+    /// Key file to import, or - to read from stdin
+    #[arg(value_name = "FILE")]
+    pub input: String,
+
+    /// Input format: auto, openssh, pkcs8, seed, expanded or base64
+    #[arg(long, short = 'f', value_name = "FORMAT", default_value = "auto")]
+    pub format: ImportFormat,
+
+    #[command(flatten)]
+    pub keys: KeysFileArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct SignArgs {
+    #[command(flatten)]
+    pub key: StoredKeyArgs,
+
+    #[command(flatten)]
+    pub message: MessageArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct VerifySigArgs {
+    #[command(flatten)]
+    pub key: PublicKeyArgs,
+
+    /// Signature to verify (128 hex characters)
+    #[arg(long, short = 's', value_name = "HEX")]
+    pub signature: String,
+
+    #[command(flatten)]
+    pub message: MessageArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct SharedSecretArgs {
+    #[command(flatten)]
+    pub key: StoredKeyArgs,
+
+    /// Peer's Ed25519 public key (64 hex characters)
+    #[arg(long, value_name = "HEX")]
+    pub peer: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ToX25519Args {
+    #[command(flatten)]
+    pub key: PublicKeyArgs,
+
+    /// Also print the X25519 private key of the selected key
+    #[arg(long, conflicts_with = "public_key")]
+    pub private: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct AdvertArgs {
+    #[command(flatten)]
+    pub key: StoredKeyArgs,
+
+    /// Node name to advertise
+    #[arg(long, value_name = "NAME")]
+    pub name: Option<String>,
+
+    /// Node type: chat, repeater or room
+    #[arg(long = "type", value_name = "TYPE", default_value = "chat")]
+    pub node_type: NodeType,
+
+    /// Latitude to advertise
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        allow_negative_numbers = true,
+        requires = "lon"
+    )]
+    pub lat: Option<f64>,
+
+    /// Longitude to advertise
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        allow_negative_numbers = true,
+        requires = "lat"
+    )]
+    pub lon: Option<f64>,
+
+    /// Advert timestamp (defaults to now)
+    #[arg(long, value_name = "UNIX_SECONDS")]
+    pub timestamp: Option<u32>,
+
+    #[command(flatten)]
+    pub packet: PacketArgs,
+
+    /// Write the advert as binary to PATH instead of printing hex
+    #[arg(long, short = 'o', value_name = "PATH")]
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct DecodeAdvertArgs {
+    /// Advert bytes as hex
+    #[arg(value_name = "HEX", required_unless_present = "input")]
+    pub advert: Option<String>,
+
+    /// Read the advert from a binary file
+    #[arg(long, short = 'i', value_name = "PATH", conflicts_with = "advert")]
+    pub input: Option<String>,
+
+    #[command(flatten)]
+    pub packet: PacketArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct ContactArgs {
+    #[command(flatten)]
+    pub key: PublicKeyArgs,
+
+    /// Contact name (default: first 8 hex characters of the public key)
+    #[arg(long, value_name = "NAME")]
+    pub name: Option<String>,
+
+    /// Node type: chat, repeater or room
+    #[arg(long = "type", value_name = "TYPE", default_value = "chat")]
+    pub node_type: NodeType,
+
+    /// Draw the QR code in the terminal
+    #[arg(long)]
+    pub qr: bool,
+
+    /// Write the QR code to a .png or .svg file
+    #[arg(long, value_name = "FILE")]
+    pub qr_file: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct FlashKeyArgs {
+    /// Serial port of the radio, e.g. /dev/ttyUSB0 or COM3
+    #[arg(long, value_name = "DEVICE")]
+    pub port: String,
+
+    /// Serial baud rate
+    #[arg(long, value_name = "RATE", default_value = "115200")]
+    pub baud: u32,
+
+    #[command(flatten)]
+    pub key: StoredKeyArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct ServeArgs {
+    /// Hex pattern to hand out, same syntax as a local search
+    #[arg(value_name = "PATTERN", required_unless_present = "avoid_hashes_from")]
+    pub pattern: Option<String>,
+
+    /// Stop all workers after this many keys (0 for unlimited)
+    #[arg(long, short = 'n', value_name = "NUMBER", default_value = "1")]
+    pub max_keys: usize,
+
+    #[command(flatten)]
+    pub hashes: HashFilterArgs,
+
+    #[command(flatten)]
+    pub keys: KeysFileArgs,
+
+    /// Address to accept workers on
+    #[arg(long, value_name = "ADDR", default_value = "0.0.0.0:7447")]
+    pub listen: SocketAddr,
+
+    #[command(flatten)]
+    pub token: TokenArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct JoinArgs {
+    /// Coordinator address
+    #[arg(value_name = "HOST[:PORT]")]
+    pub addr: String,
+
+    #[command(flatten)]
+    pub threads: ThreadsArgs,
+
+    /// Name the coordinator shows for this worker
+    #[arg(long, value_name = "NAME")]
+    pub name: Option<String>,
+
+    #[command(flatten)]
+    pub token: TokenArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct RequestJobArgs {
+    /// Hex pattern the final public key must match
+    #[arg(value_name = "PATTERN", required_unless_present = "avoid_hashes_from")]
+    pub pattern: Option<String>,
+
+    #[command(flatten)]
+    pub hashes: HashFilterArgs,
+
+    /// Job file to create and hand to workers
+    #[arg(long, value_name = "FILE", default_value = JOB_FILE)]
+    pub job: String,
+
+    #[command(flatten)]
+    pub secret: JobSecretArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct SearchJobArgs {
+    /// Job file from request-job
+    #[arg(value_name = "JOB_FILE", default_value = JOB_FILE)]
+    pub job: String,
+
+    /// Number of matches to find
+    #[arg(
+        long,
+        short = 'n',
+        value_name = "NUMBER",
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+        default_value = "1"
+    )]
+    pub max_keys: usize,
+
+    #[command(flatten)]
+    pub threads: ThreadsArgs,
+
+    /// Results file to create
+    #[arg(long, short = 'o', value_name = "FILE", default_value = JOB_RESULTS_FILE)]
+    pub output: String,
+}
+
+#[derive(Debug, Args)]
+pub struct CombineArgs {
+    /// Results file from search-job
+    #[arg(value_name = "RESULTS_FILE", default_value = JOB_RESULTS_FILE)]
+    pub results: String,
+
+    #[command(flatten)]
+    pub secret: JobSecretArgs,
+
+    #[command(flatten)]
+    pub keys: KeysFileArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct DaemonArgs {
+    #[command(flatten)]
+    pub socket: SocketArgs,
+
+    /// Where the queue checkpoint and default job key stores live
+    /// (default: $XDG_STATE_HOME/meshcore-keygen)
+    #[arg(long, value_name = "DIR")]
+    pub state_dir: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub threads: ThreadsArgs,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum QueueCommand {
+    /// Queue a search
+    Submit(QueueSubmitArgs),
+    /// Show all jobs, or one
+    Status {
+        /// Job number printed by queue submit
+        #[arg(value_name = "ID")]
+        id: Option<u64>,
+
+        #[command(flatten)]
+        socket: SocketArgs,
+    },
+    /// Stop a queued or running job
+    Cancel(JobIdArgs),
+    /// List the public keys a job found and where they are stored
+    Results(JobIdArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct QueueSubmitArgs {
+    /// Hex pattern to search for, same syntax as a local search
+    #[arg(value_name = "PATTERN")]
+    pub pattern: String,
+
+    /// Number of keys to find
+    #[arg(
+        long,
+        short = 'n',
+        value_name = "NUMBER",
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+        default_value = "1"
+    )]
+    pub max_keys: usize,
+
+    /// Give up after this much search time, e.g. 90m or 2h
+    #[arg(long, value_name = "DURATION", value_parser = parse_budget)]
+    pub budget: Option<Duration>,
+
+    /// Higher priorities run first and pause lower ones
+    #[arg(
+        long,
+        value_name = "N",
+        allow_negative_numbers = true,
+        default_value = "0"
+    )]
+    pub priority: i32,
+
+    #[command(flatten)]
+    pub hashes: HashFilterArgs,
+
+    /// Key store name in the daemon's state dir, e.g. repeaters.txt (default: job-ID-keys.txt)
+    #[arg(long, short = 'o', value_name = "NAME")]
+    pub output: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub socket: SocketArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct JobIdArgs {
+    /// Job number printed by queue submit
+    #[arg(value_name = "ID")]
+    pub id: u64,
+
+    #[command(flatten)]
+    pub socket: SocketArgs,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Print the merged settings, defaults included, as TOML
+    Show,
+}
+
+/// Node hashes to stay away from: the ones already in a mesh and, optionally, the reserved ones.
+#[derive(Debug, Args)]
+pub struct HashFilterArgs {
+    /// Only accept keys whose node hash (first byte) is not used in FILE
+    ///
+    /// Reads existing node public keys or 2-character node hashes from FILE and only accepts
+    /// keys whose first byte is unused, so paths through the mesh stay unambiguous.
+    #[arg(long, value_name = "FILE")]
+    pub avoid_hashes_from: Option<String>,
+
+    // Reserved hashes are allowed unless asked otherwise, so explicit 00/FF patterns keep
+    // working. The config can flip the default, so both directions have a flag.
+    /// Reject keys whose node hash is reserved by the firmware (00 or FF)
+    #[arg(long)]
+    pub skip_reserved_hashes: bool,
+
+    /// Accept reserved node hashes even if the config says to skip them
+    #[arg(long, conflicts_with = "skip_reserved_hashes")]
+    pub allow_reserved_hashes: bool,
+}
+
+impl HashFilterArgs {
+    /// Builds the node hash filter from --avoid-hashes-from and the reserved hash policy.
+    pub fn load(&self, settings: &Config) -> Result<NodeHashFilter> {
+        let mut excluded_hashes = match &self.avoid_hashes_from {
+            Some(path) => parse_hash_list(
+                &std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read {}", path))?,
+            )
+            .with_context(|| format!("Invalid node hash list {}", path))?,
+            None => NodeHashFilter::default(),
+        };
+
+        let skip_reserved = if self.skip_reserved_hashes {
+            true
+        } else if self.allow_reserved_hashes {
+            false
+        } else {
+            settings.reserved_hashes() == ReservedHashes::Skip
+        };
+        if skip_reserved {
+            excluded_hashes.exclude_reserved();
+        }
+
+        Ok(excluded_hashes)
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ThreadsArgs {
+    /// Worker threads to run (default: all cores but one)
+    #[arg(
+        long,
+        short = 't',
+        value_name = "N",
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub threads: Option<usize>,
+}
+
+/// Points key-consuming commands at a key store other than the default.
+#[derive(Debug, Args)]
+pub struct KeysFileArgs {
+    /// Key store to use (default: meshcore-keys.txt, or keys-file from the config)
+    #[arg(long, value_name = "PATH")]
+    pub keys_file: Option<String>,
+}
+
+impl KeysFileArgs {
+    /// --keys-file, or the configured key store.
+    pub fn path<'a>(&'a self, settings: &'a Config) -> &'a str {
+        self.keys_file.as_deref().unwrap_or(settings.keys_file())
+    }
+}
+
+/// Picks a key store entry by public key prefix or position; `-` reads the key from stdin.
+#[derive(Debug, Args)]
+pub struct KeySelectorArgs {
+    /// Key to use: public key prefix in the key store, or - for stdin
+    #[arg(long, short = 'k', value_name = "PREFIX")]
+    pub key: Option<String>,
+
+    /// Key to use by 1-based position in the key store
+    #[arg(
+        long,
+        value_name = "N",
+        conflicts_with = "key",
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub index: Option<usize>,
+}
+
+impl KeySelectorArgs {
+    pub fn selector(&self) -> Option<KeySelector> {
+        match (&self.key, self.index) {
+            (_, Some(index)) => Some(KeySelector::Index(index)),
+            (Some(key), None) if key == "-" => Some(KeySelector::Stdin),
+            (Some(prefix), None) => Some(KeySelector::Prefix(prefix.clone())),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Selects the key store entry a command works on.
+#[derive(Debug, Args)]
+pub struct StoredKeyArgs {
+    #[command(flatten)]
+    pub selector: KeySelectorArgs,
+
+    #[command(flatten)]
+    pub keys: KeysFileArgs,
+}
+
+/// Lets public-key-only commands work on keys that aren't in the key store.
+#[derive(Debug, Args)]
+pub struct PublicKeyArgs {
+    #[command(flatten)]
+    pub selector: KeySelectorArgs,
+
+    /// Ed25519 public key to use instead of a key store entry (64 hex characters)
+    #[arg(
+        long,
+        short = 'p',
+        value_name = "HEX",
+        conflicts_with_all = ["key", "index"]
+    )]
+    pub public_key: Option<String>,
+
+    #[command(flatten)]
+    pub keys: KeysFileArgs,
+}
+
+/// Options that print a contact URI (and QR code) for every found key.
+#[derive(Debug, Args)]
+pub struct ContactShareArgs {
+    /// Print the MeshCore contact URI for every found key
+    #[arg(long)]
+    pub contact: bool,
+
+    /// Also draw the contact URI as a QR code in the terminal (implies --contact)
+    #[arg(long)]
+    pub qr: bool,
+
+    /// Contact name in the URI (default: first 8 hex characters of the public key)
+    #[arg(long, value_name = "NAME")]
+    pub contact_name: Option<String>,
+
+    /// Contact node type in the URI: chat, repeater or room
+    #[arg(long, value_name = "TYPE", default_value = "chat")]
+    pub contact_type: NodeType,
+}
+
+impl ContactShareArgs {
+    /// The search-time contact sharing options; `--qr` implies `--contact`.
+    pub fn contact_share(&self) -> Option<ContactShare> {
+        if !self.contact && !self.qr {
+            return None;
+        }
+
+        Some(ContactShare {
+            name: self.contact_name.clone(),
+            node_type: self.contact_type,
+            qr: self.qr,
+        })
+    }
+}
+
+/// Options that write a repeater/room server CLI provisioning script for every found key.
+#[derive(Debug, Args)]
+pub struct CliScriptArgs {
+    /// Write a ready-to-paste repeater CLI script per found key into DIR
+    ///
+    /// Writes DIR/<public key>-cli.txt for every found key, with commands that set the
+    /// private key, the node name, the radio settings from --radio-profile and an admin
+    /// password placeholder. The scripts contain the private key.
+    #[arg(long, value_name = "DIR")]
+    pub emit_cli_script: Option<String>,
+
+    /// Node name for the script: {public}, {public:N}, {hash} and {n} are replaced (default: Repeater-{hash})
+    #[arg(long, value_name = "TEMPLATE", requires = "emit_cli_script")]
+    pub name_template: Option<String>,
+
+    /// key = value file with freq, bw, sf, cr, tx, lat and lon for the script
+    #[arg(long, value_name = "FILE", requires = "emit_cli_script")]
+    pub radio_profile: Option<String>,
+}
+
+impl CliScriptArgs {
+    /// --emit-cli-script with its template and radio profile.
+    pub fn options(&self) -> Result<Option<CliScriptOptions>> {
+        self.emit_cli_script
+            .as_deref()
+            .map(|output_dir| {
+                CliScriptOptions::new(
+                    output_dir,
+                    self.name_template.as_deref(),
+                    self.radio_profile.as_deref(),
+                )
+            })
+            .transpose()
+    }
+}
+
+/// A command to run for every found key.
+#[derive(Debug, Args)]
+pub struct FoundHookArgs {
+    /// Run COMMAND (through the shell) for every found key
+    ///
+    /// Runs COMMAND through the shell for every found key, with MESHCORE_PUBLIC_KEY,
+    /// MESHCORE_NODE_HASH, MESHCORE_PATTERN and MESHCORE_KEY_INDEX in the environment and the
+    /// private key as one hex line on stdin. The private key is never put in arguments or the
+    /// environment. A failing or hanging hook is logged and the search carries on.
+    #[arg(long, value_name = "COMMAND")]
+    pub on_found: Option<String>,
+
+    /// Kill the --on-found command after this long
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        requires = "on_found",
+        value_parser = clap::value_parser!(u64).range(1..),
+        default_value = "30"
+    )]
+    pub on_found_timeout: u64,
+}
+
+impl FoundHookArgs {
+    pub fn hook(&self) -> Option<FoundHook> {
+        self.on_found.as_ref().map(|command| FoundHook {
+            command: command.clone(),
+            timeout: Duration::from_secs(self.on_found_timeout),
+        })
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct MessageArgs {
+    /// Treat MESSAGE as hex-encoded bytes instead of text
+    #[arg(long)]
+    pub hex: bool,
+
+    /// Message to sign or verify
+    #[arg(value_name = "MESSAGE")]
+    pub message: String,
+}
+
+/// Adverts are handled as bare payloads unless the flood packet header is requested.
+#[derive(Debug, Args)]
+pub struct PacketArgs {
+    /// Include the flood-routed packet header and empty path around the payload
+    #[arg(long)]
+    pub packet: bool,
+}
+
+/// The shared secret for serve/join, read from a file or the environment but never from argv.
+#[derive(Debug, Args)]
+pub struct TokenArgs {
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = format!("Read the shared token from FILE instead of ${}", TOKEN_ENV)
+    )]
+    pub token_file: Option<String>,
+}
+
+/// The requester's half of a split-key job; as sensitive as the keys it will unlock.
+#[derive(Debug, Args)]
+pub struct JobSecretArgs {
+    /// Job secret kept by the requester (created owner-only)
+    #[arg(long, value_name = "FILE", default_value = JOB_SECRET_FILE)]
+    pub secret: String,
+}
+
+/// Control socket shared by the daemon and its clients.
+#[derive(Debug, Args)]
+pub struct SocketArgs {
+    /// Daemon control socket (default: $XDG_RUNTIME_DIR/meshcore-keygen.sock)
+    #[arg(long, value_name = "PATH")]
+    pub socket: Option<PathBuf>,
+}
+
+#[cfg(unix)]
+fn parse_budget(text: &str) -> Result<Duration> {
+    crate::daemon::parse_budget(text)
+}
+
+#[cfg(not(unix))]
+fn parse_budget(_text: &str) -> Result<Duration> {
+    anyhow::bail!("The daemon is only supported on Unix")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(with_default_subcommand(
+            std::iter::once("meshcore-keygen")
+                .chain(args.iter().copied())
+                .map(OsString::from)
+                .collect(),
+        ))
+    }
+
+    fn search(cli: Cli) -> SearchArgs {
+        match cli.command {
+            Commands::Search(search) => search,
+            other => panic!("expected a search, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_bare_pattern_is_a_search() {
+        let cli = parse(&["BEEF", "-n", "3", "--quiet"]).unwrap();
+        assert!(cli.global.quiet);
+        let search_args = search(cli);
+        assert_eq!(search_args.pattern.as_deref(), Some("BEEF"));
+        assert_eq!(search_args.max_keys, Some(3));
+
+        let cli = parse(&["--events", "jsonl", "-q", "BEEF"]).unwrap();
+        assert!(cli.global.quiet);
+        assert_eq!(search(cli).pattern.as_deref(), Some("BEEF"));
+
+        let cli = parse(&["search", "BEEF", "-n", "3"]).unwrap();
+        assert_eq!(search(cli).pattern.as_deref(), Some("BEEF"));
+
+        // A pattern is needed unless something else says what to look for
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["--avoid-hashes-from", "mesh.txt"]).is_ok());
+        assert!(parse(&["search"]).is_err());
+    }
+
+    #[test]
+    fn test_delete_flag_still_works() {
+        let search_args = search(parse(&["--delete", "--wipe-method", "unlink"]).unwrap());
+        assert!(search_args.delete);
+        assert_eq!(search_args.wipe_method, Some(WipeMethod::Unlink));
+        assert!(parse(&["BEEF", "--delete"]).is_err());
+        assert!(parse(&["--wipe-method", "unlink"]).is_err());
+
+        let cli = parse(&["wipe", "--wipe-method", "tools-only"]).unwrap();
+        assert!(matches!(cli.command, Commands::Wipe(_)));
+    }
+
+    #[test]
+    fn test_global_options_before_a_subcommand() {
+        let cli = parse(&["--quiet", "search", "X"]).unwrap();
+        assert!(cli.global.quiet);
+        assert_eq!(search(cli).pattern.as_deref(), Some("X"));
+
+        let cli = parse(&["--events", "jsonl", "generate", "--count", "1"]).unwrap();
+        assert_eq!(cli.global.events, Some(EventFormat::JsonLines));
+        assert!(matches!(cli.command, Commands::Generate(ref args) if args.count == 1));
+
+        let cli = parse(&["--no-config", "--events=jsonl", "benchmark", "--force"]).unwrap();
+        assert!(cli.global.no_config);
+        assert!(matches!(cli.command, Commands::Benchmark(ref args) if args.force));
+
+        // Help for a subcommand, not the top-level help
+        let err = parse(&["--no-config", "benchmark", "--help"]).unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::DisplayHelp);
+        assert!(err.to_string().contains("--force"));
+    }
+}
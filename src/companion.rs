@@ -0,0 +1,382 @@
+use crate::keystore::{KeySelector, decode_private_key, select_key};
+use anyhow::{Context, Result};
+use std::io::{Read, Write};
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// Serial frames sent to the radio start with `<`, frames from the radio with `>`,
+/// followed by a little-endian u16 payload length.
+const FRAME_TO_RADIO: u8 = b'<';
+const FRAME_FROM_RADIO: u8 = b'>';
+
+/// Largest frame the companion firmware sends or accepts.
+const MAX_FRAME_SIZE: usize = 172;
+
+const CMD_APP_START: u8 = 1;
+const CMD_IMPORT_PRIVATE_KEY: u8 = 24;
+
+const RESP_CODE_OK: u8 = 0;
+const RESP_CODE_ERR: u8 = 1;
+const RESP_CODE_SELF_INFO: u8 = 5;
+const RESP_CODE_DISABLED: u8 = 15;
+
+/// Codes at or above this are unsolicited pushes (new messages, adverts, ...) that can
+/// arrive between a command and its response.
+const PUSH_CODE_MIN: u8 = 0x80;
+
+/// Fixed-size part of the self-info frame that precedes the node name.
+const SELF_INFO_NAME_OFFSET: usize = 58;
+
+/// Protocol version and name we announce in `CMD_APP_START`.
+const APP_VERSION: u8 = 1;
+const APP_NAME: &str = "meshcore-keygen";
+
+/// The identity a companion radio reports in reply to `CMD_APP_START`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfInfo {
+    pub node_type: u8,
+    pub public_key: [u8; 32],
+    pub name: String,
+}
+
+impl SelfInfo {
+    /// Parses a `RESP_CODE_SELF_INFO` frame. Only the public key is required; older
+    /// firmware sends a shorter frame, in which case the name is left empty.
+    fn decode(frame: &[u8]) -> Result<Self> {
+        if frame.first() != Some(&RESP_CODE_SELF_INFO) || frame.len() < 36 {
+            anyhow::bail!("Malformed self-info frame ({} bytes)", frame.len());
+        }
+
+        let mut public_key = [0u8; 32];
+        public_key.copy_from_slice(&frame[4..36]);
+        let name = frame
+            .get(SELF_INFO_NAME_OFFSET..)
+            .map(|name| {
+                let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+                String::from_utf8_lossy(&name[..end]).into_owned()
+            })
+            .unwrap_or_default();
+
+        Ok(SelfInfo {
+            node_type: frame[1],
+            public_key,
+            name,
+        })
+    }
+}
+
+/// Minimal client for the companion radio frame protocol over any byte stream,
+/// so the same code drives a serial port, a pseudo-terminal or an in-memory mock.
+pub struct CompanionClient<T: Read + Write> {
+    stream: T,
+}
+
+impl<T: Read + Write> CompanionClient<T> {
+    pub fn new(stream: T) -> Self {
+        Self { stream }
+    }
+
+    fn send_frame(&mut self, payload: &[u8]) -> Result<()> {
+        if payload.len() > MAX_FRAME_SIZE {
+            anyhow::bail!(
+                "Frame of {} bytes exceeds the protocol limit",
+                payload.len()
+            );
+        }
+
+        let mut frame = Zeroizing::new(Vec::with_capacity(payload.len() + 3));
+        frame.push(FRAME_TO_RADIO);
+        frame.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+        frame.extend_from_slice(payload);
+        self.stream.write_all(&frame)?;
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    /// Reads the next frame from the radio. Bytes before the start marker are skipped,
+    /// since firmware built with debug logging interleaves plain text on the same port.
+    fn read_frame(&mut self) -> Result<Vec<u8>> {
+        let mut byte = [0u8; 1];
+        loop {
+            self.stream
+                .read_exact(&mut byte)
+                .context("No response from device")?;
+            if byte[0] != FRAME_FROM_RADIO {
+                continue;
+            }
+
+            let mut length = [0u8; 2];
+            self.stream.read_exact(&mut length)?;
+            let length = u16::from_le_bytes(length) as usize;
+            if length == 0 || length > MAX_FRAME_SIZE {
+                continue;
+            }
+
+            let mut frame = vec![0u8; length];
+            self.stream.read_exact(&mut frame)?;
+            return Ok(frame);
+        }
+    }
+
+    /// Sends a command and returns its response, skipping unsolicited push frames.
+    fn command(&mut self, payload: &[u8]) -> Result<Vec<u8>> {
+        self.send_frame(payload)?;
+        loop {
+            let frame = self.read_frame()?;
+            if frame[0] < PUSH_CODE_MIN {
+                return Ok(frame);
+            }
+        }
+    }
+
+    /// Starts an app session; the radio answers with its current identity.
+    pub fn app_start(&mut self) -> Result<SelfInfo> {
+        let mut payload = vec![CMD_APP_START, APP_VERSION, 0, 0, 0, 0, 0, 0];
+        payload.extend_from_slice(APP_NAME.as_bytes());
+
+        SelfInfo::decode(&self.command(&payload)?)
+    }
+
+    /// Replaces the radio's identity with a 64-byte MeshCore expanded key.
+    pub fn import_private_key(&mut self, expanded_key: &[u8; 64]) -> Result<()> {
+        let mut payload = Zeroizing::new(Vec::with_capacity(65));
+        payload.push(CMD_IMPORT_PRIVATE_KEY);
+        payload.extend_from_slice(expanded_key);
+
+        let response = self.command(&payload)?;
+        match response[0] {
+            RESP_CODE_OK => Ok(()),
+            RESP_CODE_DISABLED => anyhow::bail!(
+                "Device firmware was built without private key import (ENABLE_PRIVATE_KEY_IMPORT)"
+            ),
+            RESP_CODE_ERR => anyhow::bail!(
+                "Device rejected the key (error code {})",
+                response.get(1).copied().unwrap_or_default()
+            ),
+            code => anyhow::bail!("Unexpected response code {} to key import", code),
+        }
+    }
+}
+
+/// Installs a key from the key store on a companion radio and reads its identity back.
+/// The device's previous public key is shown first, since its old identity is lost.
+pub fn run_flash_key(
+    port: &str,
+    baud_rate: u32,
+    keys_file: &str,
+    key_selector: Option<&KeySelector>,
+) -> Result<()> {
+    let key = select_key(keys_file, key_selector)?;
+    let private_key = decode_private_key(&key)?;
+
+    let serial = serialport::new(port, baud_rate)
+        .timeout(Duration::from_secs(5))
+        .open()
+        .with_context(|| format!("Failed to open serial port {}", port))?;
+    flash_key(CompanionClient::new(serial), &private_key, &key.public_key)
+}
+
+fn flash_key<T: Read + Write>(
+    mut client: CompanionClient<T>,
+    private_key: &[u8; 64],
+    public_key: &str,
+) -> Result<()> {
+    let before = client.app_start()?;
+    let before_public = hex::encode_upper(before.public_key);
+    println!("📻🔑 Connected to '{}' ({})", before.name, before_public);
+
+    if before_public == public_key {
+        println!("🤷‍♀️💭 Device already uses key {}", public_key);
+        return Ok(());
+    }
+
+    client.import_private_key(private_key)?;
+
+    let after = client.app_start()?;
+    let after_public = hex::encode_upper(after.public_key);
+    if after_public != public_key {
+        anyhow::bail!(
+            "Device accepted the key but now reports {} instead of {}",
+            after_public,
+            public_key
+        );
+    }
+
+    println!("✅📻 Key installed, device now reports {}", after_public);
+    println!(
+        "   ⚠️ The previous identity {} is gone from the device",
+        before_public
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{create_meshcore_private_key, extract_public_key_from_meshcore_key};
+    use std::collections::VecDeque;
+
+    /// In-process stand-in for a companion radio. Frames written to it are answered
+    /// the way the firmware would, and the replies are queued for reading.
+    struct MockRadio {
+        identity: [u8; 64],
+        import_enabled: bool,
+        input: Vec<u8>,
+        output: VecDeque<u8>,
+    }
+
+    impl MockRadio {
+        fn new(seed: u8) -> Self {
+            Self {
+                identity: create_meshcore_private_key(&[seed; 32]),
+                import_enabled: true,
+                input: Vec::new(),
+                output: VecDeque::new(),
+            }
+        }
+
+        fn public_key(&self) -> [u8; 32] {
+            extract_public_key_from_meshcore_key(&self.identity).unwrap()
+        }
+
+        fn reply(&mut self, payload: &[u8]) {
+            // Debug noise and a push frame, which the client must skip over
+            self.output.extend(b"DEBUG: rx\r\n");
+            self.output.extend([FRAME_FROM_RADIO, 1, 0, 0x83]);
+            self.output.push_back(FRAME_FROM_RADIO);
+            self.output.extend((payload.len() as u16).to_le_bytes());
+            self.output.extend(payload);
+        }
+
+        fn handle(&mut self, command: &[u8]) {
+            match command[0] {
+                CMD_APP_START => {
+                    let mut frame = vec![RESP_CODE_SELF_INFO, 1, 22, 22];
+                    frame.extend(self.public_key());
+                    frame.resize(SELF_INFO_NAME_OFFSET, 0);
+                    frame.extend(b"Mock Radio");
+                    self.reply(&frame);
+                }
+                CMD_IMPORT_PRIVATE_KEY if !self.import_enabled => self.reply(&[RESP_CODE_DISABLED]),
+                CMD_IMPORT_PRIVATE_KEY if command.len() >= 65 => {
+                    self.identity.copy_from_slice(&command[1..65]);
+                    self.reply(&[RESP_CODE_OK]);
+                }
+                _ => self.reply(&[RESP_CODE_ERR, 6]),
+            }
+        }
+    }
+
+    impl Write for MockRadio {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.input.extend_from_slice(buf);
+            while self.input.len() >= 3 {
+                assert_eq!(self.input[0], FRAME_TO_RADIO);
+                let length = u16::from_le_bytes([self.input[1], self.input[2]]) as usize;
+                if self.input.len() < 3 + length {
+                    break;
+                }
+                let command: Vec<u8> = self.input.drain(..3 + length).skip(3).collect();
+                self.handle(&command);
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Read for MockRadio {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let count = buf.len().min(self.output.len());
+            for (slot, byte) in buf.iter_mut().zip(self.output.drain(..count)) {
+                *slot = byte;
+            }
+            Ok(count)
+        }
+    }
+
+    #[test]
+    fn test_flash_key_against_mock_radio() {
+        let new_key = create_meshcore_private_key(&[0x42; 32]);
+        let new_public = hex::encode_upper(extract_public_key_from_meshcore_key(&new_key).unwrap());
+
+        let mut client = CompanionClient::new(MockRadio::new(1));
+        let before = client.app_start().unwrap();
+        assert_eq!(before.name, "Mock Radio");
+        assert_eq!(before.public_key, MockRadio::new(1).public_key());
+
+        flash_key(client, &new_key, &new_public).unwrap();
+    }
+
+    #[test]
+    fn test_flash_key_reports_disabled_import() {
+        let new_key = create_meshcore_private_key(&[0x42; 32]);
+        let mut radio = MockRadio::new(1);
+        radio.import_enabled = false;
+
+        let error = flash_key(CompanionClient::new(radio), &new_key, "00")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("without private key import"), "{}", error);
+    }
+
+    #[test]
+    fn test_self_info_tolerates_short_frames() {
+        let mut frame = vec![RESP_CODE_SELF_INFO, 2, 0, 0];
+        frame.extend([0xAB; 32]);
+        let info = SelfInfo::decode(&frame).unwrap();
+        assert_eq!(info.public_key, [0xAB; 32]);
+        assert_eq!(info.name, "");
+
+        assert!(SelfInfo::decode(&[RESP_CODE_OK]).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_flash_key_over_pseudo_terminal() {
+        use serialport::{SerialPort, TTYPort};
+        use tempfile::NamedTempFile;
+
+        let (mut master, slave) = TTYPort::pair().unwrap();
+        master.set_timeout(Duration::from_secs(5)).unwrap();
+        let port_name = slave.name().unwrap();
+
+        // Serve the mock radio on the master side of the pty
+        let device = std::thread::spawn(move || {
+            let mut radio = MockRadio::new(7);
+            let mut buffer = [0u8; 256];
+            // APP_START, IMPORT, APP_START
+            let mut responses = 0;
+            while responses < 3 {
+                let read = master.read(&mut buffer).unwrap();
+                let pending = radio.output.len();
+                radio.write_all(&buffer[..read]).unwrap();
+                if radio.output.len() > pending {
+                    responses += 1;
+                }
+                let reply: Vec<u8> = radio.output.drain(..).collect();
+                master.write_all(&reply).unwrap();
+            }
+            radio.public_key()
+        });
+
+        let store = NamedTempFile::new().unwrap();
+        let key = create_meshcore_private_key(&[0x33; 32]);
+        let public_key = extract_public_key_from_meshcore_key(&key).unwrap();
+        std::fs::write(
+            store.path(),
+            format!(
+                "{}; {}\n",
+                hex::encode_upper(key),
+                hex::encode_upper(public_key)
+            ),
+        )
+        .unwrap();
+
+        run_flash_key(&port_name, 115_200, store.path().to_str().unwrap(), None).unwrap();
+        assert_eq!(device.join().unwrap(), public_key);
+        drop(slave);
+    }
+}
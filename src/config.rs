@@ -0,0 +1,303 @@
+use crate::keystore::KEYS_FILE;
+use crate::pattern::PatternSet;
+use crate::secure::WipeMethod;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Looked up in the working directory, so a project can pin its own settings.
+pub const PROJECT_CONFIG_FILE: &str = "meshcore-keygen.toml";
+
+const CONFIG_DIR_NAME: &str = "meshcore-keygen";
+const USER_CONFIG_FILE: &str = "config.toml";
+
+/// What a run prints by default: the usual chatter, only public keys, or only public keys
+/// plus JSON-lines events on stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Quiet,
+    Jsonl,
+}
+
+/// Whether keys with the node hashes the firmware reserves (00 and FF) are accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReservedHashes {
+    #[default]
+    Allow,
+    Skip,
+}
+
+/// A named set of patterns, e.g. the hexspeak words a team hunts for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Profile {
+    pub patterns: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_keys: Option<usize>,
+}
+
+impl Profile {
+    /// The patterns as one comma-separated pattern list.
+    pub fn pattern(&self) -> String {
+        self.patterns.join(",")
+    }
+}
+
+/// One config file. Every setting is optional so files can be layered: the user's file
+/// first, then the project's, then command-line flags on top.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ConfigFile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threads: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<OutputFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keys_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wipe_method: Option<WipeMethod>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reserved_hashes: Option<ReservedHashes>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl ConfigFile {
+    pub fn parse(contents: &str) -> Result<Self> {
+        let file: ConfigFile = toml::from_str(contents)?;
+        if file.threads == Some(0) {
+            anyhow::bail!("threads must be at least 1");
+        }
+        for (name, profile) in &file.profiles {
+            if profile.patterns.is_empty() {
+                anyhow::bail!("Profile '{}' has no patterns", name);
+            }
+            PatternSet::compile(&profile.pattern())
+                .with_context(|| format!("Profile '{}' has an invalid pattern", name))?;
+        }
+        Ok(file)
+    }
+
+    /// Settings in `other` win; profiles with the same name are replaced, not merged.
+    fn merge(&mut self, other: ConfigFile) {
+        self.threads = other.threads.or(self.threads);
+        self.output = other.output.or(self.output);
+        self.keys_file = other.keys_file.or(self.keys_file.take());
+        self.wipe_method = other.wipe_method.or(self.wipe_method);
+        self.reserved_hashes = other.reserved_hashes.or(self.reserved_hashes);
+        self.profiles.extend(other.profiles);
+    }
+}
+
+/// The settings from every config file that applies, and where they came from.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub values: ConfigFile,
+    pub sources: Vec<PathBuf>,
+}
+
+impl Config {
+    /// Reads `explicit` alone when given. Otherwise layers the user config
+    /// (`$XDG_CONFIG_HOME/meshcore-keygen/config.toml`) under `./meshcore-keygen.toml`;
+    /// either may be missing.
+    pub fn load(explicit: Option<&Path>) -> Result<Self> {
+        match explicit {
+            Some(path) => {
+                let mut config = Config::default();
+                config.add(path, true)?;
+                Ok(config)
+            }
+            None => Self::load_layers(
+                user_config_path().as_deref(),
+                Path::new(PROJECT_CONFIG_FILE),
+            ),
+        }
+    }
+
+    fn load_layers(user: Option<&Path>, project: &Path) -> Result<Self> {
+        let mut config = Config::default();
+        if let Some(user) = user {
+            config.add(user, false)?;
+        }
+        config.add(project, false)?;
+        Ok(config)
+    }
+
+    fn add(&mut self, path: &Path, required: bool) -> Result<()> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        let file = ConfigFile::parse(&contents)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        self.values.merge(file);
+        self.sources.push(path.to_path_buf());
+        Ok(())
+    }
+
+    pub fn threads(&self) -> Option<usize> {
+        self.values.threads
+    }
+
+    pub fn output(&self) -> OutputFormat {
+        self.values.output.unwrap_or_default()
+    }
+
+    pub fn keys_file(&self) -> &str {
+        self.values.keys_file.as_deref().unwrap_or(KEYS_FILE)
+    }
+
+    pub fn wipe_method(&self) -> WipeMethod {
+        self.values.wipe_method.unwrap_or_default()
+    }
+
+    pub fn reserved_hashes(&self) -> ReservedHashes {
+        self.values.reserved_hashes.unwrap_or_default()
+    }
+
+    pub fn profile(&self, name: &str) -> Result<&Profile> {
+        self.values.profiles.get(name).with_context(|| {
+            let known: Vec<&str> = self.values.profiles.keys().map(String::as_str).collect();
+            if known.is_empty() {
+                format!("No profile '{}': no config file defines any", name)
+            } else {
+                format!("No profile '{}' (known: {})", name, known.join(", "))
+            }
+        })
+    }
+
+    /// The merged settings as TOML, with the defaults filled in and the files they came
+    /// from listed at the top.
+    pub fn show(&self, default_threads: usize) -> Result<String> {
+        let effective = ConfigFile {
+            threads: Some(self.threads().unwrap_or(default_threads)),
+            output: Some(self.output()),
+            keys_file: Some(self.keys_file().to_string()),
+            wipe_method: Some(self.wipe_method()),
+            reserved_hashes: Some(self.reserved_hashes()),
+            profiles: self.values.profiles.clone(),
+        };
+
+        let mut shown = String::new();
+        if self.sources.is_empty() {
+            shown.push_str("# No config file in use, showing the defaults\n");
+        } else {
+            for source in &self.sources {
+                writeln!(shown, "# From {}", source.display())?;
+            }
+        }
+        shown.push_str("# Command-line flags override everything below\n\n");
+        shown.push_str(&toml::to_string_pretty(&effective)?);
+        Ok(shown)
+    }
+}
+
+/// `$XDG_CONFIG_HOME/meshcore-keygen/config.toml`, falling back to `~/.config` (or
+/// `%APPDATA%` on Windows).
+pub fn user_config_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(base.join(CONFIG_DIR_NAME).join(USER_CONFIG_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config_file() {
+        let file = ConfigFile::parse(
+            r#"
+            threads = 6
+            output = "jsonl"
+            keys-file = "/srv/keys.txt"
+            wipe-method = "tools-only"
+            reserved-hashes = "skip"
+
+            [profiles.hexspeak]
+            patterns = ["C0FFEE", "DEADBEEF", "*CAFE"]
+            max-keys = 3
+            "#,
+        )
+        .unwrap();
+        assert_eq!(file.threads, Some(6));
+        assert_eq!(file.output, Some(OutputFormat::Jsonl));
+        assert_eq!(file.wipe_method, Some(WipeMethod::ToolsOnly));
+        assert_eq!(file.reserved_hashes, Some(ReservedHashes::Skip));
+        assert_eq!(file.profiles["hexspeak"].pattern(), "C0FFEE,DEADBEEF,*CAFE");
+        assert_eq!(file.profiles["hexspeak"].max_keys, Some(3));
+
+        assert!(ConfigFile::parse("thraeds = 4").is_err());
+        assert!(ConfigFile::parse("threads = 0").is_err());
+        assert!(ConfigFile::parse("[profiles.bad]\npatterns = [\"XYZ\"]").is_err());
+        assert!(ConfigFile::parse("[profiles.empty]\npatterns = []").is_err());
+    }
+
+    #[test]
+    fn test_project_config_overrides_user_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let user = dir.path().join("user.toml");
+        let project = dir.path().join("project.toml");
+        std::fs::write(
+            &user,
+            "threads = 2\nkeys-file = \"user.txt\"\n[profiles.a]\npatterns = [\"AA\"]\n",
+        )
+        .unwrap();
+        std::fs::write(&project, "threads = 8\n[profiles.b]\npatterns = [\"BB\"]\n").unwrap();
+
+        let config = Config::load_layers(Some(&user), &project).unwrap();
+        assert_eq!(config.sources, vec![user.clone(), project.clone()]);
+        assert_eq!(config.values.threads, Some(8));
+        assert_eq!(config.values.keys_file.as_deref(), Some("user.txt"));
+        assert!(config.profile("a").is_ok());
+        assert!(config.profile("b").is_ok());
+        assert!(
+            config
+                .profile("c")
+                .unwrap_err()
+                .to_string()
+                .contains("a, b")
+        );
+
+        // Missing layers are skipped, but an explicit file must exist
+        let missing = dir.path().join("missing.toml");
+        assert!(
+            Config::load_layers(None, &missing)
+                .unwrap()
+                .sources
+                .is_empty()
+        );
+        assert!(Config::load(Some(&missing)).is_err());
+    }
+
+    #[test]
+    fn test_show_fills_in_defaults() {
+        let config = Config {
+            values: ConfigFile::parse("threads = 3").unwrap(),
+            sources: vec![PathBuf::from("meshcore-keygen.toml")],
+        };
+        let shown = config.show(7).unwrap();
+        assert!(shown.contains("# From meshcore-keygen.toml"));
+        assert!(shown.contains("threads = 3"));
+        assert!(shown.contains("keys-file = \"meshcore-keys.txt\""));
+        assert!(shown.contains("wipe-method = \"auto\""));
+
+        // What is shown can be read back as a config file
+        let body: String = shown
+            .lines()
+            .filter(|l| !l.starts_with('#'))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(ConfigFile::parse(&body).unwrap().threads, Some(3));
+    }
+}
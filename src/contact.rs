@@ -0,0 +1,243 @@
+use crate::advert::NodeType;
+use crate::output::say;
+use crate::secure::write_key_file;
+use anyhow::{Context, Result};
+use qrcode::QrCode;
+use qrcode::render::{svg, unicode};
+use std::path::Path;
+
+/// Scheme and path the companion app registers for "add contact" links.
+const CONTACT_URI_PREFIX: &str = "meshcore://contact/add";
+
+/// Pixels per QR module in PNG output; large enough for phone cameras at arm's length.
+const PNG_MODULE_SIZE: usize = 8;
+
+/// Modules of blank margin around the code, as required by the QR specification.
+const QUIET_ZONE: usize = 4;
+
+/// How a found key should be shared straight from the search output.
+#[derive(Debug, Clone)]
+pub struct ContactShare {
+    pub name: Option<String>,
+    pub node_type: NodeType,
+    pub qr: bool,
+}
+
+impl ContactShare {
+    /// Prints the contact URI (and QR code if requested) for a freshly found key, unless
+    /// the output is quiet.
+    /// Without an explicit name the node is called after its public key prefix,
+    /// which is what the firmware shows for unnamed nodes too.
+    pub fn print_for(&self, public_key: &str) -> Result<()> {
+        let name = self
+            .name
+            .clone()
+            .unwrap_or_else(|| default_contact_name(public_key));
+        let uri = contact_uri(public_key, &name, self.node_type);
+
+        // Through say! so --quiet leaves stdout to the found public keys
+        say!("   📇 Contact: {}", uri);
+        if self.qr {
+            say!("{}", render_terminal(&uri)?);
+        }
+        Ok(())
+    }
+}
+
+/// Name used when none is given: the first four public key bytes.
+pub fn default_contact_name(public_key: &str) -> String {
+    public_key[..8.min(public_key.len())].to_string()
+}
+
+/// Builds the contact URI the companion app accepts when scanning or tapping a link.
+pub fn contact_uri(public_key: &str, name: &str, node_type: NodeType) -> String {
+    format!(
+        "{}?name={}&public_key={}&type={}",
+        CONTACT_URI_PREFIX,
+        percent_encode(name),
+        public_key.to_ascii_lowercase(),
+        node_type as u8
+    )
+}
+
+/// Encodes everything outside the RFC 3986 unreserved set, so names with spaces,
+/// emoji or `&` survive as a single query parameter.
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn encode_qr(uri: &str) -> Result<QrCode> {
+    QrCode::new(uri.as_bytes()).map_err(|e| anyhow::anyhow!("Failed to encode QR code: {}", e))
+}
+
+/// Renders the QR code with Unicode half-blocks, two modules per character cell.
+/// Colours are inverted so the code scans on the usual dark terminal background.
+pub fn render_terminal(uri: &str) -> Result<String> {
+    Ok(encode_qr(uri)?
+        .render::<unicode::Dense1x2>()
+        .dark_color(unicode::Dense1x2::Light)
+        .light_color(unicode::Dense1x2::Dark)
+        .build())
+}
+
+/// Renders the QR code as a standalone SVG document.
+pub fn render_svg(uri: &str) -> Result<String> {
+    Ok(encode_qr(uri)?
+        .render::<svg::Color<'_>>()
+        .min_dimensions(256, 256)
+        .build())
+}
+
+/// Renders the QR code as an 8-bit greyscale PNG.
+pub fn render_png(uri: &str) -> Result<Vec<u8>> {
+    let code = encode_qr(uri)?;
+    let modules = code.width();
+    let colors = code.to_colors();
+    let size = (modules + 2 * QUIET_ZONE) * PNG_MODULE_SIZE;
+
+    let mut pixels = vec![0xFFu8; size * size];
+    for (index, color) in colors.iter().enumerate() {
+        if *color != qrcode::Color::Dark {
+            continue;
+        }
+        let x = (index % modules + QUIET_ZONE) * PNG_MODULE_SIZE;
+        let y = (index / modules + QUIET_ZONE) * PNG_MODULE_SIZE;
+        for row in y..y + PNG_MODULE_SIZE {
+            pixels[row * size + x..row * size + x + PNG_MODULE_SIZE].fill(0);
+        }
+    }
+
+    let mut png_bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png_bytes, size as u32, size as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder
+        .write_header()
+        .context("Failed to write PNG header")?;
+    writer
+        .write_image_data(&pixels)
+        .context("Failed to write PNG data")?;
+    writer.finish().context("Failed to finish PNG")?;
+
+    Ok(png_bytes)
+}
+
+/// Prints the contact URI for a public key and renders it as a QR code in the terminal
+/// and/or to a `.png` or `.svg` file, picked by extension.
+pub fn run_contact(
+    public_key: &str,
+    name: Option<&str>,
+    node_type: NodeType,
+    terminal_qr: bool,
+    qr_file: Option<&str>,
+) -> Result<()> {
+    let name = name
+        .map(str::to_string)
+        .unwrap_or_else(|| default_contact_name(public_key));
+    let uri = contact_uri(public_key, &name, node_type);
+    println!("{}", uri);
+
+    if terminal_qr {
+        println!("{}", render_terminal(&uri)?);
+    }
+
+    if let Some(path) = qr_file {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+        let contents = match extension.as_deref() {
+            Some("png") => render_png(&uri)?,
+            Some("svg") => render_svg(&uri)?.into_bytes(),
+            _ => anyhow::bail!("QR file must end in .png or .svg: {}", path),
+        };
+        write_key_file(path, &contents, false)?;
+        println!("🖼️📇 QR code written to: {}", path);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUBLIC_KEY: &str = "CAFEBABE00112233445566778899AABBCCDDEEFF00112233445566778899AABB";
+
+    #[test]
+    fn test_contact_uri_format() {
+        assert_eq!(
+            contact_uri(PUBLIC_KEY, "Hill Top #2", NodeType::Repeater),
+            "meshcore://contact/add?name=Hill%20Top%20%232&public_key=cafebabe00112233445566778899aabbccddeeff00112233445566778899aabb&type=2"
+        );
+        assert_eq!(default_contact_name(PUBLIC_KEY), "CAFEBABE");
+    }
+
+    #[test]
+    fn test_percent_encode_utf8() {
+        assert_eq!(percent_encode("a-b_c.d~e"), "a-b_c.d~e");
+        assert_eq!(percent_encode("né&x=1"), "n%C3%A9%26x%3D1");
+    }
+
+    #[test]
+    fn test_terminal_qr_uses_half_blocks() {
+        let rendered = render_terminal(&contact_uri(PUBLIC_KEY, "n", NodeType::Chat)).unwrap();
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        // Each character cell holds two module rows, so there are about half as many lines as columns
+        assert!(lines.len() > 10);
+        assert!(lines[0].chars().count() > lines.len());
+        assert!(rendered.contains('▀') || rendered.contains('▄'));
+    }
+
+    #[test]
+    fn test_png_and_svg_output() {
+        let uri = contact_uri(PUBLIC_KEY, "node", NodeType::Room);
+        let modules = encode_qr(&uri).unwrap().width();
+
+        let png_bytes = render_png(&uri).unwrap();
+        let decoder = png::Decoder::new(png_bytes.as_slice());
+        let reader = decoder.read_info().unwrap();
+        let expected_size = ((modules + 2 * QUIET_ZONE) * PNG_MODULE_SIZE) as u32;
+        assert_eq!(reader.info().width, expected_size);
+        assert_eq!(reader.info().height, expected_size);
+
+        let svg = render_svg(&uri).unwrap();
+        assert!(svg.contains("<svg"));
+    }
+
+    #[test]
+    fn test_run_contact_rejects_unknown_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("code.gif");
+        assert!(
+            run_contact(
+                PUBLIC_KEY,
+                None,
+                NodeType::Chat,
+                false,
+                Some(path.to_str().unwrap())
+            )
+            .is_err()
+        );
+
+        let path = dir.path().join("code.png");
+        run_contact(
+            PUBLIC_KEY,
+            None,
+            NodeType::Chat,
+            false,
+            Some(path.to_str().unwrap()),
+        )
+        .unwrap();
+        assert!(std::fs::read(path).unwrap().starts_with(b"\x89PNG"));
+    }
+}
@@ -1,9 +1,8 @@
+use crate::output::say;
+use crate::pattern::PatternSet;
 use crate::secure::SecureString;
-use crate::types::{FoundKey, SearchConfig, SearchStats};
-use crate::utils::{
-    check_prefix_match, create_meshcore_private_key, hex_string_to_bytes,
-    validate_meshcore_key_format,
-};
+use crate::types::{FoundKey, PartialMatch, SearchConfig, SearchStats};
+use crate::utils::{create_meshcore_private_key, validate_meshcore_key_format};
 use crossbeam::channel;
 use ed25519_dalek::SigningKey;
 use rand::RngCore;
@@ -14,6 +13,9 @@ use std::sync::atomic::Ordering;
 pub struct CpuKeySearcher;
 
 impl CpuKeySearcher {
+    /// Backend name used to key cached benchmark results.
+    pub const BACKEND: &'static str = "cpu";
+
     /// Spawns multiple CPU worker threads for parallel key generation and searching.
     /// Uses smaller batch sizes and local RNG for optimal CPU performance.
     pub fn search(
@@ -24,19 +26,26 @@ impl CpuKeySearcher {
     ) {
         // CPU works best with smaller, more frequent batches
         let batch_size = match config.prefix.len() {
-            1..=4 => 1024, // Small batches for short patterns
+            0..=4 => 1024, // Small batches for short (or hash-only) patterns
             5..=6 => 2048, // Medium batches for medium patterns
             _ => 4096,     // Larger batches for long patterns
         };
 
-        println!(
-            "  🦺 CPU worker #{} activated! Batch size: {}",
-            thread_id, batch_size
-        );
+        // The dashboard owns the screen, so stray prints would corrupt it
+        if !config.tui && !config.silent_workers {
+            say!(
+                "  🦺 CPU worker #{} activated! Batch size: {}",
+                thread_id,
+                batch_size
+            );
+        }
 
-        let prefix_bytes = hex_string_to_bytes(&config.prefix);
+        let patterns =
+            PatternSet::compile(&config.prefix).expect("patterns are validated with the config");
         let mut rng = rand::thread_rng();
         let mut local_attempts = 0u64;
+        let mut local_best_partial = 0usize;
+        stats.heartbeat(thread_id);
         const UPDATE_INTERVAL: u64 = 5000;
 
         while !stats.stop_search.load(Ordering::Relaxed) {
@@ -55,21 +64,28 @@ impl CpuKeySearcher {
                 let verifying_key = signing_key.verifying_key();
                 let public_key_bytes = verifying_key.to_bytes();
 
-                // Quick prefix check
-                if check_prefix_match(&public_key_bytes, &prefix_bytes) {
+                // Quick pattern check, then reject node hashes already used in the mesh
+                if patterns.matches(&public_key_bytes)
+                    && config.excluded_hashes.allows(public_key_bytes[0])
+                {
                     // Generate meshcore-compatible private key
                     let meshcore_private_key = create_meshcore_private_key(&seed);
 
-                    // Validate the key format
-                    if validate_meshcore_key_format(&meshcore_private_key) {
+                    // Validate the key format, then claim the node hash when the batch must
+                    // not repeat one
+                    if validate_meshcore_key_format(&meshcore_private_key)
+                        && (!config.unique_hashes || stats.claim_hash(public_key_bytes[0]))
+                    {
                         let found_key = FoundKey {
                             private_key: SecureString::new(
                                 hex::encode(meshcore_private_key).to_uppercase(),
                             ),
                             public_key: hex::encode(public_key_bytes).to_uppercase(),
+                            // Kept so the key can later be exported to OpenSSH or PKCS#8
+                            seed: Some(SecureString::new(hex::encode(seed).to_uppercase())),
                         };
 
-                        stats.prefix_matches.fetch_add(1, Ordering::Relaxed);
+                        stats.record_match(thread_id);
 
                         if found_sender.send(found_key).is_err() {
                             return;
@@ -77,13 +93,24 @@ impl CpuKeySearcher {
                     }
                 }
 
+                // Only the dashboard shows near misses, so skip the extra work otherwise
+                if config.tui {
+                    let (matched, fixed) = patterns.closest_match(&public_key_bytes);
+                    if matched > local_best_partial {
+                        local_best_partial = matched;
+                        stats.offer_partial_match(PartialMatch {
+                            public_key: hex::encode(public_key_bytes).to_uppercase(),
+                            matched_nibbles: matched,
+                            pattern_nibbles: fixed,
+                        });
+                    }
+                }
+
                 local_attempts += 1;
 
                 // Update stats more frequently for better responsiveness
-                if local_attempts % UPDATE_INTERVAL == 0 {
-                    stats
-                        .total_attempts
-                        .fetch_add(local_attempts, Ordering::Relaxed);
+                if local_attempts.is_multiple_of(UPDATE_INTERVAL) {
+                    stats.record_attempts(thread_id, local_attempts);
                     local_attempts = 0;
                 }
             }
@@ -91,9 +118,7 @@ impl CpuKeySearcher {
 
         // Ensure final attempt count is recorded
         if local_attempts > 0 {
-            stats
-                .total_attempts
-                .fetch_add(local_attempts, Ordering::Relaxed);
+            stats.record_attempts(thread_id, local_attempts);
         }
     }
 
@@ -122,7 +147,7 @@ impl CpuKeySearcher {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{SearchBehavior, SearchStats};
+    use crate::types::{NodeHashFilter, SearchBehavior, SearchStats};
 
     #[test]
     fn test_cpu_searcher_creation() {
@@ -131,11 +156,11 @@ mod tests {
 
     #[test]
     fn test_cpu_search_basic() {
-        let config = Arc::new(SearchConfig {
-            prefix: "A".to_string(),
-            search_behavior: SearchBehavior::FindN(1),
-            cpu_threads: 1,
-        });
+        let config = Arc::new(SearchConfig::new(
+            "A".to_string(),
+            SearchBehavior::FindN(1),
+            1,
+        ));
 
         let stats = Arc::new(SearchStats::new());
         let (sender, _receiver) = channel::unbounded();
@@ -148,11 +173,11 @@ mod tests {
 
     #[test]
     fn test_cpu_worker_spawning() {
-        let config = Arc::new(SearchConfig {
-            prefix: "B".to_string(),
-            search_behavior: SearchBehavior::FindN(1),
-            cpu_threads: 2,
-        });
+        let config = Arc::new(SearchConfig::new(
+            "B".to_string(),
+            SearchBehavior::FindN(1),
+            2,
+        ));
 
         let stats = Arc::new(SearchStats::new());
         let (sender, _receiver) = channel::unbounded();
@@ -172,20 +197,70 @@ mod tests {
     #[test]
     fn test_batch_size_scaling() {
         // Test that batch sizes scale appropriately with prefix length
-        let short_config = SearchConfig {
-            prefix: "A".to_string(),
-            search_behavior: SearchBehavior::FindN(1),
-            cpu_threads: 1,
-        };
+        let short_config = SearchConfig::new("A".to_string(), SearchBehavior::FindN(1), 1);
 
-        let long_config = SearchConfig {
-            prefix: "ABCDEFGH".to_string(),
-            search_behavior: SearchBehavior::FindN(1),
-            cpu_threads: 1,
-        };
+        let long_config = SearchConfig::new("ABCDEFGH".to_string(), SearchBehavior::FindN(1), 1);
 
         // We can't directly test batch sizes since they're local to the search function,
         // but we can ensure the configurations are valid
         assert!(short_config.prefix.len() < long_config.prefix.len());
     }
+
+    #[test]
+    fn test_cpu_search_respects_excluded_hashes() {
+        // Leave a single node hash free so every match must land on it
+        let mut excluded_hashes = NodeHashFilter::default();
+        for hash in (0..=255u8).filter(|&hash| hash != 0x5A) {
+            excluded_hashes.exclude(hash);
+        }
+
+        let config = Arc::new(SearchConfig {
+            excluded_hashes,
+            ..SearchConfig::new(String::new(), SearchBehavior::FindN(1), 1)
+        });
+
+        let stats = Arc::new(SearchStats::new());
+        let (sender, receiver) = channel::unbounded();
+
+        let search_stats = Arc::clone(&stats);
+        let handle =
+            std::thread::spawn(move || CpuKeySearcher::search(config, search_stats, sender, 0));
+
+        let found = receiver.recv().unwrap();
+        stats.stop_search.store(true, Ordering::Relaxed);
+        handle.join().unwrap();
+
+        assert!(found.public_key.starts_with("5A"));
+    }
+
+    #[test]
+    fn test_cpu_search_unique_hashes() {
+        // Only the 16 "Cx" hashes are allowed, and each must show up exactly once
+        let mut excluded_hashes = NodeHashFilter::default();
+        for hash in (0..=255u8).filter(|hash| hash >> 4 != 0xC) {
+            excluded_hashes.exclude(hash);
+        }
+
+        let config = Arc::new(SearchConfig {
+            excluded_hashes,
+            unique_hashes: true,
+            ..SearchConfig::new(String::new(), SearchBehavior::FindN(16), 2)
+        });
+
+        let stats = Arc::new(SearchStats::new());
+        let (sender, receiver) = channel::unbounded();
+        let handles = CpuKeySearcher::spawn_workers(2, config, Arc::clone(&stats), sender);
+
+        let mut hashes: Vec<String> = (0..16)
+            .map(|_| receiver.recv().unwrap().public_key[..2].to_string())
+            .collect();
+        stats.stop_search.store(true, Ordering::Relaxed);
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        hashes.sort();
+        hashes.dedup();
+        assert_eq!(hashes.len(), 16);
+    }
 }
@@ -0,0 +1,861 @@
+use crate::checkpoint::Checkpoint;
+use crate::cpu::CpuKeySearcher;
+use crate::keystore::append_key;
+use crate::output::{say, say_warning};
+use crate::pattern::PatternSet;
+use crate::types::{NodeHashFilter, SearchBehavior, SearchConfig, SearchStats};
+use crate::utils::{format_duration, format_large_number};
+use anyhow::{Context, Result};
+use crossbeam::channel;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const STATE_DIR_NAME: &str = "meshcore-keygen";
+const SOCKET_NAME: &str = "meshcore-keygen.sock";
+const CHECKPOINT_NAME: &str = "daemon-state.json";
+
+/// How often the scheduler and the accept loop look for new work or a shutdown.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Attempt counters are checkpointed this often; state changes are saved right away.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A client that doesn't finish its request in this time is dropped.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Requests are a few hundred bytes.
+const MAX_REQUEST_BYTES: u64 = 64 * 1024;
+
+/// Per-user state directory: `$XDG_STATE_HOME/meshcore-keygen`, falling back to
+/// `~/.local/state/meshcore-keygen`.
+pub fn default_state_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state"))
+        })?;
+    Some(base.join(STATE_DIR_NAME))
+}
+
+/// The socket lives in `$XDG_RUNTIME_DIR` when there is one, next to the state otherwise.
+pub fn default_socket_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .map(|dir| dir.join(SOCKET_NAME))
+        .or_else(|| default_state_dir().map(|dir| dir.join(SOCKET_NAME)))
+}
+
+/// Parses a budget like `90s`, `30m`, `2h` or `1d`; a bare number is seconds.
+pub fn parse_budget(text: &str) -> Result<Duration> {
+    let text = text.trim();
+    let (number, unit) = text.split_at(
+        text.find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len()),
+    );
+    let value: u64 = number
+        .parse()
+        .with_context(|| format!("Budget '{}' must start with a number", text))?;
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        other => anyhow::bail!("Unknown budget unit '{}' (use s, m, h or d)", other),
+    };
+    if value == 0 {
+        anyhow::bail!("Budget must be greater than zero");
+    }
+    Ok(Duration::from_secs(value.saturating_mul(seconds)))
+}
+
+/// What a client asks the daemon to search for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobSpec {
+    pub pattern: String,
+    pub count: usize,
+    /// Search time after which the job gives up, across restarts and pauses.
+    pub budget_secs: Option<u64>,
+    /// Name of the key store in the state dir the found keys are appended to; defaults to
+    /// one file per job.
+    pub output: Option<PathBuf>,
+    /// Higher runs first, and pauses a running job with a lower priority.
+    pub priority: i32,
+    /// Node hashes found keys must not use, from the client's --avoid-hashes-from and
+    /// reserved hash policy. Missing in state files written before it existed.
+    #[serde(default)]
+    pub excluded_hashes: Vec<u8>,
+}
+
+impl JobSpec {
+    fn hash_filter(&self) -> NodeHashFilter {
+        let mut filter = NodeHashFilter::default();
+        for &hash in &self.excluded_hashes {
+            filter.exclude(hash);
+        }
+        filter
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Done,
+    /// Used up its budget before finding every key.
+    Exhausted,
+    Cancelled,
+    Failed,
+}
+
+impl JobState {
+    fn is_finished(self) -> bool {
+        !matches!(self, JobState::Queued | JobState::Running)
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            JobState::Queued => "⏳ queued",
+            JobState::Running => "🚀 running",
+            JobState::Done => "✅ done",
+            JobState::Exhausted => "⌛ out of budget",
+            JobState::Cancelled => "🛑 cancelled",
+            JobState::Failed => "💥 failed",
+        }
+    }
+}
+
+/// A job and its progress. Found keys are listed by public key only; the private halves
+/// are in the job's output key store.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: u64,
+    pub spec: JobSpec,
+    pub state: JobState,
+    pub output: PathBuf,
+    pub attempts: u64,
+    pub run_secs: f64,
+    pub found: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Everything the daemon checkpoints.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct QueueState {
+    next_id: u64,
+    jobs: Vec<Job>,
+}
+
+impl QueueState {
+    fn job_mut(&mut self, id: u64) -> Result<&mut Job> {
+        self.jobs
+            .iter_mut()
+            .find(|job| job.id == id)
+            .with_context(|| format!("No job #{}", id))
+    }
+
+    /// Highest priority first, then first come first served.
+    fn next_queued(&self) -> Option<&Job> {
+        self.jobs
+            .iter()
+            .filter(|job| job.state == JobState::Queued)
+            .min_by_key(|job| (-(job.spec.priority as i64), job.id))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "request", rename_all = "snake_case")]
+pub enum Request {
+    Submit { spec: JobSpec },
+    Status { id: Option<u64> },
+    Cancel { id: u64 },
+    Results { id: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "response", rename_all = "snake_case")]
+pub enum Response {
+    Submitted {
+        id: u64,
+    },
+    Jobs {
+        jobs: Vec<Job>,
+    },
+    Cancelled {
+        id: u64,
+    },
+    Results {
+        id: u64,
+        state: JobState,
+        output: PathBuf,
+        public_keys: Vec<String>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// A queue of searches sharing one worker pool. Jobs run one at a time on every thread; a
+/// higher-priority submission pauses the running job, which resumes later. Random search has
+/// no position to lose, so pausing (or restarting the daemon) costs nothing but the pause.
+pub struct Daemon {
+    state: Mutex<QueueState>,
+    checkpoint: Checkpoint,
+    state_dir: PathBuf,
+    threads: usize,
+    shutdown: AtomicBool,
+}
+
+impl Daemon {
+    /// Loads the queue from the last checkpoint. Jobs that were running when the previous
+    /// daemon stopped go back into the queue with their counters intact.
+    pub fn open(state_dir: &Path, threads: usize) -> Result<Self> {
+        let checkpoint = Checkpoint::new(state_dir.join(CHECKPOINT_NAME));
+        let mut state: QueueState = checkpoint.load()?.unwrap_or_default();
+        for job in &mut state.jobs {
+            if job.state == JobState::Running {
+                job.state = JobState::Queued;
+            }
+        }
+        checkpoint.save(&state)?;
+
+        Ok(Self {
+            state: Mutex::new(state),
+            checkpoint,
+            state_dir: state_dir.to_path_buf(),
+            threads,
+            shutdown: AtomicBool::new(false),
+        })
+    }
+
+    /// Makes the scheduler and the accept loop return; running jobs are paused, not lost.
+    pub fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+    }
+
+    fn save(&self, state: &QueueState) {
+        // A failed checkpoint only matters if the daemon dies before the next one succeeds
+        if let Err(e) = self.checkpoint.save(state) {
+            say_warning!("😤 Couldn't checkpoint the queue: {:#}", e);
+        }
+    }
+
+    fn handle(&self, request: Request) -> Response {
+        let result = match request {
+            Request::Submit { spec } => self.submit(spec),
+            Request::Status { id } => {
+                let state = self.state.lock().unwrap();
+                let jobs: Vec<Job> = state
+                    .jobs
+                    .iter()
+                    .filter(|job| id.is_none_or(|id| job.id == id))
+                    .cloned()
+                    .collect();
+                match id {
+                    Some(id) if jobs.is_empty() => Err(anyhow::anyhow!("No job #{}", id)),
+                    _ => Ok(Response::Jobs { jobs }),
+                }
+            }
+            Request::Cancel { id } => self.cancel(id),
+            Request::Results { id } => {
+                let mut state = self.state.lock().unwrap();
+                state.job_mut(id).map(|job| Response::Results {
+                    id,
+                    state: job.state,
+                    output: job.output.clone(),
+                    public_keys: job.found.clone(),
+                })
+            }
+        };
+        result.unwrap_or_else(|e| Response::Error {
+            message: format!("{:#}", e),
+        })
+    }
+
+    fn submit(&self, spec: JobSpec) -> Result<Response> {
+        // Clients validate too, but anything that can reach the socket can send a job
+        let patterns = PatternSet::compile(&spec.pattern)?;
+        if patterns.is_empty() {
+            anyhow::bail!("Pattern cannot be empty");
+        }
+        if patterns.match_probability(&spec.hash_filter()) == 0.0 {
+            anyhow::bail!("Pattern '{}' can never match", spec.pattern);
+        }
+        if spec.count == 0 {
+            anyhow::bail!("Count must be at least 1");
+        }
+        if let Some(output) = &spec.output {
+            check_output_name(output)?;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.next_id += 1;
+        let id = state.next_id;
+        let output = self.state_dir.join(
+            spec.output
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(format!("job-{}-keys.txt", id))),
+        );
+        say!(
+            "📥 Job #{}: {} x{} (priority {}) -> {}",
+            id,
+            spec.pattern,
+            spec.count,
+            spec.priority,
+            output.display()
+        );
+        state.jobs.push(Job {
+            id,
+            spec,
+            state: JobState::Queued,
+            output,
+            attempts: 0,
+            run_secs: 0.0,
+            found: Vec::new(),
+            error: None,
+        });
+        self.save(&state);
+        Ok(Response::Submitted { id })
+    }
+
+    fn cancel(&self, id: u64) -> Result<Response> {
+        let mut state = self.state.lock().unwrap();
+        let job = state.job_mut(id)?;
+        if job.state.is_finished() {
+            anyhow::bail!("Job #{} already finished ({:?})", id, job.state);
+        }
+        // A running job notices within one poll interval and stops its workers
+        job.state = JobState::Cancelled;
+        say!("🛑 Job #{} cancelled", id);
+        self.save(&state);
+        Ok(Response::Cancelled { id })
+    }
+
+    /// Runs queued jobs until shutdown.
+    pub fn run_scheduler(&self) {
+        while !self.shutdown.load(Ordering::Relaxed) {
+            let next = self.state.lock().unwrap().next_queued().map(|job| job.id);
+            let Some(id) = next else {
+                std::thread::sleep(POLL_INTERVAL);
+                continue;
+            };
+
+            if let Err(e) = self.run_job(id) {
+                let mut state = self.state.lock().unwrap();
+                if let Ok(job) = state.job_mut(id) {
+                    job.state = JobState::Failed;
+                    job.error = Some(format!("{:#}", e));
+                }
+                say_warning!("💥 Job #{} failed: {:#}", id, e);
+                self.save(&state);
+            }
+        }
+    }
+
+    fn run_job(&self, id: u64) -> Result<()> {
+        let (spec, output, base_attempts, base_secs, remaining) = {
+            let mut state = self.state.lock().unwrap();
+            let job = state.job_mut(id)?;
+            job.state = JobState::Running;
+            let snapshot = (
+                job.spec.clone(),
+                job.output.clone(),
+                job.attempts,
+                job.run_secs,
+                job.spec.count.saturating_sub(job.found.len()),
+            );
+            self.save(&state);
+            snapshot
+        };
+        say!(
+            "▶️ Job #{}: searching {} on {} threads",
+            id,
+            spec.pattern,
+            self.threads
+        );
+
+        let config = Arc::new(SearchConfig {
+            excluded_hashes: spec.hash_filter(),
+            keys_file: output.to_string_lossy().into_owned(),
+            ..SearchConfig::new(
+                spec.pattern.clone(),
+                SearchBehavior::FindN(remaining),
+                self.threads,
+            )
+        });
+        PatternSet::compile(&config.prefix)?;
+        create_key_store(&output)?;
+        let stats = Arc::new(SearchStats::with_workers(self.threads));
+        let (found_sender, found_receiver) = channel::unbounded();
+        let handles =
+            CpuKeySearcher::spawn_workers(self.threads, config, Arc::clone(&stats), found_sender);
+        let started = Instant::now();
+        let mut last_checkpoint = Instant::now();
+
+        let store = |state: &mut QueueState, key: &crate::types::FoundKey| -> Result<()> {
+            let job = state.job_mut(id)?;
+            if job.found.len() >= job.spec.count {
+                return Ok(());
+            }
+            append_key(&output.to_string_lossy(), key)
+                .with_context(|| format!("Failed to write {}", output.display()))?;
+            job.found.push(key.public_key.clone());
+            say!("🎉 Job #{}: found {}", id, key.public_key);
+            Ok(())
+        };
+
+        let result = loop {
+            let found = found_receiver.recv_timeout(POLL_INTERVAL).ok();
+            let mut state = self.state.lock().unwrap();
+            if let Some(key) = found {
+                if let Err(e) = store(&mut state, &key) {
+                    break Err(e);
+                }
+                self.save(&state);
+            }
+
+            let preempting = state
+                .next_queued()
+                .is_some_and(|next| next.spec.priority > spec.priority);
+            let job = state.job_mut(id)?;
+            job.attempts = base_attempts + stats.total_attempts.load(Ordering::Relaxed);
+            job.run_secs = base_secs + started.elapsed().as_secs_f64();
+
+            let next_state = if job.state == JobState::Cancelled {
+                Some(JobState::Cancelled)
+            } else if job.found.len() >= job.spec.count {
+                Some(JobState::Done)
+            } else if spec
+                .budget_secs
+                .is_some_and(|budget| job.run_secs >= budget as f64)
+            {
+                Some(JobState::Exhausted)
+            } else if preempting || self.shutdown.load(Ordering::Relaxed) {
+                Some(JobState::Queued)
+            } else {
+                None
+            };
+
+            if let Some(next_state) = next_state {
+                job.state = next_state;
+                break Ok(());
+            }
+            if last_checkpoint.elapsed() >= CHECKPOINT_INTERVAL {
+                self.save(&state);
+                last_checkpoint = Instant::now();
+            }
+        };
+
+        stats.stop_search.store(true, Ordering::Relaxed);
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // Keys that turned up while the workers were stopping still belong to the job
+        let mut state = self.state.lock().unwrap();
+        for key in found_receiver.try_iter() {
+            store(&mut state, &key)?;
+        }
+        let job = state.job_mut(id)?;
+        job.attempts = base_attempts + stats.total_attempts.load(Ordering::Relaxed);
+        if job.state == JobState::Queued && job.found.len() >= job.spec.count {
+            job.state = JobState::Done;
+        }
+        say!(
+            "⏹️ Job #{}: {} after {} attempts, {} of {} key(s)",
+            id,
+            job.state.label(),
+            format_large_number(job.attempts),
+            job.found.len(),
+            job.spec.count
+        );
+        self.save(&state);
+        result
+    }
+
+    /// Answers requests on the socket until shutdown. One request per connection.
+    pub fn serve(&self, listener: UnixListener) -> Result<()> {
+        listener.set_nonblocking(true)?;
+        while !self.shutdown.load(Ordering::Relaxed) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    // A misbehaving client only affects its own request
+                    let _ = self.serve_client(stream);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(POLL_INTERVAL),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        ErrorKind::Interrupted | ErrorKind::ConnectionAborted
+                    ) => {}
+                Err(e) => return Err(e).context("The control socket failed"),
+            }
+        }
+        Ok(())
+    }
+
+    fn serve_client(&self, stream: UnixStream) -> Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+        stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+
+        let mut line = String::new();
+        BufReader::new((&stream).take(MAX_REQUEST_BYTES)).read_line(&mut line)?;
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => self.handle(request),
+            Err(e) => Response::Error {
+                message: format!("Malformed request: {}", e),
+            },
+        };
+        let mut writer = &stream;
+        writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+        Ok(())
+    }
+}
+
+/// Job key stores are names inside the state dir. Anyone in the socket's group can submit
+/// jobs, and the daemon writes private keys with its own permissions, so a submitter must
+/// not be able to point it at an arbitrary path.
+fn check_output_name(output: &Path) -> Result<()> {
+    let mut components = output.components();
+    let is_plain_name = matches!(
+        (components.next(), components.next()),
+        (Some(std::path::Component::Normal(_)), None)
+    );
+    if !is_plain_name
+        || output
+            .extension()
+            .is_none_or(|extension| extension != "txt")
+    {
+        anyhow::bail!(
+            "Output must be a .txt file name, it is created in the daemon's state dir: {}",
+            output.display()
+        );
+    }
+    Ok(())
+}
+
+/// Creates a job key store readable by the daemon's user only, and tightens one left behind
+/// by an older daemon.
+fn create_key_store(path: &Path) -> Result<()> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    Ok(())
+}
+
+/// Binds the control socket. A socket file left behind by a crashed daemon is replaced, but
+/// a live daemon on the same path is reported instead of being hijacked.
+pub fn bind_socket(path: &Path) -> Result<UnixListener> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            anyhow::bail!("A daemon is already listening on {}", path.display());
+        }
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("Failed to listen on {}", path.display()))?;
+    // Owner and group: a team shares the daemon through a common group
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o660))?;
+    Ok(listener)
+}
+
+/// Runs the daemon in the foreground until it is killed.
+pub fn run_daemon(state_dir: &Path, socket: &Path, threads: usize) -> Result<()> {
+    let daemon = Arc::new(Daemon::open(state_dir, threads)?);
+    let listener = bind_socket(socket)?;
+    let queued = daemon
+        .state
+        .lock()
+        .unwrap()
+        .jobs
+        .iter()
+        .filter(|job| job.state == JobState::Queued)
+        .count();
+
+    say!("🗄️ meshcore-keygen daemon on {}", socket.display());
+    say!(
+        "   📂 State: {} | 💻 {} threads | ⏳ {} job(s) waiting",
+        daemon.checkpoint.path().display(),
+        threads,
+        queued
+    );
+
+    let scheduler = Arc::clone(&daemon);
+    let scheduler_handle = std::thread::spawn(move || scheduler.run_scheduler());
+    let result = daemon.serve(listener);
+
+    // Without a socket nobody can reach the jobs; pause them so the checkpoint is clean
+    daemon.shutdown();
+    scheduler_handle.join().unwrap();
+    result
+}
+
+/// Sends one request to a daemon and waits for its answer.
+pub fn request(socket: &Path, request: &Request) -> Result<Response> {
+    let stream = UnixStream::connect(socket).with_context(|| {
+        format!(
+            "No daemon on {} (start one with meshcore-keygen daemon)",
+            socket.display()
+        )
+    })?;
+    let mut writer = &stream;
+    writeln!(writer, "{}", serde_json::to_string(request)?)?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let response: Response =
+        serde_json::from_str(&line).context("The daemon sent a malformed response")?;
+    if let Response::Error { message } = response {
+        anyhow::bail!(message);
+    }
+    Ok(response)
+}
+
+/// Client side of `queue`: sends the request and prints the answer.
+pub fn run_queue_request(socket: &Path, request_to_send: Request) -> Result<()> {
+    match request(socket, &request_to_send)? {
+        Response::Submitted { id } => say!("📥 Queued as job #{}", id),
+        Response::Cancelled { id } => say!("🛑 Job #{} cancelled", id),
+        Response::Jobs { jobs } => {
+            if jobs.is_empty() {
+                say!("📭 No jobs");
+            }
+            for job in jobs {
+                println!("{}", describe_job(&job));
+            }
+        }
+        Response::Results {
+            id,
+            state,
+            output,
+            public_keys,
+        } => {
+            say!(
+                "🔑 Job #{} ({}): {} key(s) in {}",
+                id,
+                state.label(),
+                public_keys.len(),
+                output.display()
+            );
+            for public_key in public_keys {
+                println!("{}", public_key);
+            }
+        }
+        Response::Error { message } => anyhow::bail!(message),
+    }
+    Ok(())
+}
+
+/// One status line per job.
+fn describe_job(job: &Job) -> String {
+    let mut line = format!(
+        "#{} {} {} | {}/{} key(s) | {} attempts in {}",
+        job.id,
+        job.state.label(),
+        job.spec.pattern,
+        job.found.len(),
+        job.spec.count,
+        format_large_number(job.attempts),
+        format_duration(job.run_secs)
+    );
+    if let Some(budget) = job.spec.budget_secs {
+        line.push_str(&format!(" of {}", format_duration(budget as f64)));
+    }
+    if job.spec.priority != 0 {
+        line.push_str(&format!(" | priority {}", job.spec.priority));
+    }
+    if let Some(error) = &job.error {
+        line.push_str(&format!(" | {}", error));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(pattern: &str, count: usize, priority: i32) -> JobSpec {
+        JobSpec {
+            pattern: pattern.to_string(),
+            count,
+            budget_secs: None,
+            output: None,
+            priority,
+            excluded_hashes: Vec::new(),
+        }
+    }
+
+    fn wait_for(socket: &Path, id: u64, wanted: JobState) -> Job {
+        let deadline = Instant::now() + Duration::from_secs(30);
+        loop {
+            let Response::Jobs { mut jobs } =
+                request(socket, &Request::Status { id: Some(id) }).unwrap()
+            else {
+                panic!("expected a job list");
+            };
+            let job = jobs.remove(0);
+            if job.state == wanted {
+                return job;
+            }
+            assert!(
+                Instant::now() < deadline,
+                "job #{} stuck in {:?}",
+                id,
+                job.state
+            );
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    #[test]
+    fn test_parse_budget() {
+        assert_eq!(parse_budget("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_budget("30m").unwrap(), Duration::from_secs(1800));
+        assert_eq!(parse_budget("2h").unwrap(), Duration::from_secs(7200));
+        assert_eq!(parse_budget("1d").unwrap(), Duration::from_secs(86400));
+        assert!(parse_budget("0s").is_err());
+        assert!(parse_budget("5w").is_err());
+        assert!(parse_budget("m").is_err());
+    }
+
+    #[test]
+    fn test_queue_order() {
+        let mut state = QueueState::default();
+        for (id, priority) in [(1, 0), (2, 5), (3, 5)] {
+            state.jobs.push(Job {
+                id,
+                spec: spec("A", 1, priority),
+                state: JobState::Queued,
+                output: PathBuf::new(),
+                attempts: 0,
+                run_secs: 0.0,
+                found: Vec::new(),
+                error: None,
+            });
+        }
+        assert_eq!(state.next_queued().unwrap().id, 2);
+        state.jobs[1].state = JobState::Done;
+        assert_eq!(state.next_queued().unwrap().id, 3);
+    }
+
+    #[test]
+    fn test_daemon_over_unix_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("daemon.sock");
+        let daemon = Arc::new(Daemon::open(dir.path(), 1).unwrap());
+        let listener = bind_socket(&socket).unwrap();
+        assert!(bind_socket(&socket).is_err());
+
+        let server = Arc::clone(&daemon);
+        let server_handle = std::thread::spawn(move || server.serve(listener));
+        let scheduler = Arc::clone(&daemon);
+        let scheduler_handle = std::thread::spawn(move || scheduler.run_scheduler());
+
+        // A practically impossible job first, then an easy one that pauses it
+        let Response::Submitted { id: slow } = request(
+            &socket,
+            &Request::Submit {
+                spec: spec("ABCDEF0123", 1, 0),
+            },
+        )
+        .unwrap() else {
+            panic!("expected a job id");
+        };
+        wait_for(&socket, slow, JobState::Running);
+
+        let output = dir.path().join("easy-keys.txt");
+        let mut easy = spec("C", 2, 10);
+        easy.output = Some(PathBuf::from("easy-keys.txt"));
+        // Leaves CF as the only node hash the job may use
+        easy.excluded_hashes = (0xC0..=0xCE).collect();
+        let Response::Submitted { id: quick } =
+            request(&socket, &Request::Submit { spec: easy }).unwrap()
+        else {
+            panic!("expected a job id");
+        };
+        wait_for(&socket, quick, JobState::Done);
+
+        let Response::Results { public_keys, .. } =
+            request(&socket, &Request::Results { id: quick }).unwrap()
+        else {
+            panic!("expected results");
+        };
+        assert_eq!(public_keys.len(), 2);
+        let stored = crate::keystore::load_keys(output.to_str().unwrap()).unwrap();
+        assert_eq!(stored.len(), 2);
+        assert!(stored.iter().all(|key| key.public_key.starts_with("CF")));
+        let mode = std::fs::metadata(&output).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // The paused job resumes, and can be cancelled while running
+        wait_for(&socket, slow, JobState::Running);
+        request(&socket, &Request::Cancel { id: slow }).unwrap();
+        wait_for(&socket, slow, JobState::Cancelled);
+        assert!(request(&socket, &Request::Cancel { id: slow }).is_err());
+
+        let invalid = Request::Submit {
+            spec: spec("XYZ", 1, 0),
+        };
+        assert!(request(&socket, &invalid).is_err());
+        let mut unreachable = spec("C", 1, 0);
+        unreachable.excluded_hashes = (0xC0..=0xCF).collect();
+        let unreachable = Request::Submit { spec: unreachable };
+        assert!(request(&socket, &unreachable).is_err());
+
+        // Key stores can't be put outside the state dir
+        for path in [
+            "/tmp/keys.txt",
+            "../keys.txt",
+            "sub/keys.txt",
+            "daemon-state.json",
+        ] {
+            let mut outside = spec("C", 1, 0);
+            outside.output = Some(PathBuf::from(path));
+            assert!(request(&socket, &Request::Submit { spec: outside }).is_err());
+        }
+
+        daemon.shutdown();
+        server_handle.join().unwrap().unwrap();
+        scheduler_handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_daemon_resumes_from_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let daemon = Daemon::open(dir.path(), 1).unwrap();
+            daemon.submit(spec("ABCDEF0123", 1, 0)).unwrap();
+            let mut state = daemon.state.lock().unwrap();
+            let job = state.job_mut(1).unwrap();
+            job.state = JobState::Running;
+            job.attempts = 1234;
+            daemon.save(&state);
+        }
+
+        // As if the daemon had been killed mid-job
+        let daemon = Daemon::open(dir.path(), 1).unwrap();
+        let state = daemon.state.lock().unwrap();
+        assert_eq!(state.jobs[0].state, JobState::Queued);
+        assert_eq!(state.jobs[0].attempts, 1234);
+        assert_eq!(state.next_id, 1);
+    }
+}
@@ -0,0 +1,1050 @@
+use crate::cpu::CpuKeySearcher;
+use crate::keygen::{emit_found, print_search_summary};
+use crate::keystore::{append_key, format_key_line, parse_key_line};
+use crate::output::{Event, output, say, say_inline, say_warning};
+use crate::pattern::PatternSet;
+use crate::performance::SearchEstimate;
+use crate::types::{FoundKey, NodeHashFilter, SearchBehavior, SearchConfig, SearchStats};
+use crate::utils::{format_duration, format_large_number};
+use anyhow::{Context, Result};
+use crossbeam::channel;
+use crypto_box::aead::{Aead, AeadCore, OsRng};
+use crypto_box::{Nonce, PublicKey, SalsaBox, SecretKey};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+/// Bumped whenever a message changes, so mismatched builds fail the handshake instead of
+/// misreading each other.
+const PROTOCOL_VERSION: u32 = 1;
+
+/// Port `serve` listens on and `join` connects to when none is given.
+pub const DEFAULT_PORT: u16 = 7447;
+
+/// Environment variable holding the shared secret both sides authenticate with.
+pub const TOKEN_ENV: &str = "MESHCORE_KEYGEN_TOKEN";
+
+/// Anything shorter is too easy to brute-force from a captured handshake.
+const MIN_TOKEN_LEN: usize = 16;
+
+/// Messages are a few hundred bytes; the cap keeps unauthenticated peers from filling memory.
+const MAX_MESSAGE_BYTES: usize = 16 * 1024;
+
+/// How long a peer may take to complete the handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often workers report their attempt count.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Reads wake up this often so connection threads notice the end of the search.
+const READ_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// A worker that hasn't reported for this long is assumed gone and disconnected.
+const WORKER_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How often the coordinator prints the combined progress line.
+const STATUS_INTERVAL: Duration = Duration::from_secs(3);
+
+/// One line of JSON per message. The handshake proves both sides know the shared token
+/// (HMAC-SHA256 over both nonces and the session keys), and binds each side's X25519 session
+/// key, so found keys can be boxed from the worker's key to the coordinator's without
+/// anyone else being able to read or forge them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message {
+    /// Coordinator → worker, right after connecting.
+    Challenge { version: u32, nonce: String },
+    /// Worker → coordinator: who it is and proof that it knows the token.
+    Hello {
+        version: u32,
+        name: String,
+        threads: usize,
+        nonce: String,
+        box_public_key: String,
+        proof: String,
+    },
+    /// Coordinator → worker: what to search for, plus proof that the coordinator knows the
+    /// token too, so a worker never spends its CPU on an impostor's job.
+    Job {
+        pattern: String,
+        excluded_hashes: Vec<u8>,
+        box_public_key: String,
+        proof: String,
+    },
+    /// Worker → coordinator: attempts made since joining, sent every second.
+    Progress { attempts: u64 },
+    /// Worker → coordinator: a matching key, its key store line sealed to the coordinator.
+    Found {
+        public_key: String,
+        nonce: String,
+        ciphertext: String,
+    },
+    /// Coordinator → worker: the search is over.
+    Stop,
+}
+
+/// Newline-delimited JSON reader that survives read timeouts: a partial line stays buffered
+/// until the rest arrives, so timeouts can be used to poll for shutdown.
+struct MessageReader {
+    reader: BufReader<TcpStream>,
+    pending: Vec<u8>,
+}
+
+impl MessageReader {
+    /// Returns `None` when nothing complete arrived before the read timeout.
+    fn receive(&mut self) -> Result<Option<Message>> {
+        let limit = (MAX_MESSAGE_BYTES - self.pending.len()) as u64;
+        match (&mut self.reader)
+            .take(limit)
+            .read_until(b'\n', &mut self.pending)
+        {
+            Ok(_) if self.pending.ends_with(b"\n") => {
+                let message = serde_json::from_slice(&self.pending);
+                self.pending.clear();
+                Ok(Some(message.context("Malformed message")?))
+            }
+            Ok(_) if self.pending.len() >= MAX_MESSAGE_BYTES => {
+                anyhow::bail!("Message exceeds {} bytes", MAX_MESSAGE_BYTES)
+            }
+            Ok(_) => anyhow::bail!("Connection closed"),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Waits for the next message, giving up at the deadline.
+    fn receive_before(&mut self, deadline: Instant) -> Result<Message> {
+        loop {
+            if let Some(message) = self.receive()? {
+                return Ok(message);
+            }
+            if Instant::now() >= deadline {
+                anyhow::bail!("Timed out waiting for the peer");
+            }
+        }
+    }
+}
+
+struct MessageWriter(TcpStream);
+
+impl MessageWriter {
+    fn send(&mut self, message: &Message) -> Result<()> {
+        let mut line = serde_json::to_vec(message)?;
+        line.push(b'\n');
+        self.0.write_all(&line)?;
+        Ok(())
+    }
+}
+
+/// Splits a connection into its reading and writing halves, with the polling read timeout.
+fn split_connection(stream: TcpStream) -> Result<(MessageReader, MessageWriter)> {
+    stream.set_nonblocking(false)?;
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(READ_POLL_INTERVAL))?;
+    stream.set_write_timeout(Some(WORKER_TIMEOUT))?;
+    let writer = MessageWriter(stream.try_clone()?);
+    Ok((
+        MessageReader {
+            reader: BufReader::new(stream),
+            pending: Vec::new(),
+        },
+        writer,
+    ))
+}
+
+/// Reads the shared token from `--token-file` or the environment. Never from argv, where it
+/// would end up in `ps` output and shell history.
+pub fn load_token(token_file: Option<&str>) -> Result<Zeroizing<String>> {
+    let token = match token_file {
+        Some(path) => Zeroizing::new(
+            std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read token file {}", path))?,
+        ),
+        None => Zeroizing::new(std::env::var(TOKEN_ENV).with_context(|| {
+            format!(
+                "Set {} or pass --token-file with a shared secret",
+                TOKEN_ENV
+            )
+        })?),
+    };
+    let token = Zeroizing::new(token.trim().to_string());
+    if token.len() < MIN_TOKEN_LEN {
+        anyhow::bail!(
+            "The shared token must be at least {} characters long",
+            MIN_TOKEN_LEN
+        );
+    }
+    Ok(token)
+}
+
+/// HMAC over length-prefixed fields, so no two different field lists authenticate the same bytes.
+fn prove(token: &str, role: &str, fields: &[&[u8]]) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(token.as_bytes()).expect("HMAC takes keys of any length");
+    for field in std::iter::once(&role.as_bytes()).chain(fields) {
+        mac.update(&(field.len() as u64).to_le_bytes());
+        mac.update(field);
+    }
+    mac
+}
+
+fn worker_proof_fields<'a>(
+    challenge: &'a str,
+    nonce: &'a str,
+    box_public_key: &'a str,
+) -> [&'a [u8]; 3] {
+    [
+        challenge.as_bytes(),
+        nonce.as_bytes(),
+        box_public_key.as_bytes(),
+    ]
+}
+
+fn job_proof_fields<'a>(
+    challenge: &'a str,
+    nonce: &'a str,
+    pattern: &'a str,
+    excluded_hashes: &'a [u8],
+    box_public_key: &'a str,
+) -> [&'a [u8]; 5] {
+    [
+        challenge.as_bytes(),
+        nonce.as_bytes(),
+        pattern.as_bytes(),
+        excluded_hashes,
+        box_public_key.as_bytes(),
+    ]
+}
+
+/// Checks a hex proof in constant time.
+fn verify_proof(mac: Hmac<Sha256>, proof: &str) -> Result<()> {
+    let proof = hex::decode(proof).context("Proof is not hex")?;
+    mac.verify_slice(&proof)
+        .map_err(|_| anyhow::anyhow!("Authentication failed, check the shared token"))
+}
+
+fn random_nonce() -> String {
+    let mut nonce = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    hex::encode_upper(nonce)
+}
+
+fn decode_box_public_key(text: &str) -> Result<PublicKey> {
+    let bytes = hex::decode(text).context("Session key is not hex")?;
+    PublicKey::from_slice(&bytes).map_err(|_| anyhow::anyhow!("Session key must be 32 bytes"))
+}
+
+/// Sealing goes through the private key store line, so the coordinator stores exactly what a
+/// local search would have written.
+fn seal_found_key(
+    key: &FoundKey,
+    worker_secret: &SecretKey,
+    coordinator: &PublicKey,
+) -> Result<Message> {
+    let nonce = SalsaBox::generate_nonce(&mut OsRng);
+    let ciphertext = SalsaBox::new(coordinator, worker_secret)
+        .encrypt(&nonce, format_key_line(key).as_bytes())
+        .map_err(|_| anyhow::anyhow!("Failed to seal the found key"))?;
+    Ok(Message::Found {
+        public_key: key.public_key.clone(),
+        nonce: hex::encode_upper(nonce),
+        ciphertext: hex::encode_upper(ciphertext),
+    })
+}
+
+/// Opens a sealed key and checks it really is a key this job asked for: workers are trusted
+/// with keys, but not to be bug-free or to run the same pattern semantics.
+fn open_found_key(
+    session: &SalsaBox,
+    public_key: &str,
+    nonce: &str,
+    ciphertext: &str,
+    patterns: &PatternSet,
+    excluded_hashes: &NodeHashFilter,
+) -> Result<FoundKey> {
+    let nonce = hex::decode(nonce).context("Nonce is not hex")?;
+    if nonce.len() != 24 {
+        anyhow::bail!("Nonce must be 24 bytes");
+    }
+    let ciphertext = hex::decode(ciphertext).context("Ciphertext is not hex")?;
+    let line = Zeroizing::new(
+        session
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| anyhow::anyhow!("Sealed key failed to decrypt"))?,
+    );
+    let line = std::str::from_utf8(&line).context("Sealed key is not text")?;
+
+    let key = parse_key_line(line)?;
+    if !key.public_key.eq_ignore_ascii_case(public_key) {
+        anyhow::bail!("Sealed key does not belong to public key {}", public_key);
+    }
+    let mut public_bytes = [0u8; 32];
+    hex::decode_to_slice(&key.public_key, &mut public_bytes)?;
+    if !patterns.matches(&public_bytes) || !excluded_hashes.allows(public_bytes[0]) {
+        anyhow::bail!("Key {} does not match the job", key.public_key);
+    }
+    Ok(key)
+}
+
+/// What the coordinator knows about a worker, connected or gone.
+#[derive(Debug, Clone)]
+struct WorkerRecord {
+    name: String,
+    threads: usize,
+    attempts: u64,
+    found: usize,
+    connected: bool,
+}
+
+/// State shared between the coordinator's accept loop and its connection threads.
+struct Shared {
+    token: Zeroizing<String>,
+    pattern: String,
+    patterns: PatternSet,
+    excluded_hashes: NodeHashFilter,
+    box_secret: SecretKey,
+    workers: Mutex<Vec<WorkerRecord>>,
+    stop: AtomicBool,
+    found_sender: channel::Sender<(FoundKey, usize)>,
+}
+
+impl Shared {
+    fn total_attempts(&self) -> u64 {
+        self.workers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|w| w.attempts)
+            .sum()
+    }
+
+    fn update_worker(&self, id: usize, update: impl FnOnce(&mut WorkerRecord)) {
+        update(&mut self.workers.lock().unwrap()[id]);
+    }
+}
+
+/// Hands the job out to every worker that joins and collects what they find.
+/// Every worker gets the whole pattern set: the search is random, so splitting it would not
+/// save any work, and a worker dropping out loses nothing but the time it spent.
+pub struct Coordinator {
+    listener: TcpListener,
+    config: SearchConfig,
+    shared: Arc<Shared>,
+    found_receiver: channel::Receiver<(FoundKey, usize)>,
+}
+
+impl Coordinator {
+    /// Binds right away so a taken port is reported before anything else happens.
+    pub fn bind(addr: SocketAddr, config: SearchConfig, token: Zeroizing<String>) -> Result<Self> {
+        let listener =
+            TcpListener::bind(addr).with_context(|| format!("Failed to listen on {}", addr))?;
+        // Non-blocking accept lets the accept loop notice the end of the search
+        listener.set_nonblocking(true)?;
+
+        let (found_sender, found_receiver) = channel::unbounded();
+        let shared = Arc::new(Shared {
+            token,
+            pattern: config.prefix.clone(),
+            patterns: PatternSet::compile(&config.prefix)?,
+            excluded_hashes: config.excluded_hashes.clone(),
+            // A fresh session key per run: nothing sealed to an earlier run can be replayed
+            box_secret: SecretKey::generate(&mut OsRng),
+            workers: Mutex::new(Vec::new()),
+            stop: AtomicBool::new(false),
+            found_sender,
+        });
+
+        Ok(Self {
+            listener,
+            config,
+            shared,
+            found_receiver,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Runs until the requested number of keys has been stored in `keys_file`.
+    /// Returns how many keys were found.
+    pub fn run(self, keys_file: &str) -> Result<usize> {
+        let Self {
+            listener,
+            config,
+            shared,
+            found_receiver,
+        } = self;
+        let estimate = SearchEstimate::for_patterns(&shared.patterns, &shared.excluded_hashes);
+        let search_started = Instant::now();
+        output().emit(&Event::Started {
+            pattern: config.prefix.clone(),
+            threads: 0,
+            max_keys: match config.search_behavior {
+                SearchBehavior::FindN(n) => Some(n),
+                SearchBehavior::Continuous => None,
+            },
+            probability: estimate.probability,
+            expected_attempts: estimate.expected_attempts,
+        });
+
+        let connections: Arc<Mutex<Vec<JoinHandle<()>>>> = Arc::default();
+        let accept_shared = Arc::clone(&shared);
+        let accept_connections = Arc::clone(&connections);
+        let accept_handle = std::thread::spawn(move || {
+            while !accept_shared.stop.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, peer)) => {
+                        let shared = Arc::clone(&accept_shared);
+                        let handle = std::thread::spawn(move || {
+                            serve_worker(stream, peer, &shared);
+                        });
+                        accept_connections.lock().unwrap().push(handle);
+                    }
+                    Err(_) => std::thread::sleep(READ_POLL_INTERVAL),
+                }
+            }
+        });
+
+        let mut total_found = 0usize;
+        let mut last_status = Instant::now();
+        let mut last_attempts = 0u64;
+        loop {
+            let target_reached = match config.search_behavior {
+                SearchBehavior::FindN(n) => total_found >= n,
+                SearchBehavior::Continuous => false,
+            };
+            if target_reached {
+                break;
+            }
+
+            match found_receiver.recv_timeout(READ_POLL_INTERVAL) {
+                Ok((found_key, worker_id)) => {
+                    let worker_name = shared.workers.lock().unwrap()[worker_id].name.clone();
+                    total_found += 1;
+                    emit_found(&found_key, total_found, search_started);
+                    if output().is_quiet() {
+                        println!("{}", found_key.public_key);
+                    }
+                    say!(
+                        "\n🎉✨ BOOM! Found key #{} 💎🔥\n   Public Key: {}\n   🖥️ Found by: {}",
+                        total_found,
+                        found_key.public_key,
+                        worker_name
+                    );
+                    if let Err(e) = append_key(keys_file, &found_key) {
+                        say_warning!("😤 Ugh, error logging key (but we found it anyway!): {}", e);
+                    }
+                }
+                Err(channel::RecvTimeoutError::Timeout) => {}
+                Err(channel::RecvTimeoutError::Disconnected) => break,
+            }
+
+            if last_status.elapsed() >= STATUS_INTERVAL {
+                let attempts = shared.total_attempts();
+                let keys_per_sec =
+                    (attempts - last_attempts) as f64 / last_status.elapsed().as_secs_f64();
+                let (connected, threads) = {
+                    let workers = shared.workers.lock().unwrap();
+                    let connected = workers.iter().filter(|w| w.connected);
+                    (
+                        connected.clone().count(),
+                        connected.map(|w| w.threads).sum::<usize>(),
+                    )
+                };
+                output().emit(&Event::Progress {
+                    attempts,
+                    matches: total_found,
+                    keys_per_sec,
+                    elapsed_secs: search_started.elapsed().as_secs_f64(),
+                    probability_so_far: estimate.probability_within(attempts as f64),
+                });
+                say_inline!(
+                    "\r\x1B[K🚀 Attempts: {} | ✨ Found: {} | ⚡️ Keys/sec: {:.0} | 🖥️ Workers: {} ({} threads) | 🕐 Running: {}",
+                    format_large_number(attempts),
+                    total_found,
+                    keys_per_sec,
+                    connected,
+                    threads,
+                    format_duration(search_started.elapsed().as_secs_f64())
+                );
+                last_status = Instant::now();
+                last_attempts = attempts;
+            }
+        }
+
+        // Connection threads send Stop to their workers once they see the flag
+        shared.stop.store(true, Ordering::Relaxed);
+        accept_handle.join().unwrap();
+        for handle in std::mem::take(&mut *connections.lock().unwrap()) {
+            handle.join().unwrap();
+        }
+
+        let attempts = shared.total_attempts();
+        output().emit(&Event::Finished {
+            found: total_found,
+            attempts,
+            elapsed_secs: search_started.elapsed().as_secs_f64(),
+        });
+        print_worker_breakdown(&shared.workers.lock().unwrap());
+        Ok(total_found)
+    }
+}
+
+/// Per-worker totals at the end of a distributed run.
+fn print_worker_breakdown(workers: &[WorkerRecord]) {
+    if workers.is_empty() {
+        return;
+    }
+    say!("\n\n🖥️ Per-worker breakdown:");
+    for worker in workers {
+        say!(
+            "   {}: {} threads, {} attempts, {} key(s){}",
+            worker.name,
+            worker.threads,
+            format_large_number(worker.attempts),
+            worker.found,
+            if worker.connected {
+                ""
+            } else {
+                " (left early)"
+            }
+        );
+    }
+}
+
+/// Authenticates one worker, sends it the job and relays its reports until the search ends,
+/// the worker leaves or it goes silent.
+fn serve_worker(stream: TcpStream, peer: SocketAddr, shared: &Shared) {
+    let (mut reader, mut writer) = match split_connection(stream) {
+        Ok(halves) => halves,
+        Err(e) => {
+            say_warning!("\n🚫 Couldn't set up the connection from {}: {}", peer, e);
+            return;
+        }
+    };
+
+    let joined = match handshake_worker(&mut reader, &mut writer, peer, shared) {
+        Ok(joined) => joined,
+        Err(e) => {
+            say_warning!("\n🚫 Rejected worker {}: {}", peer, e);
+            return;
+        }
+    };
+
+    let result = relay_worker(&mut reader, &joined, shared);
+    // The search may be over, but the worker may also just have vanished
+    if shared.stop.load(Ordering::Relaxed) {
+        let _ = writer.send(&Message::Stop);
+    }
+    if let Err(e) = result {
+        shared.update_worker(joined.id, |worker| worker.connected = false);
+        say_warning!("\n👋 Worker {} left: {}", joined.name, e);
+    }
+}
+
+fn handshake_worker(
+    reader: &mut MessageReader,
+    writer: &mut MessageWriter,
+    peer: SocketAddr,
+    shared: &Shared,
+) -> Result<JoinedWorker> {
+    let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+    let challenge = random_nonce();
+    writer.send(&Message::Challenge {
+        version: PROTOCOL_VERSION,
+        nonce: challenge.clone(),
+    })?;
+
+    let Message::Hello {
+        version,
+        name,
+        threads,
+        nonce,
+        box_public_key,
+        proof,
+    } = reader.receive_before(deadline)?
+    else {
+        anyhow::bail!("Expected a hello");
+    };
+    if version != PROTOCOL_VERSION {
+        anyhow::bail!(
+            "Protocol version {} is not supported (expected {})",
+            version,
+            PROTOCOL_VERSION
+        );
+    }
+    verify_proof(
+        prove(
+            &shared.token,
+            "worker",
+            &worker_proof_fields(&challenge, &nonce, &box_public_key),
+        ),
+        &proof,
+    )?;
+    let worker_box_key = decode_box_public_key(&box_public_key)?;
+
+    let excluded_hashes: Vec<u8> = (0..=255u8)
+        .filter(|&hash| !shared.excluded_hashes.allows(hash))
+        .collect();
+    let coordinator_box_key = hex::encode_upper(shared.box_secret.public_key().as_bytes());
+    let proof = prove(
+        &shared.token,
+        "coordinator",
+        &job_proof_fields(
+            &challenge,
+            &nonce,
+            &shared.pattern,
+            &excluded_hashes,
+            &coordinator_box_key,
+        ),
+    );
+    writer.send(&Message::Job {
+        pattern: shared.pattern.clone(),
+        excluded_hashes,
+        box_public_key: coordinator_box_key,
+        proof: hex::encode_upper(proof.finalize().into_bytes()),
+    })?;
+
+    let name = match name.trim() {
+        "" => peer.to_string(),
+        name => format!("{} ({})", name, peer),
+    };
+    let id = {
+        let mut workers = shared.workers.lock().unwrap();
+        workers.push(WorkerRecord {
+            name: name.clone(),
+            threads,
+            attempts: 0,
+            found: 0,
+            connected: true,
+        });
+        workers.len() - 1
+    };
+    say!("\n🤝 Worker {} joined with {} threads", name, threads);
+
+    Ok(JoinedWorker {
+        id,
+        name,
+        session: SalsaBox::new(&worker_box_key, &shared.box_secret),
+    })
+}
+
+/// A worker that passed the handshake.
+struct JoinedWorker {
+    id: usize,
+    name: String,
+    /// Box between the two session keys; only this worker can seal keys that open with it.
+    session: SalsaBox,
+}
+
+fn relay_worker(reader: &mut MessageReader, worker: &JoinedWorker, shared: &Shared) -> Result<()> {
+    let id = worker.id;
+    let mut last_seen = Instant::now();
+    while !shared.stop.load(Ordering::Relaxed) {
+        let Some(message) = reader.receive()? else {
+            if last_seen.elapsed() >= WORKER_TIMEOUT {
+                anyhow::bail!(
+                    "no report for {}",
+                    format_duration(WORKER_TIMEOUT.as_secs_f64())
+                );
+            }
+            continue;
+        };
+        last_seen = Instant::now();
+
+        match message {
+            Message::Progress { attempts } => {
+                shared.update_worker(id, |worker| worker.attempts = worker.attempts.max(attempts))
+            }
+            Message::Found {
+                public_key,
+                nonce,
+                ciphertext,
+            } => {
+                match open_found_key(
+                    &worker.session,
+                    &public_key,
+                    &nonce,
+                    &ciphertext,
+                    &shared.patterns,
+                    &shared.excluded_hashes,
+                ) {
+                    Ok(key) => {
+                        shared.update_worker(id, |worker| worker.found += 1);
+                        let _ = shared.found_sender.send((key, id));
+                    }
+                    Err(e) => say_warning!("\n😤 Discarded a key from {}: {}", worker.name, e),
+                }
+            }
+            other => anyhow::bail!("unexpected message {:?}", other),
+        }
+    }
+    Ok(())
+}
+
+/// Resolves `HOST[:PORT]`, falling back to the default port.
+fn resolve_coordinator(addr: &str) -> Result<SocketAddr> {
+    addr.to_socket_addrs()
+        .or_else(|_| (addr, DEFAULT_PORT).to_socket_addrs())
+        .with_context(|| format!("Couldn't resolve coordinator address {}", addr))?
+        .next()
+        .with_context(|| format!("Coordinator address {} resolved to nothing", addr))
+}
+
+/// The job a coordinator handed out, after its proof checked out.
+struct AcceptedJob {
+    config: SearchConfig,
+    coordinator_key: PublicKey,
+}
+
+fn handshake_coordinator(
+    reader: &mut MessageReader,
+    writer: &mut MessageWriter,
+    token: &str,
+    name: &str,
+    threads: usize,
+    box_secret: &SecretKey,
+) -> Result<AcceptedJob> {
+    let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+    let Message::Challenge {
+        version,
+        nonce: challenge,
+    } = reader.receive_before(deadline)?
+    else {
+        anyhow::bail!("Expected a challenge from the coordinator");
+    };
+    if version != PROTOCOL_VERSION {
+        anyhow::bail!(
+            "Coordinator speaks protocol version {}, this build speaks {}",
+            version,
+            PROTOCOL_VERSION
+        );
+    }
+
+    let nonce = random_nonce();
+    let box_public_key = hex::encode_upper(box_secret.public_key().as_bytes());
+    let proof = prove(
+        token,
+        "worker",
+        &worker_proof_fields(&challenge, &nonce, &box_public_key),
+    );
+    writer.send(&Message::Hello {
+        version: PROTOCOL_VERSION,
+        name: name.to_string(),
+        threads,
+        nonce: nonce.clone(),
+        box_public_key,
+        proof: hex::encode_upper(proof.finalize().into_bytes()),
+    })?;
+
+    let Message::Job {
+        pattern,
+        excluded_hashes,
+        box_public_key,
+        proof,
+    } = reader
+        .receive_before(deadline)
+        .context("The coordinator rejected us, check the shared token")?
+    else {
+        anyhow::bail!("Expected a job from the coordinator");
+    };
+    verify_proof(
+        prove(
+            token,
+            "coordinator",
+            &job_proof_fields(
+                &challenge,
+                &nonce,
+                &pattern,
+                &excluded_hashes,
+                &box_public_key,
+            ),
+        ),
+        &proof,
+    )?;
+
+    // Validated here too, so a bad job fails the join instead of panicking a worker thread
+    PatternSet::compile(&pattern).context("Coordinator sent an invalid pattern")?;
+    let mut filter = NodeHashFilter::default();
+    for hash in excluded_hashes {
+        filter.exclude(hash);
+    }
+
+    Ok(AcceptedJob {
+        config: SearchConfig {
+            excluded_hashes: filter,
+            ..SearchConfig::new(pattern, SearchBehavior::Continuous, threads)
+        },
+        coordinator_key: decode_box_public_key(&box_public_key)?,
+    })
+}
+
+/// Searches for a coordinator until it says stop. Found keys are sealed and sent, never
+/// written to this machine. Returns how many keys were handed over.
+pub fn run_join(addr: &str, token: &str, name: &str, threads: usize) -> Result<usize> {
+    let coordinator = resolve_coordinator(addr)?;
+    let stream = TcpStream::connect_timeout(&coordinator, HANDSHAKE_TIMEOUT)
+        .with_context(|| format!("Couldn't connect to the coordinator at {}", coordinator))?;
+    let (mut reader, mut writer) = split_connection(stream)?;
+
+    let box_secret = SecretKey::generate(&mut OsRng);
+    let job = handshake_coordinator(&mut reader, &mut writer, token, name, threads, &box_secret)?;
+    say!(
+        "🤝 Joined the coordinator at {}: pattern {}, {} threads",
+        coordinator,
+        job.config.prefix,
+        threads
+    );
+
+    let config = Arc::new(job.config);
+    let stats = Arc::new(SearchStats::with_workers(threads));
+    let (found_sender, found_receiver) = channel::unbounded();
+    let worker_handles = CpuKeySearcher::spawn_workers(
+        threads,
+        Arc::clone(&config),
+        Arc::clone(&stats),
+        found_sender,
+    );
+
+    // The coordinator only ever talks to say stop; losing it means stopping too
+    let stopped_by_coordinator = Arc::new(AtomicBool::new(false));
+    let reader_stats = Arc::clone(&stats);
+    let reader_stopped = Arc::clone(&stopped_by_coordinator);
+    let reader_handle = std::thread::spawn(move || -> Result<()> {
+        let result = loop {
+            if reader_stats.stop_search.load(Ordering::Relaxed) {
+                break Ok(());
+            }
+            match reader.receive() {
+                Ok(Some(Message::Stop)) => {
+                    reader_stopped.store(true, Ordering::Relaxed);
+                    break Ok(());
+                }
+                Ok(Some(other)) => break Err(anyhow::anyhow!("Unexpected message {:?}", other)),
+                Ok(None) => {}
+                Err(e) => break Err(e),
+            }
+        };
+        reader_stats.stop_search.store(true, Ordering::Relaxed);
+        result
+    });
+
+    let mut sent = 0usize;
+    let mut send_result = Ok(());
+    let mut last_progress = Instant::now();
+    while !stats.stop_search.load(Ordering::Relaxed) {
+        if let Ok(found_key) = found_receiver.recv_timeout(PROGRESS_INTERVAL) {
+            let sealed = seal_found_key(&found_key, &box_secret, &job.coordinator_key)?;
+            if let Err(e) = writer.send(&sealed) {
+                send_result = Err(e);
+                break;
+            }
+            sent += 1;
+            say!("\n📤 Sent key #{} to the coordinator", sent);
+        }
+
+        // On a timer rather than when idle, so a stream of matches can't starve the counts
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            let attempts = stats.total_attempts.load(Ordering::Relaxed);
+            if let Err(e) = writer.send(&Message::Progress { attempts }) {
+                send_result = Err(e);
+                break;
+            }
+            say_inline!(
+                "\r\x1B[K🚀 Attempts: {} | 📤 Sent: {} | 🕐 Running: {}",
+                format_large_number(attempts),
+                sent,
+                format_duration(stats.elapsed().as_secs_f64())
+            );
+            last_progress = Instant::now();
+        }
+    }
+
+    stats.stop_search.store(true, Ordering::Relaxed);
+    for handle in worker_handles {
+        handle.join().unwrap();
+    }
+    let reader_result = reader_handle.join().unwrap();
+
+    if stopped_by_coordinator.load(Ordering::Relaxed) {
+        say!(
+            "\n\n🏁 The coordinator ended the search, {} key(s) sent",
+            sent
+        );
+        return Ok(sent);
+    }
+    send_result
+        .and(reader_result)
+        .context("Lost the connection to the coordinator")?;
+    Ok(sent)
+}
+
+/// Runs the coordinator for `serve`, storing keys in the configured key store.
+pub fn run_serve(config: SearchConfig, listen: SocketAddr, token: Zeroizing<String>) -> Result<()> {
+    let keys_file = config.keys_file.clone();
+    let coordinator = Coordinator::bind(listen, config, token)?;
+    say!(
+        "🛰️ Coordinating the search on {}; workers join with: meshcore-keygen join <HOST>:{}",
+        coordinator.local_addr()?,
+        coordinator.local_addr()?.port()
+    );
+    let total_found = coordinator.run(&keys_file)?;
+    print_search_summary(total_found, &keys_file);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secure::SecureString;
+    use crate::utils::{create_meshcore_private_key, extract_public_key_from_meshcore_key};
+
+    const TOKEN: &str = "correct horse battery staple";
+
+    fn found_key(seed: [u8; 32]) -> FoundKey {
+        let private_key = create_meshcore_private_key(&seed);
+        FoundKey {
+            private_key: SecureString::new(hex::encode_upper(private_key)),
+            public_key: hex::encode_upper(
+                extract_public_key_from_meshcore_key(&private_key).unwrap(),
+            ),
+            seed: Some(SecureString::new(hex::encode_upper(seed))),
+        }
+    }
+
+    fn search_config(pattern: &str, max_keys: usize) -> SearchConfig {
+        SearchConfig::new(pattern.to_string(), SearchBehavior::FindN(max_keys), 1)
+    }
+
+    #[test]
+    fn test_load_token() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), format!("  {}\n", TOKEN)).unwrap();
+        let token = load_token(Some(file.path().to_str().unwrap())).unwrap();
+        assert_eq!(token.as_str(), TOKEN);
+
+        std::fs::write(file.path(), "short").unwrap();
+        assert!(load_token(Some(file.path().to_str().unwrap())).is_err());
+    }
+
+    #[test]
+    fn test_proofs_bind_every_field() {
+        let fields = worker_proof_fields("challenge", "nonce", "key");
+        let proof = hex::encode(prove(TOKEN, "worker", &fields).finalize().into_bytes());
+        assert!(verify_proof(prove(TOKEN, "worker", &fields), &proof).is_ok());
+
+        let other_key = worker_proof_fields("challenge", "nonce", "other key");
+        assert!(verify_proof(prove(TOKEN, "worker", &other_key), &proof).is_err());
+        assert!(verify_proof(prove(TOKEN, "coordinator", &fields), &proof).is_err());
+        assert!(verify_proof(prove("another shared token", "worker", &fields), &proof).is_err());
+    }
+
+    #[test]
+    fn test_seal_and_open_found_key() {
+        let coordinator = SecretKey::generate(&mut OsRng);
+        let worker = SecretKey::generate(&mut OsRng);
+        let session = SalsaBox::new(&worker.public_key(), &coordinator);
+        let key = found_key([7u8; 32]);
+        let patterns = PatternSet::compile(&key.public_key[..2]).unwrap();
+        let filter = NodeHashFilter::default();
+
+        let Message::Found {
+            public_key,
+            nonce,
+            ciphertext,
+        } = seal_found_key(&key, &worker, &coordinator.public_key()).unwrap()
+        else {
+            panic!("expected a found message");
+        };
+        assert!(!ciphertext.contains(key.private_key.expose()));
+
+        let opened = open_found_key(
+            &session,
+            &public_key,
+            &nonce,
+            &ciphertext,
+            &patterns,
+            &filter,
+        )
+        .unwrap();
+        assert_eq!(opened.private_key.expose(), key.private_key.expose());
+        assert_eq!(opened.seed.unwrap().expose(), key.seed.unwrap().expose());
+
+        // Someone without the worker's session key can't slip in a key of their own
+        let impostor = SalsaBox::new(&SecretKey::generate(&mut OsRng).public_key(), &coordinator);
+        assert!(
+            open_found_key(
+                &impostor,
+                &public_key,
+                &nonce,
+                &ciphertext,
+                &patterns,
+                &filter
+            )
+            .is_err()
+        );
+
+        // Nor can a worker hand in a key the job didn't ask for
+        let other_hash = if key.public_key.starts_with("00") {
+            "11"
+        } else {
+            "00"
+        };
+        let wrong_job = PatternSet::compile(other_hash).unwrap();
+        assert!(
+            open_found_key(
+                &session,
+                &public_key,
+                &nonce,
+                &ciphertext,
+                &wrong_job,
+                &filter
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_distributed_search_over_loopback() {
+        let keys_file = tempfile::NamedTempFile::new().unwrap();
+        let keys_path = keys_file.path().to_str().unwrap().to_string();
+        let coordinator = Coordinator::bind(
+            "127.0.0.1:0".parse().unwrap(),
+            search_config("A", 3),
+            Zeroizing::new(TOKEN.to_string()),
+        )
+        .unwrap();
+        let addr = coordinator.local_addr().unwrap().to_string();
+        let coordinator_handle = std::thread::spawn(move || coordinator.run(&keys_path));
+
+        // A wrong token gets the connection dropped during the handshake
+        assert!(run_join(&addr, "not the shared token at all", "impostor", 1).is_err());
+
+        // A worker that authenticates and then vanishes must not stall the search
+        let (mut reader, mut writer) =
+            split_connection(TcpStream::connect(&addr).unwrap()).unwrap();
+        let box_secret = SecretKey::generate(&mut OsRng);
+        let job = handshake_coordinator(&mut reader, &mut writer, TOKEN, "dropout", 2, &box_secret)
+            .unwrap();
+        assert_eq!(job.config.prefix, "A");
+        writer.send(&Message::Progress { attempts: 10 }).unwrap();
+        drop((reader, writer));
+
+        let sent = run_join(&addr, TOKEN, "worker", 1).unwrap();
+        assert_eq!(coordinator_handle.join().unwrap().unwrap(), 3);
+        assert!(sent >= 3);
+
+        let keys = crate::keystore::load_keys(keys_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(keys.len(), 3);
+        assert!(keys.iter().all(|key| key.public_key.starts_with('A')));
+    }
+}
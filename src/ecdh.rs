@@ -0,0 +1,151 @@
+use crate::keystore::{KeySelector, decode_private_key, select_key};
+use anyhow::{Context, Result};
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use zeroize::Zeroizing;
+
+/// Converts an Ed25519 public key to its X25519 (Montgomery u-coordinate) form.
+/// MeshCore does this for every peer before deriving the shared secret for direct messages.
+pub fn ed25519_public_to_x25519(public_key: &[u8; 32]) -> Result<[u8; 32]> {
+    let point = CompressedEdwardsY(*public_key)
+        .decompress()
+        .context("Public key is not a valid Ed25519 point")?;
+    Ok(point.to_montgomery().to_bytes())
+}
+
+/// Extracts the X25519 private scalar from a MeshCore expanded key.
+/// The first half of the expanded key already is the clamped scalar, which is exactly what
+/// X25519 expects, so unlike seed-based conversions no extra hashing is needed.
+pub fn expanded_key_to_x25519(expanded_key: &[u8; 64]) -> Zeroizing<[u8; 32]> {
+    let mut scalar = Zeroizing::new([0u8; 32]);
+    scalar.copy_from_slice(&expanded_key[..32]);
+    scalar
+}
+
+/// Derives the ECDH shared secret the same way the firmware's `ed25519_key_exchange` does:
+/// our clamped scalar times the peer's key in Montgomery form.
+/// An all-zero result means the peer sent a low-order point, which must never be used as a key.
+pub fn derive_shared_secret(
+    expanded_key: &[u8; 64],
+    peer_public_key: &[u8; 32],
+) -> Result<Zeroizing<[u8; 32]>> {
+    let peer_montgomery = MontgomeryPoint(ed25519_public_to_x25519(peer_public_key)?);
+    let scalar = expanded_key_to_x25519(expanded_key);
+
+    let shared = Zeroizing::new(peer_montgomery.mul_clamped(*scalar).to_bytes());
+    if shared.iter().all(|&b| b == 0) {
+        anyhow::bail!("Peer public key is a low-order point, refusing to derive a shared secret");
+    }
+
+    Ok(shared)
+}
+
+/// Prints the shared secret between a stored key and a peer's Ed25519 public key.
+pub fn run_shared_secret(
+    keys_file: &str,
+    key_selector: Option<&KeySelector>,
+    peer_public_key: &[u8; 32],
+) -> Result<()> {
+    let key = select_key(keys_file, key_selector)?;
+    let private_key = decode_private_key(&key)?;
+
+    let shared = derive_shared_secret(&private_key, peer_public_key)?;
+    println!("{}", hex::encode(&shared[..]).to_uppercase());
+    Ok(())
+}
+
+/// Prints the X25519 form of a public key, and of the private key when explicitly requested.
+pub fn run_to_x25519(public_key: &[u8; 32], private_key: Option<&[u8; 64]>) -> Result<()> {
+    let x25519_public = ed25519_public_to_x25519(public_key)?;
+    println!(
+        "🔁🔑 X25519 public key: {}",
+        hex::encode(x25519_public).to_uppercase()
+    );
+
+    if let Some(private_key) = private_key {
+        let x25519_private = expanded_key_to_x25519(private_key);
+        println!(
+            "🔁🔒 X25519 private key: {}",
+            hex::encode(&x25519_private[..]).to_uppercase()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{create_meshcore_private_key, extract_public_key_from_meshcore_key};
+
+    #[test]
+    fn test_shared_secret_is_symmetric() {
+        let alice = create_meshcore_private_key(&[1u8; 32]);
+        let bob = create_meshcore_private_key(&[2u8; 32]);
+        let alice_public = extract_public_key_from_meshcore_key(&alice).unwrap();
+        let bob_public = extract_public_key_from_meshcore_key(&bob).unwrap();
+
+        let alice_shared = derive_shared_secret(&alice, &bob_public).unwrap();
+        let bob_shared = derive_shared_secret(&bob, &alice_public).unwrap();
+        assert_eq!(*alice_shared, *bob_shared);
+
+        let carol = create_meshcore_private_key(&[3u8; 32]);
+        let carol_public = extract_public_key_from_meshcore_key(&carol).unwrap();
+        assert_ne!(
+            *derive_shared_secret(&alice, &carol_public).unwrap(),
+            *alice_shared
+        );
+    }
+
+    #[test]
+    fn test_converted_keys_form_an_x25519_pair() {
+        let expanded_key = create_meshcore_private_key(&[9u8; 32]);
+        let public_key = extract_public_key_from_meshcore_key(&expanded_key).unwrap();
+
+        let x25519_private = expanded_key_to_x25519(&expanded_key);
+        let x25519_public = ed25519_public_to_x25519(&public_key).unwrap();
+
+        assert_eq!(
+            MontgomeryPoint::mul_base_clamped(*x25519_private).to_bytes(),
+            x25519_public
+        );
+    }
+
+    #[test]
+    fn test_libsodium_conversion_vector() {
+        // From libsodium's ed25519_convert test
+        let mut seed = [0u8; 32];
+        hex::decode_to_slice(
+            "421151a459faeade3d247115f94aedae42318124095afabe4d1451a559faedee",
+            &mut seed,
+        )
+        .unwrap();
+
+        let expanded_key = create_meshcore_private_key(&seed);
+        let public_key = extract_public_key_from_meshcore_key(&expanded_key).unwrap();
+
+        assert_eq!(
+            hex::encode(ed25519_public_to_x25519(&public_key).unwrap()),
+            "f1814f0e8ff1043d8a44d25babff3cedcae6c22c3edaa48f857ae70de2baae50"
+        );
+        assert_eq!(
+            hex::encode(&expanded_key_to_x25519(&expanded_key)[..]),
+            "8052030376d47112be7f73ed7a019293dd12ad910b654455798b4667d73de166"
+        );
+    }
+
+    #[test]
+    fn test_shared_secret_rejects_low_order_and_invalid_points() {
+        let expanded_key = create_meshcore_private_key(&[4u8; 32]);
+
+        // The Edwards identity (0, 1) is a low-order point
+        let mut identity = [0u8; 32];
+        identity[0] = 1;
+        assert!(derive_shared_secret(&expanded_key, &identity).is_err());
+
+        // y = 2 does not decompress to a curve point
+        let mut not_on_curve = [0u8; 32];
+        not_on_curve[0] = 2;
+        assert!(ed25519_public_to_x25519(&not_on_curve).is_err());
+    }
+}
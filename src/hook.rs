@@ -0,0 +1,270 @@
+use crate::distributed::TOKEN_ENV;
+use crate::output::say_warning;
+use crate::secure::SecureString;
+use crate::types::FoundKey;
+use crate::utils::format_duration;
+use anyhow::{Context, Result};
+use crossbeam::channel::{self, Sender};
+use std::io::{ErrorKind, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How often a running hook is checked for having exited.
+const HOOK_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A command run for every found key, e.g. to update an inventory or ping a chat bot.
+/// The public parts are passed in the environment; the private key only ever goes through
+/// a pipe on stdin, since argv and the environment are visible to other local users (`ps`,
+/// `/proc/<pid>/environ` for the same user) and tend to end up in logs.
+#[derive(Debug, Clone)]
+pub struct FoundHook {
+    pub command: String,
+    pub timeout: Duration,
+}
+
+impl FoundHook {
+    /// Runs the hook through the shell, so the command can carry its own arguments.
+    /// Standard output is discarded because `--quiet` reserves ours for public keys;
+    /// standard error is passed through when `show_errors` is set.
+    pub fn run_for(
+        &self,
+        key: &FoundKey,
+        pattern: &str,
+        index: usize,
+        show_errors: bool,
+    ) -> Result<()> {
+        let mut child = shell_command(&self.command)
+            .env("MESHCORE_PUBLIC_KEY", &key.public_key)
+            .env("MESHCORE_NODE_HASH", &key.public_key[..2])
+            .env("MESHCORE_PATTERN", pattern)
+            .env("MESHCORE_KEY_INDEX", index.to_string())
+            // The serve/join token has no business in a hook either
+            .env_remove(TOKEN_ENV)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(if show_errors {
+                Stdio::inherit()
+            } else {
+                Stdio::null()
+            })
+            .spawn()
+            .with_context(|| format!("Couldn't start '{}'", self.command))?;
+
+        // One short line fits in any pipe buffer, so this can't block on a hook that
+        // never reads; a hook that exits without reading is fine too
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        match writeln!(stdin, "{}", key.private_key.expose()) {
+            Err(e) if e.kind() != ErrorKind::BrokenPipe => return Err(e.into()),
+            _ => {}
+        }
+        drop(stdin);
+
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if started.elapsed() >= self.timeout {
+                kill_hook(&mut child);
+                let _ = child.wait();
+                anyhow::bail!(
+                    "killed after {}",
+                    format_duration(self.timeout.as_secs_f64())
+                );
+            }
+            std::thread::sleep(HOOK_POLL_INTERVAL);
+        };
+        check_status(status)
+    }
+}
+
+/// Runs the hook for each found key, in order, on a thread of its own, so a slow hook holds
+/// up neither the search loop nor the dashboard.
+pub struct HookRunner {
+    sender: Sender<(FoundKey, usize)>,
+    handle: JoinHandle<Vec<String>>,
+}
+
+impl HookRunner {
+    /// With `show_errors` failures and the hook's stderr go straight to the terminal;
+    /// without (the dashboard owns the screen) failures are held back until `finish`.
+    pub fn start(hook: FoundHook, pattern: String, show_errors: bool) -> Self {
+        let (sender, receiver) = channel::unbounded::<(FoundKey, usize)>();
+        let handle = std::thread::spawn(move || {
+            let mut held_back = Vec::new();
+            for (key, index) in receiver {
+                if let Err(e) = hook.run_for(&key, &pattern, index, show_errors) {
+                    let message =
+                        format!("🪝 The --on-found hook failed for key #{}: {:#}", index, e);
+                    if show_errors {
+                        say_warning!("\n{} (search continues)", message);
+                    } else {
+                        held_back.push(message);
+                    }
+                }
+            }
+            held_back
+        });
+        Self { sender, handle }
+    }
+
+    /// Queues the hook for a key that is already in the key store. The hook only needs the
+    /// key pair, so the seed stays behind; the copy is zeroed once the hook has run.
+    pub fn submit(&self, key: &FoundKey, index: usize) {
+        let key = FoundKey {
+            private_key: SecureString::new(key.private_key.expose().to_string()),
+            public_key: key.public_key.clone(),
+            seed: None,
+        };
+        // The thread only stops once the sender is dropped in finish
+        let _ = self.sender.send((key, index));
+    }
+
+    /// Waits for the queued hooks and reports the failures that were held back.
+    pub fn finish(self) {
+        drop(self.sender);
+        for message in self.handle.join().unwrap_or_default() {
+            say_warning!("{}", message);
+        }
+    }
+}
+
+/// The hook gets its own process group, so a timeout takes down everything it started and
+/// not only the shell; a leftover child may still hold the private key from stdin.
+#[cfg(unix)]
+fn shell_command(command: &str) -> Command {
+    use std::os::unix::process::CommandExt;
+
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command).process_group(0);
+    shell
+}
+
+#[cfg(unix)]
+fn kill_hook(child: &mut Child) {
+    // The group id is the shell's pid; a negative pid signals the whole group
+    // SAFETY: kill() only sends a signal, no memory is involved
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(not(unix))]
+fn kill_hook(child: &mut Child) {
+    let _ = child.kill();
+}
+
+fn check_status(status: ExitStatus) -> Result<()> {
+    if status.success() {
+        Ok(())
+    } else {
+        anyhow::bail!("{}", status)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::secure::SecureString;
+
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    fn test_key() -> FoundKey {
+        FoundKey {
+            private_key: SecureString::new("AB".repeat(64)),
+            public_key: format!("C0FFEE{}", "0".repeat(58)),
+            seed: None,
+        }
+    }
+
+    fn hook(command: String, timeout: Duration) -> FoundHook {
+        FoundHook { command, timeout }
+    }
+
+    #[test]
+    fn test_hook_gets_public_env_and_private_stdin() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("hook.txt");
+        let command = format!("{{ env; echo stdin=$(cat); }} > '{}'", out.display());
+        hook(command, TIMEOUT)
+            .run_for(&test_key(), "C0FFEE", 3, false)
+            .unwrap();
+
+        let seen = std::fs::read_to_string(&out).unwrap();
+        assert!(seen.contains(&format!("MESHCORE_PUBLIC_KEY={}", test_key().public_key)));
+        assert!(seen.contains("MESHCORE_NODE_HASH=C0"));
+        assert!(seen.contains("MESHCORE_PATTERN=C0FFEE"));
+        assert!(seen.contains("MESHCORE_KEY_INDEX=3"));
+        let private_key = "AB".repeat(64);
+        assert!(seen.contains(&format!("stdin={}", private_key)));
+        // Only stdin carries it
+        assert_eq!(seen.matches(&private_key).count(), 1);
+    }
+
+    #[test]
+    fn test_hook_failures_and_timeout() {
+        let failing = hook("exit 3".to_string(), TIMEOUT);
+        let error = failing.run_for(&test_key(), "C0", 1, false).unwrap_err();
+        assert!(error.to_string().contains('3'));
+
+        let slow = hook("sleep 10".to_string(), Duration::from_millis(200));
+        let started = Instant::now();
+        let error = slow.run_for(&test_key(), "C0", 1, false).unwrap_err();
+        assert!(error.to_string().contains("killed"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // A hook that ignores stdin still succeeds
+        hook("true".to_string(), TIMEOUT)
+            .run_for(&test_key(), "C0", 1, false)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_runner_queues_hooks_off_the_caller() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("hooks.txt");
+        let command = format!("sleep 0.3; echo $MESHCORE_KEY_INDEX >> '{}'", out.display());
+        let runner = HookRunner::start(hook(command, TIMEOUT), "C0".to_string(), false);
+
+        let started = Instant::now();
+        runner.submit(&test_key(), 1);
+        runner.submit(&test_key(), 2);
+        assert!(started.elapsed() < Duration::from_millis(300));
+
+        // finish waits for every queued hook, run one after the other
+        runner.finish();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "1\n2\n");
+    }
+
+    #[test]
+    fn test_timeout_kills_the_whole_hook() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("pid");
+        // The shell forks sleep, so killing only the shell would leave sleep running
+        let command = format!("echo $$ > '{}'; sleep 10; true", pid_file.display());
+        let error = hook(command, Duration::from_millis(300))
+            .run_for(&test_key(), "C0", 1, false)
+            .unwrap_err();
+        assert!(error.to_string().contains("killed"));
+
+        let group: libc::pid_t = std::fs::read_to_string(&pid_file)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        // SAFETY: signal 0 only checks whether the group still has members
+        while unsafe { libc::kill(-group, 0) } == 0 {
+            assert!(Instant::now() < deadline, "the hook's children survived");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
@@ -1,25 +1,24 @@
 use crate::cpu::CpuKeySearcher;
-use crate::performance::{PerformanceCache, estimate_search_time};
-use crate::types::{FoundKey, SearchBehavior, SearchConfig, SearchStats};
+use crate::hook::HookRunner;
+use crate::keystore::append_key;
+use crate::metrics::MetricsServer;
+use crate::output::{Event, output, say, say_inline, say_warning};
+use crate::pattern::PatternSet;
+use crate::performance::{PerformanceCache, REPORTED_PERCENTILES, SearchEstimate};
+use crate::platform::PlatformInfo;
+use crate::tui::run_dashboard;
+use crate::types::{FoundKey, SearchBehavior, SearchConfig, SearchStats, WorkerSnapshot};
 use crate::utils::{format_duration, format_large_number};
 use anyhow::Result;
 use crossbeam::channel;
-use std::fs::OpenOptions;
-use std::io::Write;
 use std::sync::Arc;
 use std::sync::atomic::Ordering;
 use std::time::{Duration, Instant};
 
 /// Persists found keys to disk for user access.
 /// Uses append mode to avoid losing previously found keys if the search continues.
-pub fn log_found_key(key: &FoundKey, _stats: &SearchStats) -> Result<()> {
-    let mut file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open("meshcore-keys.txt")?;
-
-    writeln!(file, "{}; {}", key.private_key.expose(), key.public_key)?;
-    Ok(())
+pub fn log_found_key(key: &FoundKey, keys_file: &str) -> Result<()> {
+    append_key(keys_file, key)
 }
 
 /// Displays performance metrics and search time estimates to help users understand expected runtime.
@@ -27,63 +26,155 @@ pub fn log_found_key(key: &FoundKey, _stats: &SearchStats) -> Result<()> {
 pub fn print_performance_info(config: &SearchConfig) -> Result<()> {
     // Use cached data to avoid re-benchmarking on every run
     let perf_result = if let Some(cached) = PerformanceCache::load() {
-        println!("\n📈✨ Using cached performance data:");
-        println!(
+        output().emit(&Event::Benchmark {
+            keys_per_sec_per_core: cached.keys_per_sec_per_core,
+            cores: cached.cores_used,
+            cached: true,
+        });
+        say!("\n📈✨ Using cached performance data:");
+        say!(
             "   ⚡️ Speed per core: {:.0} keys/sec",
             cached.keys_per_sec_per_core
         );
-        println!("   🌟 Platform: {}!", cached.platform);
         cached
     } else {
         PerformanceCache::measure_performance(config.cpu_threads)?
     };
 
-    let total_speed = perf_result.keys_per_sec_per_core * config.cpu_threads as f64;
-    let prefix_len = config.prefix.len();
-
-    println!("\n📊🔥 Search Statistics:");
-    println!("   🎯 Prefix length: {} hex characters", prefix_len);
-    println!("   🚀 Expected speed: {:.0} keys/sec!", total_speed);
+    say!("   🖥️ CPU: {}", PlatformInfo::detect().banner());
 
-    let prefix_time = estimate_search_time(prefix_len, total_speed);
-
-    // Calculate search probability ranges for better user expectations
-    let probability_50_percent = prefix_time * 0.693; // ln(2) ≈ 0.693
-    let probability_90_percent = prefix_time * 2.303; // ln(10) ≈ 2.303
+    let total_speed = perf_result.keys_per_sec_per_core * config.cpu_threads as f64;
+    let patterns = PatternSet::compile(&config.prefix)?;
+    let estimate = SearchEstimate::for_patterns(&patterns, &config.excluded_hashes);
+
+    say!("\n📊🔥 Search Statistics:");
+    say!("   🎯 Pattern: {}", config.prefix);
+    if !config.excluded_hashes.is_empty() {
+        say!(
+            "   🚫 Avoiding {} node hashes already used in the mesh",
+            config.excluded_hashes.excluded_count()
+        );
+    }
+    say!(
+        "   🎲 Match chance per key: 1 in {}",
+        format_large_number(estimate.expected_attempts.round() as u64)
+    );
+    say!("   🚀 Expected speed: {:.0} keys/sec!", total_speed);
 
-    println!(
+    say!(
         "   ⏰ Estimated time (AVERAGE): {}!",
-        format_duration(prefix_time)
-    );
-    println!("   📈 Search time ranges:");
-    println!(
-        "      • 50% chance: Found within {}",
-        format_duration(probability_50_percent)
+        format_duration(estimate.expected_time(total_speed))
     );
-    println!(
-        "      • 90% chance: Found within {}",
-        format_duration(probability_90_percent)
-    );
-    println!(
+    say!("   📈 Search time ranges:");
+    for confidence in REPORTED_PERCENTILES {
+        say!(
+            "      • {:.0}% chance: Found within {}",
+            confidence * 100.0,
+            format_duration(estimate.time_for_confidence(confidence, total_speed))
+        );
+    }
+    say!(
         "   ⚠️  Note: This is probabilistic - you might get lucky (seconds) or unlucky (much longer)!"
     );
 
     Ok(())
 }
 
+/// Past this percentile of the expected attempts, the progress line says so.
+const UNLUCKY_CONFIDENCE: f64 = 0.90;
+
+/// Describes how the search is faring against the odds, for the progress line.
+/// Attempts are independent, so the wait for the next match starts over after every hit:
+/// the cumulative probability only counts attempts since the last match, and the expected
+/// remaining time is always the full average. Past P90 the search is unlucky, not broken.
+fn format_search_odds(
+    estimate: &SearchEstimate,
+    attempts_since_match: u64,
+    keys_per_sec: f64,
+) -> String {
+    let attempts = attempts_since_match as f64;
+    let mut odds = format!(
+        "🎲 {:.0}% by now | ⏳ Next in ~{}",
+        estimate.probability_within(attempts) * 100.0,
+        format_duration(estimate.expected_time(keys_per_sec))
+    );
+    if attempts > estimate.attempts_for_confidence(UNLUCKY_CONFIDENCE) {
+        odds.push_str(" | 🍀 Unlucky streak (past P90), keep going");
+    }
+    odds
+}
+
 /// Main key search orchestration function.
 /// Sets up worker threads, manages communication between them, and handles user output.
 pub fn run_key_search(config: SearchConfig) -> Result<()> {
     print_performance_info(&config)?;
 
-    let stats = Arc::new(SearchStats::new());
+    let keys_file = config.keys_file.clone();
+    let total_found = run_search_workers(config)?;
+    print_search_summary(total_found, &keys_file);
+
+    Ok(())
+}
+
+/// Pattern-free batch generation for provisioning.
+/// Skips the benchmark and estimates since every valid key matches, but shares the worker pool
+/// so hash filtering and the per-batch uniqueness check behave exactly like a search.
+pub fn run_generate(config: SearchConfig) -> Result<()> {
+    if let SearchBehavior::FindN(count) = config.search_behavior {
+        say!("\n🏭🔑 Generating {} MeshCore key(s)...", count);
+    }
+    if !config.excluded_hashes.is_empty() {
+        say!(
+            "   🚫 Avoiding {} node hashes",
+            config.excluded_hashes.excluded_count()
+        );
+    }
+    if config.unique_hashes {
+        say!("   🧬 Every key in the batch gets its own node hash");
+    }
+
+    let keys_file = config.keys_file.clone();
+    let total_found = run_search_workers(config)?;
+    print_search_summary(total_found, &keys_file);
+
+    Ok(())
+}
+
+/// Runs the worker pool and progress monitor until the stopping condition is met,
+/// logging each key as it arrives. Returns how many keys were found.
+fn run_search_workers(config: SearchConfig) -> Result<usize> {
+    let stats = Arc::new(SearchStats::with_workers(config.cpu_threads));
+    let estimate = SearchEstimate::for_patterns(
+        &PatternSet::compile(&config.prefix)?,
+        &config.excluded_hashes,
+    );
+    // Kept alive until the search returns; dropping it stops the server
+    let _metrics_server = match config.metrics_listen {
+        Some(addr) => {
+            let server = MetricsServer::start(addr, Arc::clone(&stats), estimate)?;
+            say!("📡 Metrics: http://{}/metrics", server.local_addr());
+            Some(server)
+        }
+        None => None,
+    };
+    let search_started = Instant::now();
+    output().emit(&Event::Started {
+        pattern: config.prefix.clone(),
+        threads: config.cpu_threads,
+        max_keys: match config.search_behavior {
+            SearchBehavior::FindN(n) => Some(n),
+            SearchBehavior::Continuous => None,
+        },
+        probability: estimate.probability,
+        expected_attempts: estimate.expected_attempts,
+    });
     let config = Arc::new(config);
 
     let (found_sender, found_receiver) = channel::unbounded();
 
     // Use CPU workers for key search
     let total_cpu_threads = config.cpu_threads;
-    println!(
+    say!(
         "💻🔥 Using {} workers for maximum performance! ",
         total_cpu_threads
     );
@@ -102,12 +193,27 @@ pub fn run_key_search(config: SearchConfig) -> Result<()> {
     // Close the channel when all workers finish
     drop(found_sender);
 
+    if config.tui {
+        let total_found = run_dashboard_search(
+            &config,
+            &stats,
+            &estimate,
+            &found_receiver,
+            worker_handles,
+            search_started,
+        )?;
+        finish_search(&stats, total_found, search_started);
+        return Ok(total_found);
+    }
+
     // Monitor search progress and enforce stopping conditions
     let stats_clone = Arc::clone(&stats);
     let config_clone = Arc::clone(&config);
 
     let monitor_handle = std::thread::spawn(move || {
         let mut last_attempts = 0u64;
+        let mut last_worker_attempts = vec![0u64; config_clone.cpu_threads];
+        let mut worker_health = vec![WorkerHealth::Healthy; config_clone.cpu_threads];
         let mut last_time = Instant::now();
         let search_start_time = Instant::now();
 
@@ -123,25 +229,64 @@ pub fn run_key_search(config: SearchConfig) -> Result<()> {
 
             // Calculate search progress and time estimates
             let total_search_time = search_start_time.elapsed().as_secs();
+            let attempts_since_match = stats_clone.attempts_since_match();
+            let odds = format_search_odds(&estimate, attempts_since_match, keys_per_sec);
+            output().emit(&Event::Progress {
+                attempts: current_attempts,
+                matches: prefix_found,
+                keys_per_sec,
+                elapsed_secs: search_start_time.elapsed().as_secs_f64(),
+                probability_so_far: estimate.probability_within(attempts_since_match as f64),
+            });
 
             // Show progress with percentage for long searches (> 30 seconds)
             if total_search_time > 30 {
-                print!(
-                    "\r\x1B[K🚀 Attempts: {} | ✨ Matches: {} | ⚡️ Keys/sec: {:.0} | 🕐 Running: {}",
+                say_inline!(
+                    "\r\x1B[K🚀 Attempts: {} | ✨ Matches: {} | ⚡️ Keys/sec: {:.0} | 🕐 Running: {} | {}",
                     format_large_number(current_attempts),
                     prefix_found,
                     keys_per_sec,
-                    format_duration(total_search_time as f64)
+                    format_duration(total_search_time as f64),
+                    odds
                 );
             } else {
-                print!(
-                    "\r\x1B[K🚀 Total Attempts: {} | ✨ Matches: {} | ⚡️ Keys/sec: {:.0}",
+                say_inline!(
+                    "\r\x1B[K🚀 Total Attempts: {} | ✨ Matches: {} | ⚡️ Keys/sec: {:.0} | {}",
                     format_large_number(current_attempts),
                     prefix_found,
-                    keys_per_sec
+                    keys_per_sec,
+                    odds
                 );
             }
-            std::io::stdout().flush().unwrap();
+
+            // Per-worker rates over the same interval, to spot stragglers and hung threads
+            let snapshots = stats_clone.worker_snapshots();
+            let worker_rates: Vec<f64> = snapshots
+                .iter()
+                .zip(&last_worker_attempts)
+                .map(|(snapshot, last)| (snapshot.attempts - last) as f64 / elapsed)
+                .collect();
+            let health = assess_workers(&worker_rates, &snapshots);
+            for (thread_id, (now, before)) in health.iter().zip(&worker_health).enumerate() {
+                if now == before {
+                    continue;
+                }
+                match now {
+                    WorkerHealth::Lagging => say_warning!(
+                        "\n🐌 Worker #{} is lagging: {:.0} keys/sec, well below the others",
+                        thread_id,
+                        worker_rates[thread_id]
+                    ),
+                    WorkerHealth::Stalled => say_warning!(
+                        "\n🧊 Worker #{} hasn't reported for {} - it may be stuck",
+                        thread_id,
+                        format_duration(snapshots[thread_id].since_heartbeat.as_secs_f64())
+                    ),
+                    WorkerHealth::Healthy => {}
+                }
+            }
+            worker_health = health;
+            last_worker_attempts = snapshots.iter().map(|s| s.attempts).collect();
 
             last_attempts = current_attempts;
             last_time = now;
@@ -160,16 +305,42 @@ pub fn run_key_search(config: SearchConfig) -> Result<()> {
     });
 
     // Process and display found keys as they arrive
+    let hooks = config
+        .on_found
+        .clone()
+        .map(|hook| HookRunner::start(hook, config.prefix.clone(), true));
     let mut total_found = 0usize;
     while let Ok(found_key) = found_receiver.recv() {
-        println!(
+        emit_found(&found_key, total_found + 1, search_started);
+        if output().is_quiet() {
+            // The public key is the one thing scripts need from a quiet run
+            println!("{}", found_key.public_key);
+        }
+        say!(
             "\n🎉✨ BOOM! Found key #{} 💎🔥\n   Public Key: {}",
             total_found + 1,
             found_key.public_key
         );
+        if let Some(contact_share) = &config.contact_share
+            && let Err(e) = contact_share.print_for(&found_key.public_key)
+        {
+            say_warning!("😤 Couldn't render the contact QR code: {}", e);
+        }
+
+        if let Some(cli_script) = &config.cli_script {
+            match cli_script.write_for(&found_key, total_found + 1) {
+                Ok(path) => say!("   📜 CLI script: {}", path.display()),
+                Err(e) => say_warning!("😤 Couldn't write the CLI script: {}", e),
+            }
+        }
+
+        if let Err(e) = log_found_key(&found_key, &config.keys_file) {
+            say_warning!("😤 Ugh, error logging key (but we found it anyway!): {}", e);
+        }
 
-        if let Err(e) = log_found_key(&found_key, &stats) {
-            eprintln!("😤 Ugh, error logging key (but we found it anyway!): {}", e);
+        // Only once the key is stored: a slow hook plus a Ctrl-C must not lose it
+        if let Some(hooks) = &hooks {
+            hooks.submit(&found_key, total_found + 1);
         }
 
         total_found += 1;
@@ -192,40 +363,246 @@ pub fn run_key_search(config: SearchConfig) -> Result<()> {
     }
 
     monitor_handle.join().unwrap();
+    if let Some(hooks) = hooks {
+        hooks.finish();
+    }
+    finish_search(&stats, total_found, search_started);
+
+    Ok(total_found)
+}
+
+pub fn emit_found(found_key: &FoundKey, index: usize, search_started: Instant) {
+    output().emit(&Event::Found {
+        index,
+        public_key: found_key.public_key.clone(),
+        node_hash: found_key.public_key[..2].to_string(),
+        elapsed_secs: search_started.elapsed().as_secs_f64(),
+    });
+}
+
+fn finish_search(stats: &SearchStats, found: usize, search_started: Instant) {
+    let elapsed = search_started.elapsed();
+    output().emit(&Event::Finished {
+        found,
+        attempts: stats.total_attempts.load(Ordering::Relaxed),
+        elapsed_secs: elapsed.as_secs_f64(),
+    });
+    print_worker_summary(&stats.worker_snapshots(), elapsed);
+}
+
+/// A worker slower than this fraction of the median rate is reported as lagging.
+const LAGGING_FRACTION: f64 = 0.5;
+
+/// A worker silent for this long is reported as stalled. Workers report every few thousand
+/// attempts, which takes well under a second on anything this runs on.
+const STALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WorkerHealth {
+    Healthy,
+    Lagging,
+    Stalled,
+}
+
+/// Compares each worker with the median rather than the mean, so one hung worker doesn't
+/// drag the reference down and hide a second slow one.
+fn assess_workers(rates: &[f64], snapshots: &[WorkerSnapshot]) -> Vec<WorkerHealth> {
+    let mut sorted = rates.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let median = sorted.get(sorted.len() / 2).copied().unwrap_or(0.0);
+
+    rates
+        .iter()
+        .zip(snapshots)
+        .map(|(&rate, snapshot)| {
+            if snapshot.since_heartbeat >= STALL_TIMEOUT {
+                WorkerHealth::Stalled
+            } else if median > 0.0 && rate < median * LAGGING_FRACTION {
+                WorkerHealth::Lagging
+            } else {
+                WorkerHealth::Healthy
+            }
+        })
+        .collect()
+}
+
+/// Per-thread breakdown for the end of a run, with the spread between the slowest and
+/// fastest worker as a quick load-balance check.
+fn print_worker_summary(snapshots: &[WorkerSnapshot], elapsed: Duration) {
+    if snapshots.is_empty() {
+        return;
+    }
+    let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+
+    say!("\n\n🧵 Per-thread breakdown:");
+    for (thread_id, snapshot) in snapshots.iter().enumerate() {
+        say!(
+            "   #{}: {} attempts, {:.0} keys/sec, {} match(es)",
+            thread_id,
+            format_large_number(snapshot.attempts),
+            snapshot.attempts as f64 / seconds,
+            snapshot.matches
+        );
+    }
+
+    let slowest = snapshots.iter().map(|s| s.attempts).min().unwrap_or(0);
+    let fastest = snapshots.iter().map(|s| s.attempts).max().unwrap_or(0);
+    if snapshots.len() > 1 && fastest > 0 {
+        say!(
+            "   ⚖️ Slowest thread did {:.0}% of the work of the fastest",
+            slowest as f64 / fastest as f64 * 100.0
+        );
+    }
+}
 
+/// `--tui` variant of the found-key loop: the dashboard owns the terminal while the search
+/// runs, so anything that prints (contact QR codes) waits until it has been restored.
+fn run_dashboard_search(
+    config: &SearchConfig,
+    stats: &SearchStats,
+    estimate: &SearchEstimate,
+    found_receiver: &channel::Receiver<FoundKey>,
+    worker_handles: Vec<std::thread::JoinHandle<()>>,
+    search_started: Instant,
+) -> Result<usize> {
+    let mut found_public_keys = Vec::new();
+    // Hook stderr would draw over the dashboard, so failures are reported afterwards
+    let hooks = config
+        .on_found
+        .clone()
+        .map(|hook| HookRunner::start(hook, config.prefix.clone(), false));
+    let total_found = run_dashboard(
+        config,
+        stats,
+        estimate,
+        found_receiver,
+        |found_key, index| {
+            // Events go to a descriptor or file with --tui, so they can be sent right away
+            emit_found(found_key, index, search_started);
+            let mut notes = Vec::new();
+            if let Some(cli_script) = &config.cli_script {
+                match cli_script.write_for(found_key, index) {
+                    Ok(path) => notes.push(format!("CLI script {}", path.display())),
+                    Err(e) => notes.push(format!("CLI script failed: {}", e)),
+                }
+            }
+            if let Err(e) = log_found_key(found_key, &config.keys_file) {
+                notes.push(format!("NOT SAVED: {}", e));
+            }
+            // Stored first so a slow hook plus a Ctrl-C can't lose the key
+            if let Some(hooks) = &hooks {
+                hooks.submit(found_key, index);
+            }
+            found_public_keys.push(found_key.public_key.clone());
+            (!notes.is_empty()).then(|| notes.join(", "))
+        },
+    );
+
+    for handle in worker_handles {
+        handle.join().unwrap();
+    }
+    if let Some(hooks) = hooks {
+        hooks.finish();
+    }
+    let total_found = total_found?;
+
+    for (index, public_key) in found_public_keys.iter().enumerate() {
+        if output().is_quiet() {
+            println!("{}", public_key);
+        }
+        say!(
+            "\n🎉✨ Found key #{} 💎🔥\n   Public Key: {}",
+            index + 1,
+            public_key
+        );
+        if let Some(contact_share) = &config.contact_share
+            && let Err(e) = contact_share.print_for(public_key)
+        {
+            say_warning!("😤 Couldn't render the contact QR code: {}", e);
+        }
+    }
+
+    Ok(total_found)
+}
+
+/// Tells the user where their keys went, or that nothing matched.
+pub fn print_search_summary(total_found: usize, keys_file: &str) {
     if total_found > 0 {
-        println!(
+        say!(
             "\n\n🎉🌟 SUCCESS! Found {} matching key(s) because we're THAT good! ✨",
             total_found
         );
-        println!("📝💎 Keys have been saved to: meshcore-keys.txt");
-        println!("🔒🗑️ Remember to securely delete the file when done: ./meshcore-keygen --delete");
+        say!("📝💎 Keys have been saved to: {}", keys_file);
+        say!("🔒🗑️ Remember to securely delete the file when done: ./meshcore-keygen wipe");
     } else {
-        println!("\n\n❌💔 No matching keys found");
+        say!("\n\n❌💔 No matching keys found");
     }
-
-    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::keystore::KEYS_FILE;
     use crate::secure::SecureString;
-    use crate::types::{FoundKey, SearchStats};
+    use crate::types::FoundKey;
 
     #[test]
     fn test_log_found_key() {
         let found_key = FoundKey {
             private_key: SecureString::new("test_private_key".to_string()),
             public_key: "test_public_key".to_string(),
+            seed: None,
         };
-        let stats = SearchStats::new();
 
         // This test will create a file, so we should clean up
-        let result = log_found_key(&found_key, &stats);
+        let result = log_found_key(&found_key, KEYS_FILE);
         assert!(result.is_ok());
 
         // Clean up the test file
-        std::fs::remove_file("meshcore-keys.txt").ok();
+        std::fs::remove_file(KEYS_FILE).ok();
+    }
+
+    #[test]
+    fn test_format_search_odds() {
+        let estimate = SearchEstimate::from_probability(1.0 / 1000.0);
+
+        let fresh = format_search_odds(&estimate, 0, 100.0);
+        assert!(fresh.starts_with("🎲 0% by now"));
+        assert!(fresh.contains("Next in ~10"));
+        assert!(!fresh.contains("Unlucky"));
+
+        // ln(2) * 1000 attempts is the median
+        assert!(format_search_odds(&estimate, 693, 100.0).contains("🎲 50% by now"));
+
+        // P90 is about 2302 attempts
+        let unlucky = format_search_odds(&estimate, 2400, 100.0);
+        assert!(unlucky.contains("🎲 91% by now"));
+        assert!(unlucky.contains("Unlucky"));
+    }
+
+    #[test]
+    fn test_assess_workers() {
+        let snapshot = |since_heartbeat| WorkerSnapshot {
+            attempts: 0,
+            matches: 0,
+            since_heartbeat,
+        };
+        let fresh = snapshot(Duration::from_millis(200));
+        let silent = snapshot(Duration::from_secs(30));
+
+        assert_eq!(
+            assess_workers(&[1000.0, 950.0, 400.0, 0.0], &[fresh, fresh, fresh, silent]),
+            vec![
+                WorkerHealth::Healthy,
+                WorkerHealth::Healthy,
+                WorkerHealth::Lagging,
+                WorkerHealth::Stalled
+            ]
+        );
+        // Nothing is lagging before any worker has produced a rate
+        assert_eq!(
+            assess_workers(&[0.0, 0.0], &[fresh, fresh]),
+            vec![WorkerHealth::Healthy; 2]
+        );
     }
 }
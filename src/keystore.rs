@@ -0,0 +1,439 @@
+use crate::output::{say, say_warning};
+use crate::pattern::PatternSet;
+use crate::secure::SecureString;
+use crate::types::FoundKey;
+use crate::utils::{create_meshcore_private_key, extract_public_key_from_meshcore_key};
+use anyhow::{Context, Result};
+use std::fs::OpenOptions;
+use std::io::{BufRead, Write};
+use zeroize::Zeroizing;
+
+/// Default key store that the search appends to and every key-consuming command reads from.
+pub const KEYS_FILE: &str = "meshcore-keys.txt";
+
+/// Parses a single key store line in the `PRIVATE; PUBLIC[; SEED]` format written by the search.
+/// The public half is optional so a bare expanded private key (e.g. piped on stdin) is accepted,
+/// but when present it must match the key derived from the scalar to catch corrupted entries.
+pub fn parse_key_line(line: &str) -> Result<FoundKey> {
+    let mut parts = line.trim().splitn(3, ';');
+    let private_hex = parts.next().unwrap_or_default().trim();
+    let stated_public = parts.next().map(str::trim).filter(|p| !p.is_empty());
+    let seed_hex = parts.next().map(str::trim).filter(|s| !s.is_empty());
+
+    let private_key = decode_private_key_hex(private_hex)?;
+    let derived_public = extract_public_key_from_meshcore_key(&private_key[..])
+        .context("Private key is not a valid MeshCore expanded key")?;
+    let public_key = hex::encode(derived_public).to_uppercase();
+
+    if let Some(stated) = stated_public
+        && !stated.eq_ignore_ascii_case(&public_key)
+    {
+        anyhow::bail!(
+            "Public key {} does not match the private key (expected {})",
+            stated,
+            public_key
+        );
+    }
+
+    if let Some(seed_hex) = seed_hex {
+        let mut seed = Zeroizing::new([0u8; 32]);
+        hex::decode_to_slice(seed_hex, &mut seed[..]).context("Seed must be 64 hex characters")?;
+        if create_meshcore_private_key(&seed)[..] != private_key[..] {
+            anyhow::bail!("Seed does not expand to the stored private key");
+        }
+    }
+
+    Ok(FoundKey {
+        private_key: SecureString::new(private_hex.to_uppercase()),
+        public_key,
+        seed: seed_hex.map(|seed| SecureString::new(seed.to_uppercase())),
+    })
+}
+
+/// Formats a key as a key store line (without the newline), the inverse of `parse_key_line`.
+pub fn format_key_line(key: &FoundKey) -> Zeroizing<String> {
+    Zeroizing::new(match &key.seed {
+        Some(seed) => format!(
+            "{}; {}; {}",
+            key.private_key.expose(),
+            key.public_key,
+            seed.expose()
+        ),
+        None => format!("{}; {}", key.private_key.expose(), key.public_key),
+    })
+}
+
+/// Appends a key to the key store, keeping the seed when one is known so the key can
+/// later be exported to formats that need it.
+pub fn append_key(path: &str, key: &FoundKey) -> Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", format_key_line(key).as_str())?;
+    Ok(())
+}
+
+/// Loads every key from the key store, skipping blank lines.
+/// Line numbers are reported on failure so users can fix hand-edited files.
+pub fn load_keys(path: &str) -> Result<Vec<FoundKey>> {
+    let contents = Zeroizing::new(
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?,
+    );
+
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            parse_key_line(line).with_context(|| format!("{}:{}", path, index + 1))
+        })
+        .collect()
+}
+
+/// Checks every entry of the key store: each line must parse, its private key must expand to
+/// the stated public key (and seed, when stored), and with `patterns` the public key must
+/// match one of them. Returns the line number and problem of every bad entry.
+pub fn check_keys(
+    path: &str,
+    patterns: Option<&PatternSet>,
+) -> Result<(usize, Vec<(usize, String)>)> {
+    let contents = Zeroizing::new(
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?,
+    );
+
+    let mut checked = 0;
+    let mut problems = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        checked += 1;
+        let problem = match parse_key_line(line) {
+            Err(e) => format!("{:#}", e),
+            Ok(key) => match patterns {
+                Some(patterns) if !patterns.matches(&decode_public_key(&key.public_key)?) => {
+                    format!("Public key {} does not match the pattern", key.public_key)
+                }
+                _ => continue,
+            },
+        };
+        problems.push((index + 1, problem));
+    }
+    Ok((checked, problems))
+}
+
+/// Reports the outcome of `check_keys`; any bad entry is an error so scripts can rely on
+/// the exit status.
+pub fn run_verify_keys(path: &str, patterns: Option<&PatternSet>) -> Result<()> {
+    let (checked, problems) = check_keys(path, patterns)?;
+    for (line, problem) in &problems {
+        say_warning!("❌ {}:{}: {}", path, line, problem);
+    }
+    if !problems.is_empty() {
+        anyhow::bail!(
+            "{} of {} keys in {} failed verification",
+            problems.len(),
+            checked,
+            path
+        );
+    }
+    say!("✅🔑 All {} keys in {} are intact", checked, path);
+    Ok(())
+}
+
+/// Reads a single key from the first non-empty line of a reader.
+/// Used for stdin so private keys never need to appear in argv or shell history.
+pub fn read_key_from_reader(mut reader: impl BufRead) -> Result<FoundKey> {
+    let mut line = Zeroizing::new(String::new());
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            anyhow::bail!("No key found on input");
+        }
+        if !line.trim().is_empty() {
+            return parse_key_line(&line);
+        }
+    }
+}
+
+/// How a command names the key it works on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeySelector {
+    /// A key line piped on stdin.
+    Stdin,
+    /// 1-based position in the key store. Kept apart from prefixes because an all-digit
+    /// string is a valid hex prefix too.
+    Index(usize),
+    /// Public key hex prefix.
+    Prefix(String),
+}
+
+/// Picks a key either from stdin, by 1-based position in the key store, or by a public key
+/// hex prefix. Omitting the selector is only allowed when the store holds one key, because
+/// silently picking the first of many would sign with an identity the user didn't mean.
+pub fn select_key(path: &str, selector: Option<&KeySelector>) -> Result<FoundKey> {
+    if selector == Some(&KeySelector::Stdin) {
+        return read_key_from_reader(std::io::stdin().lock());
+    }
+
+    let mut keys = load_keys(path)?;
+
+    let Some(selector) = selector else {
+        return match keys.len() {
+            0 => anyhow::bail!("No keys found in {}", path),
+            1 => Ok(keys.remove(0)),
+            n => anyhow::bail!(
+                "{} holds {} keys, choose one with --key PUBLIC_KEY_PREFIX or --index N",
+                path,
+                n
+            ),
+        };
+    };
+
+    let selector = match selector {
+        KeySelector::Index(index) if (1..=keys.len()).contains(index) => {
+            return Ok(keys.remove(index - 1));
+        }
+        KeySelector::Index(index) => {
+            anyhow::bail!("{} has no key #{} ({} stored)", path, index, keys.len())
+        }
+        KeySelector::Prefix(prefix) => prefix,
+        KeySelector::Stdin => unreachable!("handled above"),
+    };
+
+    let prefix = selector.to_uppercase();
+    let mut matching: Vec<usize> = keys
+        .iter()
+        .enumerate()
+        .filter(|(_, key)| key.public_key.starts_with(&prefix))
+        .map(|(index, _)| index)
+        .collect();
+
+    match matching.len() {
+        0 => anyhow::bail!("No key in {} matches '{}'", path, selector),
+        1 => Ok(keys.remove(matching.remove(0))),
+        n => anyhow::bail!(
+            "'{}' matches {} keys in {}, use a longer public key prefix",
+            selector,
+            n,
+            path
+        ),
+    }
+}
+
+/// Decodes the hex private key of a found key into a zeroizing 64-byte buffer.
+pub fn decode_private_key(key: &FoundKey) -> Result<Zeroizing<[u8; 64]>> {
+    decode_private_key_hex(key.private_key.expose())
+}
+
+/// Decodes a 32-byte public key from hex, rejecting anything that isn't exactly 64 hex digits.
+pub fn decode_public_key(public_hex: &str) -> Result<[u8; 32]> {
+    let mut public_key = [0u8; 32];
+    hex::decode_to_slice(public_hex.trim(), &mut public_key)
+        .with_context(|| format!("Invalid public key '{}'", public_hex.trim()))?;
+    Ok(public_key)
+}
+
+/// Decodes straight into a zeroizing buffer so no intermediate Vec copy of the key is left behind.
+fn decode_private_key_hex(private_hex: &str) -> Result<Zeroizing<[u8; 64]>> {
+    let mut private_key = Zeroizing::new([0u8; 64]);
+    hex::decode_to_slice(private_hex, &mut private_key[..])
+        .context("Private key must be 128 hex characters (64-byte expanded key)")?;
+    Ok(private_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    fn key_line(seed_byte: u8) -> (String, String) {
+        let private_key = create_meshcore_private_key(&[seed_byte; 32]);
+        let public_key = extract_public_key_from_meshcore_key(&private_key).unwrap();
+        (
+            hex::encode(private_key).to_uppercase(),
+            hex::encode(public_key).to_uppercase(),
+        )
+    }
+
+    fn write_store(lines: &[String]) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        for line in lines {
+            writeln!(file, "{}", line).unwrap();
+        }
+        file
+    }
+
+    #[test]
+    fn test_parse_key_line_with_and_without_public_key() {
+        let (private_hex, public_hex) = key_line(1);
+
+        let full = parse_key_line(&format!("{}; {}", private_hex, public_hex)).unwrap();
+        assert_eq!(full.public_key, public_hex);
+        assert_eq!(full.private_key.expose(), private_hex);
+
+        let bare = parse_key_line(&private_hex.to_lowercase()).unwrap();
+        assert_eq!(bare.public_key, public_hex);
+    }
+
+    #[test]
+    fn test_parse_key_line_rejects_mismatched_public_key() {
+        let (private_hex, _) = key_line(1);
+        let (_, other_public) = key_line(2);
+
+        let result = parse_key_line(&format!("{}; {}", private_hex, other_public));
+        assert!(result.unwrap_err().to_string().contains("does not match"));
+    }
+
+    #[test]
+    fn test_parse_key_line_rejects_bad_length() {
+        assert!(parse_key_line("ABCD").is_err());
+        assert!(parse_key_line("").is_err());
+    }
+
+    #[test]
+    fn test_select_key_by_index_and_prefix() {
+        let (private_a, public_a) = key_line(1);
+        let (private_b, public_b) = key_line(2);
+        let store = write_store(&[
+            format!("{}; {}", private_a, public_a),
+            String::new(),
+            format!("{}; {}", private_b, public_b),
+        ]);
+        let path = store.path().to_str().unwrap();
+
+        let by_index = |index| select_key(path, Some(&KeySelector::Index(index)));
+        let by_prefix = |prefix: &str| select_key(path, Some(&KeySelector::Prefix(prefix.into())));
+        assert_eq!(by_index(2).unwrap().public_key, public_b);
+        assert_eq!(
+            by_prefix(&public_a[..8].to_lowercase()).unwrap().public_key,
+            public_a
+        );
+        assert!(
+            select_key(path, None)
+                .unwrap_err()
+                .to_string()
+                .contains("holds 2 keys")
+        );
+        assert!(by_index(3).is_err());
+        assert!(by_index(0).is_err());
+    }
+
+    #[test]
+    fn test_select_key_digit_prefix_is_not_an_index() {
+        let keys: Vec<(String, String)> = (1..=64).map(key_line).collect();
+        let (target_private, target_public) = keys
+            .iter()
+            .find(|(_, public_hex)| matches!(public_hex.as_bytes()[0], b'1'..=b'9'))
+            .unwrap();
+        let digit = &target_public[..1];
+        // Nine other keys first, so the digit is also a valid position
+        let mut lines: Vec<String> = keys
+            .iter()
+            .filter(|(_, public_hex)| !public_hex.starts_with(digit))
+            .take(9)
+            .map(|(private_hex, public_hex)| format!("{}; {}", private_hex, public_hex))
+            .collect();
+        lines.push(format!("{}; {}", target_private, target_public));
+        let store = write_store(&lines);
+
+        let key = select_key(
+            store.path().to_str().unwrap(),
+            Some(&KeySelector::Prefix(digit.to_string())),
+        )
+        .unwrap();
+        assert_eq!(&key.public_key, target_public);
+    }
+
+    #[test]
+    fn test_select_key_single_entry_needs_no_selector() {
+        let (private_hex, public_hex) = key_line(3);
+        let store = write_store(&[format!("{}; {}", private_hex, public_hex)]);
+
+        let key = select_key(store.path().to_str().unwrap(), None).unwrap();
+        assert_eq!(key.public_key, public_hex);
+    }
+
+    #[test]
+    fn test_read_key_from_reader_skips_blank_lines() {
+        let (private_hex, public_hex) = key_line(4);
+        let input = format!("\n\n{}\n", private_hex);
+
+        let key = read_key_from_reader(input.as_bytes()).unwrap();
+        assert_eq!(key.public_key, public_hex);
+        assert!(read_key_from_reader("\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_decode_public_key() {
+        let (_, public_hex) = key_line(5);
+        let decoded = decode_public_key(&public_hex).unwrap();
+        assert_eq!(hex::encode(decoded).to_uppercase(), public_hex);
+        assert!(decode_public_key("BEEF").is_err());
+    }
+
+    #[test]
+    fn test_seed_round_trips_through_store() {
+        let seed = [6u8; 32];
+        let private_key = create_meshcore_private_key(&seed);
+        let public_key = extract_public_key_from_meshcore_key(&private_key).unwrap();
+        let key = FoundKey {
+            private_key: SecureString::new(hex::encode(private_key).to_uppercase()),
+            public_key: hex::encode(public_key).to_uppercase(),
+            seed: Some(SecureString::new(hex::encode(seed).to_uppercase())),
+        };
+
+        let store = NamedTempFile::new().unwrap();
+        let path = store.path().to_str().unwrap();
+        append_key(path, &key).unwrap();
+
+        let loaded = load_keys(path).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].public_key, key.public_key);
+        assert_eq!(
+            loaded[0].seed.as_ref().unwrap().expose(),
+            hex::encode(seed).to_uppercase()
+        );
+    }
+
+    #[test]
+    fn test_parse_key_line_rejects_mismatched_seed() {
+        let (private_hex, public_hex) = key_line(1);
+        let wrong_seed = hex::encode([2u8; 32]);
+
+        let result = parse_key_line(&format!("{}; {}; {}", private_hex, public_hex, wrong_seed));
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Seed does not expand")
+        );
+    }
+
+    #[test]
+    fn test_check_keys_reports_bad_lines() {
+        let (good_private, good_public) = key_line(1);
+        let (other_private, _) = key_line(2);
+        let store = write_store(&[
+            format!("{}; {}", good_private, good_public),
+            String::new(),
+            format!("{}; {}", other_private, good_public),
+            "not a key".to_string(),
+        ]);
+        let path = store.path().to_str().unwrap();
+
+        let (checked, problems) = check_keys(path, None).unwrap();
+        assert_eq!(checked, 3);
+        let lines: Vec<usize> = problems.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, vec![3, 4]);
+
+        // A pattern the good key can't match flags it too
+        let other_start = if good_public.starts_with('0') {
+            "1"
+        } else {
+            "0"
+        };
+        let patterns = PatternSet::compile(other_start).unwrap();
+        let (_, problems) = check_keys(path, Some(&patterns)).unwrap();
+        assert_eq!(problems[0].0, 1);
+        assert!(problems[0].1.contains("does not match the pattern"));
+    }
+}
@@ -1,126 +1,571 @@
+mod advert;
+mod checkpoint;
+mod cli;
+mod companion;
+mod config;
+mod contact;
 mod cpu;
+#[cfg(unix)]
+mod daemon;
+mod distributed;
+mod ecdh;
+mod hook;
+mod keyformat;
 mod keygen;
+mod keystore;
+mod metrics;
+mod output;
+mod pattern;
 mod performance;
+mod platform;
+mod provision;
 mod secure;
+mod signing;
+mod splitkey;
+mod tui;
 mod types;
 mod utils;
-use crate::keygen::run_key_search;
-use crate::secure::secure_wipe_file;
-use crate::types::{SearchBehavior, SearchConfig};
-use anyhow::Result;
-use clap::{Arg, Command};
+use crate::advert::{AdvertAppData, run_advert, run_decode_advert};
+use crate::cli::{
+    AdvertArgs, Cli, Commands, ConfigCommand, ContactArgs, DaemonArgs, DecodeAdvertArgs,
+    ExportArgs, FlashKeyArgs, GenerateArgs, GlobalArgs, ImportArgs, JoinArgs, KeysFileArgs,
+    MessageArgs, PublicKeyArgs, QueueCommand, RequestJobArgs, SearchArgs, SearchJobArgs, ServeArgs,
+    SharedSecretArgs, SignArgs, ToX25519Args, VerifyArgs, VerifySigArgs,
+};
+use crate::companion::run_flash_key;
+use crate::config::{Config, OutputFormat};
+use crate::contact::run_contact;
+use crate::distributed::{load_token, run_join, run_serve};
+use crate::ecdh::{run_shared_secret, run_to_x25519};
+use crate::keyformat::{run_export, run_import};
+use crate::keygen::{run_generate, run_key_search};
+use crate::keystore::{decode_private_key, decode_public_key, run_verify_keys, select_key};
+use crate::output::{EventFormat, Output, output};
+use crate::pattern::PatternSet;
+use crate::performance::run_benchmark;
+use crate::secure::{WipeMethod, secure_wipe_file};
+use crate::signing::{run_sign, run_verify_signature};
+use crate::splitkey::{run_combine, run_request_job, run_search_job};
+use crate::types::{NodeHashFilter, SearchBehavior, SearchConfig};
+use anyhow::{Context, Result};
+use std::io::Read;
+use zeroize::Zeroizing;
 
 /// Main entry point that handles command-line argument parsing and delegates to keygen module.
 fn main() -> Result<()> {
-    let matches = Command::new("meshcore-keygen")
-        .version("0.1.0")
-        .about("High-performance Ed25519 key searcher for generating custom public key patterns")
-        .long_about("Searches for Ed25519 keys with specific hex patterns in the public key. \
-                     Uses multi-threaded CPU processing for maximum performance.")
-        .arg(
-            Arg::new("pattern")
-                .help("Hex pattern to search for in the public key (e.g., BEEF, 123456, 00ABC)")
-                .long_help("The hexadecimal pattern to search for. Only characters 0-9 and A-F are allowed. \
-                           Examples: BEEF, 123456, 00ABC, FFCAFE")
-                .value_name("PATTERN")
-                .required_unless_present("delete")
-                .index(1),
-        )
-        .arg(
-            Arg::new("max-keys")
-                .long("max-keys")
-                .short('n')
-                .value_name("NUMBER")
-                .help("Maximum number of keys to find before stopping")
-                .long_help("Stop searching after finding this many keys. Use 0 for unlimited search.")
-                .value_parser(clap::value_parser!(usize))
-                .default_value("1"),
-        )
-        .arg(
-            Arg::new("delete")
-                .long("delete")
-                .short('d')
-                .action(clap::ArgAction::SetTrue)
-                .help("Securely delete the meshcore-keys.txt and exit")
-                .long_help("Securely deletes meshcore-keys.txt using platform specific tooling."),
-        )
-        .get_matches();
+    let cli = Cli::parse_args();
+
+    let settings = match &cli.global.config {
+        _ if cli.global.no_config => Config::default(),
+        Some(path) => Config::load(Some(path))?,
+        None => Config::load(None)?,
+    };
+    configure_output(&cli.global, &settings)?;
 
-    // Handle secure delete option
-    if matches.get_flag("delete") {
-        return handle_secure_delete();
+    match cli.command {
+        Commands::Search(args) if args.delete => {
+            handle_wipe(args.wipe_method, &args.keys, &settings)
+        }
+        Commands::Search(args) => handle_search(args, &settings),
+        Commands::Generate(args) => handle_generate(args, &settings),
+        Commands::Verify(args) => handle_verify(&args, &settings),
+        Commands::Wipe(args) => handle_wipe(args.wipe_method, &args.keys, &settings),
+        Commands::Benchmark(args) => run_benchmark(configured_threads(&settings)?, args.force),
+        Commands::Export(args) => handle_export(&args, &settings),
+        Commands::Import(args) => handle_import(&args, &settings),
+        Commands::Sign(args) => handle_sign(&args, &settings),
+        Commands::VerifySig(args) => handle_verify_signature(&args, &settings),
+        Commands::SharedSecret(args) => handle_shared_secret(&args, &settings),
+        Commands::ToX25519(args) => handle_to_x25519(&args, &settings),
+        Commands::Advert(args) => handle_advert(&args, &settings),
+        Commands::DecodeAdvert(args) => handle_decode_advert(&args),
+        Commands::Contact(args) => handle_contact(&args, &settings),
+        Commands::FlashKey(args) => handle_flash_key(&args, &settings),
+        Commands::Serve(args) => handle_serve(args, &settings),
+        Commands::Join(args) => handle_join(&args, &settings),
+        Commands::RequestJob(args) => handle_request_job(args, &settings),
+        Commands::SearchJob(args) => handle_search_job(&args, &settings),
+        Commands::Combine(args) => run_combine(
+            &args.secret.secret,
+            &args.results,
+            args.keys.path(&settings),
+        ),
+        Commands::Daemon(args) => handle_daemon(&args, &settings),
+        Commands::Queue { command } => handle_queue(command, &settings),
+        Commands::Config {
+            command: ConfigCommand::Show,
+        } => {
+            print!("{}", settings.show(default_cpu_threads()?)?);
+            Ok(())
+        }
     }
+}
 
-    // Parse arguments and create configuration
-    let pattern = matches
-        .get_one::<String>("pattern")
-        .ok_or_else(|| anyhow::anyhow!("Pattern is required"))?
-        .clone();
+/// Searches for keys matching the pattern, or the patterns of a profile.
+fn handle_search(args: SearchArgs, settings: &Config) -> Result<()> {
+    if args.tui && output().events_on_stderr() {
+        anyhow::bail!(
+            "--tui needs the terminal; send events to another descriptor with --events-fd"
+        );
+    }
 
-    let max_keys = *matches.get_one::<usize>("max-keys").unwrap();
+    // Parse arguments and create configuration; an explicit -n beats the profile's count
+    let profile = args
+        .profile
+        .as_deref()
+        .map(|name| settings.profile(name))
+        .transpose()?;
+    let pattern = match profile {
+        Some(profile) => profile.pattern(),
+        None => args.pattern.unwrap_or_default(),
+    };
+    let max_keys = args
+        .max_keys
+        .or(profile.and_then(|profile| profile.max_keys))
+        .unwrap_or(1);
+
+    let excluded_hashes = args.hashes.load(settings)?;
 
-    let config = create_search_config(pattern, max_keys)?;
+    let mut config = create_search_config(pattern, max_keys, excluded_hashes)?;
+    config.cpu_threads = requested_threads(args.threads.threads, settings)?;
+    config.keys_file = args.keys.path(settings).to_string();
+    config.contact_share = args.contact.contact_share();
+    config.cli_script = args.cli_script.options()?;
+    config.on_found = args.found_hook.hook();
+    config.tui = args.tui;
+    config.metrics_listen = args.metrics_listen;
 
     // Run the key search
     run_key_search(config)
 }
 
-/// Handles the secure deletion of the keys file.
-pub fn handle_secure_delete() -> Result<()> {
-    secure_wipe_file("meshcore-keys.txt")?;
+/// Installs the process-wide output mode from `--quiet`, `--events` and `--events-fd`, or
+/// from the configured output format when neither --quiet nor --events is given.
+fn configure_output(args: &GlobalArgs, settings: &Config) -> Result<()> {
+    let from_flags = args.quiet || args.events.is_some();
+    let (quiet, events) = match settings.output() {
+        _ if from_flags => (args.quiet, args.events),
+        OutputFormat::Text => (false, None),
+        OutputFormat::Quiet => (true, None),
+        OutputFormat::Jsonl => (true, Some(EventFormat::JsonLines)),
+    };
+    let output = Output::new(quiet);
+    let output = match (events, args.events_fd) {
+        (None, _) => output,
+        (Some(EventFormat::JsonLines), None) => output.with_events_to_stderr(),
+        #[cfg(unix)]
+        (Some(EventFormat::JsonLines), Some(fd)) => output.with_events_to_fd(fd)?,
+        #[cfg(not(unix))]
+        (Some(EventFormat::JsonLines), Some(_)) => {
+            anyhow::bail!("--events-fd is only supported on Unix")
+        }
+    };
+    output.install();
     Ok(())
 }
 
+/// Runs the job daemon in the foreground.
+#[cfg(unix)]
+fn handle_daemon(args: &DaemonArgs, settings: &Config) -> Result<()> {
+    let state_dir = match &args.state_dir {
+        Some(dir) => dir.clone(),
+        None => daemon::default_state_dir()
+            .context("No state directory; set $XDG_STATE_HOME or pass --state-dir")?,
+    };
+    daemon::run_daemon(
+        &state_dir,
+        &daemon_socket(&args.socket)?,
+        requested_threads(args.threads.threads, settings)?,
+    )
+}
+
+#[cfg(not(unix))]
+fn handle_daemon(_args: &DaemonArgs, _settings: &Config) -> Result<()> {
+    anyhow::bail!("The daemon is only supported on Unix")
+}
+
+/// Talks to a running daemon.
+#[cfg(unix)]
+fn handle_queue(command: QueueCommand, settings: &Config) -> Result<()> {
+    use crate::daemon::{JobSpec, Request};
+
+    let (request, socket) = match command {
+        QueueCommand::Submit(args) => {
+            // Normalised and checked here so mistakes show up before they reach the queue
+            let config =
+                create_search_config(args.pattern, args.max_keys, args.hashes.load(settings)?)?;
+            let request = Request::Submit {
+                spec: JobSpec {
+                    pattern: config.prefix,
+                    count: args.max_keys,
+                    budget_secs: args.budget.map(|budget| budget.as_secs()),
+                    output: args.output,
+                    priority: args.priority,
+                    excluded_hashes: (0..=u8::MAX)
+                        .filter(|&hash| !config.excluded_hashes.allows(hash))
+                        .collect(),
+                },
+            };
+            (request, args.socket)
+        }
+        QueueCommand::Status { id, socket } => (Request::Status { id }, socket),
+        QueueCommand::Cancel(args) => (Request::Cancel { id: args.id }, args.socket),
+        QueueCommand::Results(args) => (Request::Results { id: args.id }, args.socket),
+    };
+    daemon::run_queue_request(&daemon_socket(&socket)?, request)
+}
+
+#[cfg(not(unix))]
+fn handle_queue(_command: QueueCommand, _settings: &Config) -> Result<()> {
+    anyhow::bail!("The daemon is only supported on Unix")
+}
+
+#[cfg(unix)]
+fn daemon_socket(args: &cli::SocketArgs) -> Result<std::path::PathBuf> {
+    match &args.socket {
+        Some(path) => Ok(path.clone()),
+        None => daemon::default_socket_path()
+            .context("No socket path; set $XDG_RUNTIME_DIR or pass --socket"),
+    }
+}
+
+/// Handles the secure deletion of the key store.
+fn handle_wipe(method: Option<WipeMethod>, keys: &KeysFileArgs, settings: &Config) -> Result<()> {
+    secure_wipe_file(
+        keys.path(settings),
+        method.unwrap_or(settings.wipe_method()),
+    )
+}
+
+/// Checks every key store entry, and optionally that it matches a pattern.
+fn handle_verify(args: &VerifyArgs, settings: &Config) -> Result<()> {
+    let patterns = match &args.pattern {
+        Some(pattern) => Some(PatternSet::compile(&pattern.to_uppercase())?),
+        None => None,
+    };
+    run_verify_keys(args.keys.path(settings), patterns.as_ref())
+}
+
+/// Generates a batch of pattern-free keys.
+fn handle_generate(args: GenerateArgs, settings: &Config) -> Result<()> {
+    let mut config =
+        create_generate_config(args.count, args.hashes.load(settings)?, args.unique_hashes)?;
+    config.contact_share = args.contact.contact_share();
+    config.cpu_threads = requested_threads(args.threads.threads, settings)?;
+    config.keys_file = args.keys.path(settings).to_string();
+    config.cli_script = args.cli_script.options()?;
+    config.on_found = args.found_hook.hook();
+    run_generate(config)
+}
+
+/// Prints the contact URI and QR code for a stored key or a bare public key.
+fn handle_contact(args: &ContactArgs, settings: &Config) -> Result<()> {
+    let public_key = hex::encode_upper(resolve_public_key(&args.key, settings)?);
+    run_contact(
+        &public_key,
+        args.name.as_deref(),
+        args.node_type,
+        args.qr,
+        args.qr_file.as_deref(),
+    )
+}
+
+/// Pushes a stored key to a companion radio over serial.
+fn handle_flash_key(args: &FlashKeyArgs, settings: &Config) -> Result<()> {
+    run_flash_key(
+        &args.port,
+        args.baud,
+        args.key.keys.path(settings),
+        args.key.selector.selector().as_ref(),
+    )
+}
+
+/// Imports a key file (or stdin) into the key store.
+fn handle_import(args: &ImportArgs, settings: &Config) -> Result<()> {
+    let input = &args.input;
+    let bytes = Zeroizing::new(if input == "-" {
+        let mut buffer = Vec::new();
+        std::io::stdin().read_to_end(&mut buffer)?;
+        buffer
+    } else {
+        std::fs::read(input).with_context(|| format!("Failed to read {}", input))?
+    });
+
+    run_import(args.keys.path(settings), &bytes, args.format)
+}
+
+/// Exports a key from the key store in another format.
+fn handle_export(args: &ExportArgs, settings: &Config) -> Result<()> {
+    run_export(
+        args.key.keys.path(settings),
+        args.key.selector.selector().as_ref(),
+        args.format,
+        args.output.as_deref(),
+        args.comment.as_deref(),
+    )
+}
+
+/// Coordinates a search for workers on other machines.
+fn handle_serve(args: ServeArgs, settings: &Config) -> Result<()> {
+    let token = load_token(args.token.token_file.as_deref())?;
+    let mut config = create_search_config(
+        args.pattern.unwrap_or_default(),
+        args.max_keys,
+        args.hashes.load(settings)?,
+    )?;
+    config.keys_file = args.keys.path(settings).to_string();
+    run_serve(config, args.listen, token)
+}
+
+/// Lends this machine's cores to a coordinator.
+fn handle_join(args: &JoinArgs, settings: &Config) -> Result<()> {
+    let token = load_token(args.token.token_file.as_deref())?;
+    let threads = requested_threads(args.threads.threads, settings)?;
+    run_join(
+        &args.addr,
+        &token,
+        args.name.as_deref().unwrap_or_default(),
+        threads,
+    )?;
+    Ok(())
+}
+
+/// Writes a split-key job and keeps its secret.
+fn handle_request_job(args: RequestJobArgs, settings: &Config) -> Result<()> {
+    let config = create_search_config(
+        args.pattern.unwrap_or_default(),
+        1,
+        args.hashes.load(settings)?,
+    )?;
+    run_request_job(&config, &args.job, &args.secret.secret)
+}
+
+/// Searches someone else's split-key job.
+fn handle_search_job(args: &SearchJobArgs, settings: &Config) -> Result<()> {
+    run_search_job(
+        &args.job,
+        requested_threads(args.threads.threads, settings)?,
+        args.max_keys,
+        &args.output,
+    )
+}
+
+/// --threads, or the configured thread count.
+fn requested_threads(threads: Option<usize>, settings: &Config) -> Result<usize> {
+    match threads {
+        Some(threads) => Ok(threads),
+        None => configured_threads(settings),
+    }
+}
+
+/// The configured thread count, or all cores but one.
+fn configured_threads(settings: &Config) -> Result<usize> {
+    match settings.threads() {
+        Some(threads) => Ok(threads),
+        None => default_cpu_threads(),
+    }
+}
+
+/// Signs a message with a stored or piped expanded key.
+fn handle_sign(args: &SignArgs, settings: &Config) -> Result<()> {
+    let message = parse_message(&args.message)?;
+    run_sign(
+        args.key.keys.path(settings),
+        args.key.selector.selector().as_ref(),
+        &message,
+    )
+}
+
+/// Verifies a signature against an explicit public key or one taken from the key store.
+fn handle_verify_signature(args: &VerifySigArgs, settings: &Config) -> Result<()> {
+    let message = parse_message(&args.message)?;
+    let public_key = resolve_public_key(&args.key, settings)?;
+
+    let mut signature = [0u8; 64];
+    hex::decode_to_slice(args.signature.trim(), &mut signature)
+        .context("Signature must be 128 hex characters")?;
+
+    run_verify_signature(&public_key, &message, &signature)
+}
+
+/// Derives the ECDH shared secret between a stored key and a peer.
+fn handle_shared_secret(args: &SharedSecretArgs, settings: &Config) -> Result<()> {
+    let peer = decode_public_key(&args.peer)?;
+    run_shared_secret(
+        args.key.keys.path(settings),
+        args.key.selector.selector().as_ref(),
+        &peer,
+    )
+}
+
+/// Converts a public key, or a stored key pair, to X25519.
+fn handle_to_x25519(args: &ToX25519Args, settings: &Config) -> Result<()> {
+    if !args.private {
+        return run_to_x25519(&resolve_public_key(&args.key, settings)?, None);
+    }
+
+    let key = select_key(
+        args.key.keys.path(settings),
+        args.key.selector.selector().as_ref(),
+    )?;
+    let private_key = decode_private_key(&key)?;
+    run_to_x25519(&decode_public_key(&key.public_key)?, Some(&private_key))
+}
+
+/// Builds and signs a self-advert for a stored key.
+fn handle_advert(args: &AdvertArgs, settings: &Config) -> Result<()> {
+    let location = match (args.lat, args.lon) {
+        (Some(lat), Some(lon)) => Some((lat, lon)),
+        _ => None,
+    };
+
+    let app_data = AdvertAppData {
+        node_type: args.node_type,
+        location,
+        name: args.name.clone(),
+    };
+
+    run_advert(
+        args.key.keys.path(settings),
+        args.key.selector.selector().as_ref(),
+        &app_data,
+        args.timestamp,
+        args.packet.packet,
+        args.output.as_deref(),
+    )
+}
+
+/// Decodes an advert given as hex or read from a binary file.
+fn handle_decode_advert(args: &DecodeAdvertArgs) -> Result<()> {
+    let bytes = match (&args.input, &args.advert) {
+        (Some(path), _) => {
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path))?
+        }
+        (None, advert) => decode_message(advert.as_deref().unwrap_or_default(), true)?,
+    };
+    run_decode_advert(&bytes, args.packet.packet)
+}
+
+/// Uses --public-key when given, otherwise the public half of the selected key store entry.
+fn resolve_public_key(args: &PublicKeyArgs, settings: &Config) -> Result<[u8; 32]> {
+    match &args.public_key {
+        Some(public_hex) => decode_public_key(public_hex),
+        None => {
+            let key = select_key(args.keys.path(settings), args.selector.selector().as_ref())?;
+            decode_public_key(&key.public_key)
+        }
+    }
+}
+
+/// Decodes the MESSAGE argument as text or, with --hex, as raw bytes.
+fn parse_message(args: &MessageArgs) -> Result<Vec<u8>> {
+    decode_message(&args.message, args.hex)
+}
+
+/// Adverts and other MeshCore frames are binary, so hex input is needed to sign them exactly.
+pub fn decode_message(message: &str, is_hex: bool) -> Result<Vec<u8>> {
+    if is_hex {
+        hex::decode(message.trim()).context("MESSAGE is not valid hex")
+    } else {
+        Ok(message.as_bytes().to_vec())
+    }
+}
+
 /// Validates command-line pattern and creates search configuration.
 /// Enforces Ed25519 constraints to prevent generating invalid keys that would be rejected by meshcore.
-pub fn create_search_config(pattern: String, max_keys: usize) -> Result<SearchConfig> {
-    let pattern = pattern.to_uppercase();
+/// An empty pattern is only meaningful when node hashes are being avoided.
+pub fn create_search_config(
+    pattern: String,
+    max_keys: usize,
+    excluded_hashes: NodeHashFilter,
+) -> Result<SearchConfig> {
+    let pattern: String = pattern.to_uppercase().split_whitespace().collect();
+
+    // Compiling rejects invalid characters and malformed wildcards before any worker starts
+    let patterns = PatternSet::compile(&pattern)?;
 
-    // Reject invalid hex characters to prevent runtime errors during key generation
-    if !pattern.chars().all(|c| "0123456789ABCDEF".contains(c)) {
+    // Checked on the compiled set: a bare comma compiles to a pattern that matches every key
+    if patterns.is_empty() && excluded_hashes.is_empty() {
+        anyhow::bail!("Pattern cannot be empty.");
+    }
+
+    if patterns.match_probability(&excluded_hashes) == 0.0 {
         anyhow::bail!(
-            "Invalid hex characters in pattern '{}'. Only 0-9 and A-F are allowed.",
+            "Every node hash compatible with pattern '{}' is already in use.",
             pattern
         );
     }
 
-    if pattern.is_empty() {
-        anyhow::bail!("Pattern cannot be empty.");
-    }
-
     let search_behavior = match max_keys {
         0 => SearchBehavior::Continuous,
         n => SearchBehavior::FindN(n),
     };
 
-    // Reserve one core for system operations to maintain responsiveness during intensive computation
-    let cpu_threads = std::thread::available_parallelism()?
-        .get()
-        .saturating_sub(1)
-        .max(1);
+    Ok(SearchConfig {
+        excluded_hashes,
+        ..SearchConfig::new(pattern, search_behavior, default_cpu_threads()?)
+    })
+}
+
+/// Creates the configuration for pattern-free generation.
+/// With unique hashes the batch can't be larger than the number of hashes still available,
+/// otherwise the workers would search forever for the last few keys.
+pub fn create_generate_config(
+    count: usize,
+    excluded_hashes: NodeHashFilter,
+    unique_hashes: bool,
+) -> Result<SearchConfig> {
+    if count == 0 {
+        anyhow::bail!("Count must be at least 1.");
+    }
+
+    let available_hashes = 256 - excluded_hashes.excluded_count();
+    if available_hashes == 0 {
+        anyhow::bail!("Every node hash is excluded, no key can be generated.");
+    }
+    if unique_hashes && count > available_hashes {
+        anyhow::bail!(
+            "Cannot generate {} keys with unique node hashes, only {} hashes are available.",
+            count,
+            available_hashes
+        );
+    }
 
     Ok(SearchConfig {
-        prefix: pattern,
-        search_behavior,
-        cpu_threads,
+        excluded_hashes,
+        unique_hashes,
+        ..SearchConfig::new(
+            String::new(),
+            SearchBehavior::FindN(count),
+            default_cpu_threads()?,
+        )
     })
 }
 
+/// Reserve one core for system operations to maintain responsiveness during intensive computation
+fn default_cpu_threads() -> Result<usize> {
+    Ok(std::thread::available_parallelism()?
+        .get()
+        .saturating_sub(1)
+        .max(1))
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::create_search_config;
-    use crate::types::SearchBehavior;
+    use crate::types::{NodeHashFilter, SearchBehavior};
+    use crate::{create_generate_config, create_search_config, decode_message};
 
     #[test]
     fn test_create_search_config() {
-        let config = create_search_config("BEEF".to_string(), 1).unwrap();
+        let config =
+            create_search_config("BEEF".to_string(), 1, NodeHashFilter::default()).unwrap();
         assert_eq!(config.prefix, "BEEF");
         assert!(matches!(config.search_behavior, SearchBehavior::FindN(1)));
     }
 
     #[test]
     fn test_create_search_config_invalid_hex() {
-        let result = create_search_config("XYZT".to_string(), 1);
+        let result = create_search_config("XYZT".to_string(), 1, NodeHashFilter::default());
         assert!(result.is_err());
         assert!(
             result
@@ -132,7 +577,7 @@ mod tests {
 
     #[test]
     fn test_create_search_config_valid_prefix_00() {
-        let result = create_search_config("00BEEF".to_string(), 1);
+        let result = create_search_config("00BEEF".to_string(), 1, NodeHashFilter::default());
         assert!(result.is_ok());
         let config = result.unwrap();
         assert_eq!(config.prefix, "00BEEF");
@@ -140,7 +585,7 @@ mod tests {
 
     #[test]
     fn test_create_search_config_valid_prefix_ff() {
-        let result = create_search_config("FFBEEF".to_string(), 1);
+        let result = create_search_config("FFBEEF".to_string(), 1, NodeHashFilter::default());
         assert!(result.is_ok());
         let config = result.unwrap();
         assert_eq!(config.prefix, "FFBEEF");
@@ -148,7 +593,7 @@ mod tests {
 
     #[test]
     fn test_create_search_config_empty_pattern() {
-        let result = create_search_config("".to_string(), 1);
+        let result = create_search_config("".to_string(), 1, NodeHashFilter::default());
         assert!(result.is_err());
         assert!(
             result
@@ -156,23 +601,85 @@ mod tests {
                 .to_string()
                 .contains("Pattern cannot be empty")
         );
+
+        for blank in [",", " , ", "*"] {
+            assert!(create_search_config(blank.to_string(), 0, NodeHashFilter::default()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_create_search_config_pattern_sets() {
+        let config =
+            create_search_config("*cafe, B??F".to_string(), 1, NodeHashFilter::default()).unwrap();
+        assert_eq!(config.prefix, "*CAFE,B??F");
+
+        assert!(create_search_config("CA*FE".to_string(), 1, NodeHashFilter::default()).is_err());
     }
 
     #[test]
     fn test_create_search_config_max_keys_variants() {
-        let config_one = create_search_config("BEEF".to_string(), 1).unwrap();
+        let config_one =
+            create_search_config("BEEF".to_string(), 1, NodeHashFilter::default()).unwrap();
         assert!(matches!(
             config_one.search_behavior,
             SearchBehavior::FindN(1)
         ));
 
-        let config_n = create_search_config("BEEF".to_string(), 5).unwrap();
+        let config_n =
+            create_search_config("BEEF".to_string(), 5, NodeHashFilter::default()).unwrap();
         assert!(matches!(config_n.search_behavior, SearchBehavior::FindN(5)));
 
-        let config_continuous = create_search_config("BEEF".to_string(), 0).unwrap();
+        let config_continuous =
+            create_search_config("BEEF".to_string(), 0, NodeHashFilter::default()).unwrap();
         assert!(matches!(
             config_continuous.search_behavior,
             SearchBehavior::Continuous
         ));
     }
+
+    #[test]
+    fn test_decode_message_text_and_hex() {
+        assert_eq!(decode_message("hi", false).unwrap(), b"hi".to_vec());
+        assert_eq!(decode_message("BEEF", true).unwrap(), vec![0xBE, 0xEF]);
+        assert_eq!(decode_message("BEEF", false).unwrap(), b"BEEF".to_vec());
+        assert!(decode_message("XYZ", true).is_err());
+    }
+
+    #[test]
+    fn test_create_search_config_avoided_hashes() {
+        let mut excluded_hashes = NodeHashFilter::default();
+        excluded_hashes.exclude(0xBE);
+
+        // No pattern is fine when there are hashes to avoid
+        let config = create_search_config(String::new(), 1, excluded_hashes.clone()).unwrap();
+        assert_eq!(config.prefix, "");
+        assert_eq!(config.excluded_hashes.excluded_count(), 1);
+
+        // A pattern pinned to an excluded hash can never match
+        let result = create_search_config("BEEF".to_string(), 1, excluded_hashes);
+        assert!(result.unwrap_err().to_string().contains("already in use"));
+    }
+
+    #[test]
+    fn test_create_generate_config() {
+        let config = create_generate_config(10, NodeHashFilter::default(), true).unwrap();
+        assert_eq!(config.prefix, "");
+        assert!(config.unique_hashes);
+        assert!(matches!(config.search_behavior, SearchBehavior::FindN(10)));
+
+        assert!(create_generate_config(0, NodeHashFilter::default(), false).is_err());
+    }
+
+    #[test]
+    fn test_create_generate_config_unique_capacity() {
+        let mut excluded_hashes = NodeHashFilter::default();
+        excluded_hashes.exclude_reserved();
+
+        assert!(create_generate_config(254, excluded_hashes.clone(), true).is_ok());
+        let result = create_generate_config(255, excluded_hashes.clone(), true);
+        assert!(result.unwrap_err().to_string().contains("only 254"));
+
+        // Without the uniqueness check hashes may repeat, so any count works
+        assert!(create_generate_config(1000, excluded_hashes, false).is_ok());
+    }
 }
@@ -0,0 +1,323 @@
+use crate::performance::SearchEstimate;
+use crate::types::{SearchStats, WorkerSnapshot};
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// How often the accept loop checks whether it should shut down.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Scrapers send a handful of headers; anything bigger is not a scrape.
+const MAX_REQUEST_BYTES: usize = 8192;
+
+/// A slow or idle client must not hold up the next scrape for long.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Renders the current search state in the OpenMetrics text format. Rates are averages
+/// since the start; Prometheus users get instantaneous rates from `rate()` on the counters.
+pub fn render_openmetrics(stats: &SearchStats, estimate: &SearchEstimate) -> String {
+    let elapsed = stats.elapsed().as_secs_f64().max(f64::EPSILON);
+    let attempts = stats.total_attempts.load(Ordering::Relaxed);
+    let matches = stats.prefix_matches.load(Ordering::Relaxed);
+    let workers = stats.worker_snapshots();
+
+    let mut out = String::new();
+    // Writing to a String can't fail
+    let mut family = |name: &str, kind: &str, help: &str, samples: Vec<(String, String)>| {
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        // Counter samples carry the _total suffix, gauges use the family name as is
+        let suffix = if kind == "counter" { "_total" } else { "" };
+        for (labels, value) in samples {
+            let _ = writeln!(out, "{}{}{} {}", name, suffix, labels, value);
+        }
+    };
+    let single = |value: String| vec![(String::new(), value)];
+    let per_worker = |value: &dyn Fn(&WorkerSnapshot) -> String| {
+        workers
+            .iter()
+            .enumerate()
+            .map(|(thread_id, worker)| (format!("{{worker=\"{}\"}}", thread_id), value(worker)))
+            .collect()
+    };
+
+    family(
+        "meshcore_keygen_attempts",
+        "counter",
+        "Keys generated and checked against the pattern.",
+        single(attempts.to_string()),
+    );
+    family(
+        "meshcore_keygen_matches",
+        "counter",
+        "Keys that matched the pattern.",
+        single(matches.to_string()),
+    );
+    family(
+        "meshcore_keygen_keys_per_second",
+        "gauge",
+        "Average search speed since the start.",
+        single((attempts as f64 / elapsed).to_string()),
+    );
+    family(
+        "meshcore_keygen_elapsed_seconds",
+        "gauge",
+        "Time since the search started.",
+        single(elapsed.to_string()),
+    );
+    family(
+        "meshcore_keygen_match_probability",
+        "gauge",
+        "Chance that a single attempt matches.",
+        single(format!("{:e}", estimate.probability)),
+    );
+    family(
+        "meshcore_keygen_expected_attempts",
+        "gauge",
+        "Average attempts needed per match.",
+        single(estimate.expected_attempts.to_string()),
+    );
+    family(
+        "meshcore_keygen_success_probability",
+        "gauge",
+        "Chance that the next match should have turned up by now, counting attempts since the last one.",
+        single(
+            estimate
+                .probability_within(stats.attempts_since_match() as f64)
+                .to_string(),
+        ),
+    );
+    family(
+        "meshcore_keygen_worker_attempts",
+        "counter",
+        "Attempts per worker thread.",
+        per_worker(&|worker| worker.attempts.to_string()),
+    );
+    family(
+        "meshcore_keygen_worker_keys_per_second",
+        "gauge",
+        "Average speed per worker thread since the start.",
+        per_worker(&|worker| (worker.attempts as f64 / elapsed).to_string()),
+    );
+    family(
+        "meshcore_keygen_worker_matches",
+        "counter",
+        "Matches per worker thread.",
+        per_worker(&|worker| worker.matches.to_string()),
+    );
+
+    out.push_str("# EOF\n");
+    out
+}
+
+/// Minimal HTTP/1.1 server for `/metrics`. One request per connection, no keep-alive:
+/// that is all a Prometheus scrape needs, and it keeps the server to a few dozen lines.
+pub struct MetricsServer {
+    local_addr: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MetricsServer {
+    /// Binds right away so a taken port is reported before the search starts.
+    pub fn start(
+        addr: SocketAddr,
+        stats: Arc<SearchStats>,
+        estimate: SearchEstimate,
+    ) -> Result<Self> {
+        let listener =
+            TcpListener::bind(addr).with_context(|| format!("Failed to listen on {}", addr))?;
+        // Non-blocking accept lets the thread notice shutdown without a wake-up connection
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let thread_shutdown = Arc::clone(&shutdown);
+        let handle = std::thread::spawn(move || {
+            while !thread_shutdown.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        // A broken client connection only affects that scrape
+                        let _ = serve_connection(stream, &stats, &estimate);
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                        std::thread::sleep(ACCEPT_POLL_INTERVAL);
+                    }
+                    Err(_) => std::thread::sleep(ACCEPT_POLL_INTERVAL),
+                }
+            }
+        });
+
+        Ok(Self {
+            local_addr,
+            shutdown,
+            handle: Some(handle),
+        })
+    }
+
+    /// The bound address, which differs from the requested one when port 0 was asked for.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn serve_connection(
+    mut stream: TcpStream,
+    stats: &SearchStats,
+    estimate: &SearchEstimate,
+) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+
+    // Only the request line matters, but read the whole header block so the client
+    // doesn't see a reset while it is still sending
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST_BYTES {
+        let read = stream.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let (method, path) = (request_line.next(), request_line.next());
+
+    let (status, content_type, body) = match (method, path) {
+        (Some("GET"), Some("/metrics")) => {
+            ("200 OK", CONTENT_TYPE, render_openmetrics(stats, estimate))
+        }
+        (Some("GET"), Some("/")) => (
+            "200 OK",
+            "text/plain; charset=utf-8",
+            "meshcore-keygen: metrics are at /metrics\n".to_string(),
+        ),
+        (Some("GET"), _) => (
+            "404 Not Found",
+            "text/plain; charset=utf-8",
+            "Not found\n".to_string(),
+        ),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain; charset=utf-8",
+            "Only GET is supported\n".to_string(),
+        ),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(addr: SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_render_openmetrics() {
+        let stats = SearchStats::with_workers(2);
+        stats.record_attempts(0, 300);
+        stats.record_attempts(1, 100);
+        stats.record_match(1);
+        let estimate = SearchEstimate::from_probability(1.0 / 256.0);
+
+        let text = render_openmetrics(&stats, &estimate);
+        assert!(text.contains("meshcore_keygen_attempts_total 400\n"));
+        assert!(text.contains("meshcore_keygen_matches_total 1\n"));
+        assert!(text.contains("meshcore_keygen_expected_attempts 256\n"));
+        assert!(text.contains("meshcore_keygen_worker_attempts_total{worker=\"0\"} 300\n"));
+        assert!(text.contains("meshcore_keygen_worker_matches_total{worker=\"1\"} 1\n"));
+        assert!(text.ends_with("# EOF\n"));
+
+        // The match restarted the odds, so the gauge starts again from zero
+        assert!(text.contains("meshcore_keygen_success_probability 0\n"));
+        stats.record_attempts(0, 256);
+        let text = render_openmetrics(&stats, &estimate);
+        let expected = estimate.probability_within(256.0);
+        assert!(text.contains(&format!(
+            "meshcore_keygen_success_probability {}\n",
+            expected
+        )));
+
+        // Each family's metadata sits directly above its samples
+        let lines: Vec<&str> = text.lines().collect();
+        let type_line = lines
+            .iter()
+            .position(|l| *l == "# TYPE meshcore_keygen_matches counter")
+            .unwrap();
+        assert!(lines[type_line + 1].starts_with("# HELP meshcore_keygen_matches "));
+        assert_eq!(lines[type_line + 2], "meshcore_keygen_matches_total 1");
+    }
+
+    #[test]
+    fn test_metrics_server_over_tcp() {
+        let stats = Arc::new(SearchStats::with_workers(1));
+        stats.record_attempts(0, 42);
+        let estimate = SearchEstimate::from_probability(1.0 / 16.0);
+
+        let server =
+            MetricsServer::start("127.0.0.1:0".parse().unwrap(), Arc::clone(&stats), estimate)
+                .unwrap();
+        let addr = server.local_addr();
+        assert_ne!(addr.port(), 0);
+
+        let response = get(
+            addr,
+            "GET /metrics HTTP/1.1\r\nHost: localhost\r\nAccept: */*\r\n\r\n",
+        );
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains(CONTENT_TYPE));
+        assert!(response.contains("meshcore_keygen_attempts_total 42\n"));
+
+        assert!(get(addr, "GET /nope HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 404"));
+        assert!(get(addr, "POST /metrics HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 405"));
+
+        drop(server);
+        assert!(TcpStream::connect(addr).is_err());
+    }
+
+    #[test]
+    fn test_metrics_server_reports_taken_port() {
+        let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+        let result = MetricsServer::start(
+            taken.local_addr().unwrap(),
+            Arc::new(SearchStats::new()),
+            SearchEstimate::from_probability(1.0),
+        );
+        assert!(result.is_err());
+    }
+}
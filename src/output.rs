@@ -0,0 +1,251 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+
+/// Machine-readable progress, one JSON object per line. Nothing here ever carries
+/// private key material: found keys are reported by public key only.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Started {
+        pattern: String,
+        threads: usize,
+        max_keys: Option<usize>,
+        probability: f64,
+        expected_attempts: f64,
+    },
+    Benchmark {
+        keys_per_sec_per_core: f64,
+        cores: usize,
+        cached: bool,
+    },
+    Progress {
+        attempts: u64,
+        matches: usize,
+        keys_per_sec: f64,
+        elapsed_secs: f64,
+        probability_so_far: f64,
+    },
+    Found {
+        index: usize,
+        public_key: String,
+        node_hash: String,
+        elapsed_secs: f64,
+    },
+    Finished {
+        found: usize,
+        attempts: u64,
+        elapsed_secs: f64,
+    },
+    /// Problems that would otherwise be printed to stderr in the middle of the stream.
+    Warning { message: String },
+}
+
+/// Supported `--events` formats. Only JSON lines for now, but keeping it an enum lets the
+/// flag stay stable if another format is added.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventFormat {
+    JsonLines,
+}
+
+impl FromStr for EventFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "jsonl" => Ok(Self::JsonLines),
+            other => anyhow::bail!("Unknown event format '{}' (expected jsonl)", other),
+        }
+    }
+}
+
+/// Where human-readable and machine-readable output go for this process.
+/// Set once from the command line; until then everything prints as usual.
+pub struct Output {
+    quiet: bool,
+    events: Option<Mutex<Box<dyn Write + Send>>>,
+    events_on_stderr: bool,
+}
+
+static OUTPUT: OnceLock<Output> = OnceLock::new();
+
+impl Output {
+    pub fn new(quiet: bool) -> Self {
+        Self {
+            quiet,
+            events: None,
+            events_on_stderr: false,
+        }
+    }
+
+    pub fn with_events_to_stderr(mut self) -> Self {
+        self.events = Some(Mutex::new(Box::new(std::io::stderr())));
+        self.events_on_stderr = true;
+        self
+    }
+
+    /// Sends events to an already open file descriptor, e.g. `3>events.jsonl` in the shell.
+    /// Going through /dev/fd avoids adopting a raw descriptor we don't own.
+    #[cfg(unix)]
+    pub fn with_events_to_fd(self, fd: u32) -> Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(format!("/dev/fd/{}", fd))
+            .with_context(|| format!("File descriptor {} is not open for writing", fd))?;
+        Ok(self.with_events_to(Box::new(file)))
+    }
+
+    pub fn with_events_to(mut self, writer: Box<dyn Write + Send>) -> Self {
+        self.events = Some(Mutex::new(writer));
+        self.events_on_stderr = false;
+        self
+    }
+
+    /// Installs this as the process-wide output. Only the first call has any effect.
+    pub fn install(self) {
+        let _ = OUTPUT.set(self);
+    }
+
+    pub fn is_quiet(&self) -> bool {
+        self.quiet
+    }
+
+    /// True when the terminal has to be left alone for the event stream.
+    pub fn events_on_stderr(&self) -> bool {
+        self.events_on_stderr
+    }
+
+    pub fn emit(&self, event: &Event) {
+        let Some(events) = &self.events else {
+            return;
+        };
+        let mut writer = events.lock().unwrap_or_else(|e| e.into_inner());
+        // Events are best effort: a closed pipe must not abort a running search
+        if let Ok(line) = serde_json::to_string(event) {
+            let _ = writeln!(writer, "{}", line);
+            let _ = writer.flush();
+        }
+    }
+
+    /// Warnings go to stderr, unless stderr carries the event stream, where a plain line
+    /// would break consumers.
+    pub fn warn(&self, message: String) {
+        if self.events_on_stderr {
+            self.emit(&Event::Warning { message });
+        } else {
+            eprintln!("{}", message);
+        }
+    }
+}
+
+/// The process-wide output settings.
+pub fn output() -> &'static Output {
+    OUTPUT.get_or_init(|| Output::new(false))
+}
+
+/// `println!` for decorative output that `--quiet` suppresses.
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if !$crate::output::output().is_quiet() {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// `print!` plus a flush, for progress lines that are rewritten in place.
+macro_rules! say_inline {
+    ($($arg:tt)*) => {
+        if !$crate::output::output().is_quiet() {
+            print!($($arg)*);
+            let _ = std::io::Write::flush(&mut std::io::stdout());
+        }
+    };
+}
+
+/// `eprintln!` that turns into a warning event when stderr carries events.
+macro_rules! say_warning {
+    ($($arg:tt)*) => {
+        $crate::output::output().warn(format!($($arg)*))
+    };
+}
+
+pub(crate) use {say, say_inline, say_warning};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Collects event output so tests can inspect it after handing ownership to `Output`.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_events_are_json_lines() {
+        let buffer = SharedBuffer::default();
+        let output = Output::new(true).with_events_to(Box::new(buffer.clone()));
+
+        output.emit(&Event::Found {
+            index: 1,
+            public_key: "CAFE".to_string(),
+            node_hash: "CA".to_string(),
+            elapsed_secs: 1.5,
+        });
+        output.emit(&Event::Finished {
+            found: 1,
+            attempts: 42,
+            elapsed_secs: 2.0,
+        });
+
+        let text = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[0],
+            r#"{"event":"found","index":1,"public_key":"CAFE","node_hash":"CA","elapsed_secs":1.5}"#
+        );
+        let finished: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(finished["event"], "finished");
+        assert_eq!(finished["attempts"], 42);
+        assert!(output.is_quiet());
+        assert!(!output.events_on_stderr());
+    }
+
+    #[test]
+    fn test_event_format_parsing() {
+        assert_eq!(
+            "jsonl".parse::<EventFormat>().unwrap(),
+            EventFormat::JsonLines
+        );
+        assert!("xml".parse::<EventFormat>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_events_to_file_descriptor() {
+        use std::os::unix::io::AsRawFd;
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let output = Output::new(false)
+            .with_events_to_fd(file.as_file().as_raw_fd() as u32)
+            .unwrap();
+        output.emit(&Event::Warning {
+            message: "hello".to_string(),
+        });
+
+        let text = std::fs::read_to_string(file.path()).unwrap();
+        assert_eq!(text, "{\"event\":\"warning\",\"message\":\"hello\"}\n");
+
+        assert!(Output::new(false).with_events_to_fd(9999).is_err());
+    }
+}
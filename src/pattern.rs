@@ -0,0 +1,340 @@
+use crate::types::NodeHashFilter;
+use crate::utils::{check_prefix_match, hex_string_to_bytes};
+use anyhow::Result;
+
+/// A public key is 32 bytes, i.e. 64 hex characters.
+const PUBLIC_KEY_NIBBLES: usize = 64;
+
+/// Inclusion-exclusion over overlapping patterns is exponential, so keep lists small.
+pub const MAX_PATTERNS: usize = 16;
+
+/// A single compiled pattern: which bits of the public key are fixed, and to what.
+/// Prefixes, suffixes and `?` wildcards all reduce to the same mask/value pair, which
+/// lets the matcher and the probability estimate share one representation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    mask: [u8; 32],
+    value: [u8; 32],
+    /// Leading bytes without wildcards, checked first as the cheap common-case reject.
+    leading_bytes: Vec<u8>,
+    /// Byte range that still needs the masked comparison after the leading bytes.
+    masked_range: (usize, usize),
+}
+
+impl Pattern {
+    /// Parses `CAFE` or `CAFE*` (prefix), `*BEEF` (suffix), with `?` matching any hex digit.
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = text.trim().to_uppercase();
+        let (body, is_suffix) = match (text.strip_prefix('*'), text.strip_suffix('*')) {
+            (Some(_), Some(_)) if text.len() > 1 => {
+                anyhow::bail!("Pattern '{}' can't be anchored at both ends", text)
+            }
+            (Some(body), _) => (body, true),
+            (None, Some(body)) => (body, false),
+            (None, None) => (text.as_str(), false),
+        };
+
+        if let Some(invalid) = body.chars().find(|c| !c.is_ascii_hexdigit() && *c != '?') {
+            anyhow::bail!(
+                "Invalid hex characters in pattern '{}' ('{}'). Only 0-9, A-F and ? are allowed.",
+                text,
+                invalid
+            );
+        }
+        if body.len() > PUBLIC_KEY_NIBBLES {
+            anyhow::bail!(
+                "Pattern '{}' is longer than a public key ({} hex characters)",
+                text,
+                PUBLIC_KEY_NIBBLES
+            );
+        }
+
+        let offset = if is_suffix {
+            PUBLIC_KEY_NIBBLES - body.len()
+        } else {
+            0
+        };
+        let mut pattern = Pattern {
+            mask: [0; 32],
+            value: [0; 32],
+            leading_bytes: Vec::new(),
+            masked_range: (0, 0),
+        };
+        for (index, digit) in body.chars().enumerate() {
+            if let Some(digit) = digit.to_digit(16) {
+                pattern.fix_nibble(offset + index, digit as u8);
+            }
+        }
+
+        // Whole leading bytes without wildcards can use the plain byte comparison
+        let leading_hex: String = body.chars().take_while(|c| *c != '?').collect::<String>();
+        let leading_len = if is_suffix { 0 } else { leading_hex.len() / 2 };
+        pattern.leading_bytes = hex_string_to_bytes(&leading_hex[..leading_len * 2]);
+
+        let last_fixed = pattern
+            .mask
+            .iter()
+            .rposition(|&m| m != 0)
+            .map_or(0, |i| i + 1);
+        pattern.masked_range = (leading_len.min(last_fixed), last_fixed);
+        Ok(pattern)
+    }
+
+    fn fix_nibble(&mut self, nibble: usize, digit: u8) {
+        let shift = if nibble.is_multiple_of(2) { 4 } else { 0 };
+        self.mask[nibble / 2] |= 0x0F << shift;
+        self.value[nibble / 2] |= digit << shift;
+    }
+
+    #[inline]
+    pub fn matches(&self, public_key: &[u8; 32]) -> bool {
+        let (start, end) = self.masked_range;
+        check_prefix_match(public_key, &self.leading_bytes)
+            && (start..end).all(|i| public_key[i] & self.mask[i] == self.value[i])
+    }
+
+    /// How many of the fixed hex digits this key gets right, for the "closest so far" display.
+    pub fn matched_nibbles(&self, public_key: &[u8; 32]) -> usize {
+        let end = self.masked_range.1;
+        let mut matched = 0;
+        for ((byte, mask), value) in public_key[..end].iter().zip(&self.mask).zip(&self.value) {
+            let diff = (byte ^ value) & mask;
+            matched += usize::from(mask & 0xF0 != 0 && diff & 0xF0 == 0);
+            matched += usize::from(mask & 0x0F != 0 && diff & 0x0F == 0);
+        }
+        matched
+    }
+
+    pub fn fixed_nibbles(&self) -> usize {
+        self.mask.iter().map(|m| m.count_ones() as usize / 4).sum()
+    }
+
+    /// The pattern both this one and `other` describe, or None if they contradict each other.
+    fn intersect(&self, other: &Pattern) -> Option<Pattern> {
+        let mut combined = self.clone();
+        for i in 0..32 {
+            if (self.value[i] ^ other.value[i]) & self.mask[i] & other.mask[i] != 0 {
+                return None;
+            }
+            combined.mask[i] |= other.mask[i];
+            combined.value[i] |= other.value[i];
+        }
+        Some(combined)
+    }
+
+    /// Exact probability that a uniformly random public key matches and has an allowed
+    /// node hash. The first byte is counted against the filter; every other fixed bit
+    /// halves the probability.
+    fn probability(&self, excluded_hashes: &NodeHashFilter) -> f64 {
+        let allowed_first_bytes =
+            excluded_hashes.count_allowed_masked(self.mask[0], self.value[0]) as f64;
+        let fixed_bits: u32 = self.mask[1..].iter().map(|m| m.count_ones()).sum();
+        allowed_first_bytes / 256.0 * 0.5_f64.powi(fixed_bits as i32)
+    }
+}
+
+/// One or more patterns, any of which counts as a match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternSet {
+    patterns: Vec<Pattern>,
+}
+
+impl PatternSet {
+    /// Compiles a comma-separated pattern list. An empty list matches every key,
+    /// which is what hash-only searches and `generate` rely on.
+    pub fn compile(text: &str) -> Result<Self> {
+        let patterns = text
+            .split(',')
+            .map(str::trim)
+            .filter(|pattern| !pattern.is_empty())
+            .map(Pattern::parse)
+            .collect::<Result<Vec<_>>>()?;
+
+        if patterns.len() > MAX_PATTERNS {
+            anyhow::bail!(
+                "At most {} patterns can be searched at once, got {}",
+                MAX_PATTERNS,
+                patterns.len()
+            );
+        }
+
+        Ok(PatternSet {
+            patterns: if patterns.is_empty() {
+                vec![Pattern::parse("")?]
+            } else {
+                patterns
+            },
+        })
+    }
+
+    /// True when no pattern fixes a single digit, e.g. for `""`, `","` or `"*"`.
+    pub fn is_empty(&self) -> bool {
+        self.patterns
+            .iter()
+            .all(|pattern| pattern.fixed_nibbles() == 0)
+    }
+
+    #[inline]
+    pub fn matches(&self, public_key: &[u8; 32]) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.matches(public_key))
+    }
+
+    /// The pattern this key comes closest to, as (matched, fixed) hex digits.
+    pub fn closest_match(&self, public_key: &[u8; 32]) -> (usize, usize) {
+        self.patterns
+            .iter()
+            .map(|pattern| (pattern.matched_nibbles(public_key), pattern.fixed_nibbles()))
+            .max_by_key(|&(matched, _)| matched)
+            .unwrap_or((0, 0))
+    }
+
+    /// Exact per-attempt probability that a random key matches any pattern and uses an
+    /// allowed node hash. Overlapping patterns are handled by inclusion-exclusion;
+    /// contradictory combinations are pruned, so disjoint lists stay linear.
+    pub fn match_probability(&self, excluded_hashes: &NodeHashFilter) -> f64 {
+        fn visit(
+            rest: &[Pattern],
+            current: &Pattern,
+            sign: f64,
+            excluded_hashes: &NodeHashFilter,
+            total: &mut f64,
+        ) {
+            for (index, pattern) in rest.iter().enumerate() {
+                if let Some(combined) = current.intersect(pattern) {
+                    *total += sign * combined.probability(excluded_hashes);
+                    visit(&rest[index + 1..], &combined, -sign, excluded_hashes, total);
+                }
+            }
+        }
+
+        let mut total = 0.0;
+        let everything = Pattern::parse("").expect("the empty pattern is valid");
+        visit(
+            &self.patterns,
+            &everything,
+            1.0,
+            excluded_hashes,
+            &mut total,
+        );
+        total.clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_with_hex(hex: &str) -> [u8; 32] {
+        let mut key = [0u8; 32];
+        hex::decode_to_slice(hex, &mut key).unwrap();
+        key
+    }
+
+    const KEY: &str = "CAFEBABE00112233445566778899AABBCCDDEEFF00112233445566778899BEEF";
+
+    #[test]
+    fn test_prefix_suffix_and_wildcards() {
+        let key = key_with_hex(KEY);
+
+        assert!(Pattern::parse("CAFE").unwrap().matches(&key));
+        assert!(Pattern::parse("cafe*").unwrap().matches(&key));
+        assert!(Pattern::parse("CAF").unwrap().matches(&key), "odd length");
+        assert!(!Pattern::parse("CAFF").unwrap().matches(&key));
+        assert!(Pattern::parse("*BEEF").unwrap().matches(&key));
+        assert!(Pattern::parse("*EEF").unwrap().matches(&key));
+        assert!(!Pattern::parse("*CAFE").unwrap().matches(&key));
+        assert!(Pattern::parse("C?F?BA").unwrap().matches(&key));
+        assert!(Pattern::parse("*B??F").unwrap().matches(&key));
+        assert!(Pattern::parse(KEY).unwrap().matches(&key));
+
+        assert!(Pattern::parse("*CA*").is_err());
+        assert!(Pattern::parse("CAFG").is_err());
+        assert!(Pattern::parse(&format!("{}0", KEY)).is_err());
+    }
+
+    #[test]
+    fn test_pattern_set_matches_any() {
+        let key = key_with_hex(KEY);
+        assert!(PatternSet::compile("0000, *BEEF").unwrap().matches(&key));
+        assert!(!PatternSet::compile("0000,*0000").unwrap().matches(&key));
+        assert!(PatternSet::compile("").unwrap().matches(&key));
+
+        let too_many = vec!["AB"; MAX_PATTERNS + 1].join(",");
+        assert!(PatternSet::compile(&too_many).is_err());
+    }
+
+    #[test]
+    fn test_closest_match() {
+        let key = key_with_hex(KEY);
+        assert_eq!(Pattern::parse("CAFF").unwrap().matched_nibbles(&key), 3);
+        assert_eq!(Pattern::parse("*0EEF").unwrap().matched_nibbles(&key), 3);
+        assert_eq!(Pattern::parse("C??E").unwrap().fixed_nibbles(), 2);
+
+        let patterns = PatternSet::compile("0000,CAF0,*BEE0").unwrap();
+        assert_eq!(patterns.closest_match(&key), (3, 4));
+        assert_eq!(PatternSet::compile("").unwrap().closest_match(&key), (0, 0));
+    }
+
+    #[test]
+    fn test_probability_single_patterns() {
+        let none = NodeHashFilter::default();
+        let p = |text: &str| PatternSet::compile(text).unwrap().match_probability(&none);
+
+        assert_eq!(p(""), 1.0);
+        assert_eq!(p("A"), 1.0 / 16.0);
+        assert_eq!(p("CAFE"), 1.0 / 65536.0);
+        assert_eq!(p("*BEEF"), 1.0 / 65536.0);
+        assert_eq!(p("C??E"), 1.0 / 256.0);
+    }
+
+    #[test]
+    fn test_probability_multiple_patterns() {
+        let none = NodeHashFilter::default();
+        let p = |text: &str| PatternSet::compile(text).unwrap().match_probability(&none);
+
+        // Disjoint prefixes add up
+        assert_eq!(p("A,B"), 2.0 / 16.0);
+        // A prefix and a suffix overlap: P(A or B) = P(A) + P(B) - P(A and B)
+        assert_eq!(p("A,*B"), 2.0 / 16.0 - 1.0 / 256.0);
+        // A pattern implied by another adds nothing
+        assert_eq!(p("A,AB"), 1.0 / 16.0);
+        // Duplicates don't double count
+        assert_eq!(p("AB,AB"), 1.0 / 256.0);
+    }
+
+    #[test]
+    fn test_probability_with_excluded_hashes() {
+        let mut filter = NodeHashFilter::default();
+        filter.exclude_reserved();
+        let p = |text: &str| {
+            PatternSet::compile(text)
+                .unwrap()
+                .match_probability(&filter)
+        };
+
+        assert_eq!(p(""), 254.0 / 256.0);
+        assert_eq!(p("0"), 15.0 / 256.0);
+        assert_eq!(p("00"), 0.0);
+        assert_eq!(p("*00"), 254.0 / 256.0 / 256.0);
+
+        // Cross-check the exact value against brute force over the first two bytes
+        let patterns = PatternSet::compile("0?,?F,*1").unwrap();
+        let mut matching = 0;
+        for first in 0..=255u8 {
+            for last in 0..16u8 {
+                let mut key = [0u8; 32];
+                key[0] = first;
+                key[31] = last;
+                if filter.allows(first) && patterns.matches(&key) {
+                    matching += 1;
+                }
+            }
+        }
+        assert_eq!(
+            patterns.match_probability(&filter),
+            matching as f64 / (256.0 * 16.0)
+        );
+    }
+}
@@ -1,48 +1,140 @@
-use crate::types::PerformanceResult;
-use anyhow::Result;
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Write};
+use crate::cpu::CpuKeySearcher;
+use crate::output::{Event, output, say, say_inline, say_warning};
+use crate::pattern::PatternSet;
+use crate::platform::PlatformInfo;
+use crate::types::{NodeHashFilter, PerformanceResult, SearchBehavior, SearchConfig, SearchStats};
+use anyhow::{Context, Result};
+use crossbeam::channel;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
 use std::time::{Duration, Instant};
 
+/// Everything a benchmark result depends on. A cached result is only reused when all of it
+/// matches, so a cache copied from another host or left over from an older build is ignored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CacheKey {
+    pub cpu_model: String,
+    pub cpu_flags: Vec<String>,
+    pub cores: usize,
+    pub cpu_quota: Option<f64>,
+    pub version: String,
+    pub backend: String,
+}
+
+impl CacheKey {
+    /// Describes the machine and build we are running on right now.
+    pub fn current(backend: &str) -> Self {
+        let platform = PlatformInfo::detect();
+        Self {
+            cpu_model: platform.cpu_model_name().to_string(),
+            cpu_flags: platform.cpu_flags,
+            cores: platform.cores,
+            cpu_quota: platform.cpu_quota,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            backend: backend.to_string(),
+        }
+    }
+
+    /// Short digest of the key, so different machines sharing a home directory
+    /// (e.g. over NFS) each keep their own cache file.
+    fn file_name(&self) -> String {
+        let digest = Sha256::new()
+            .chain_update(self.cpu_model.as_bytes())
+            .chain_update(self.cpu_flags.join(" ").as_bytes())
+            .chain_update(self.cores.to_le_bytes())
+            .chain_update(self.cpu_quota.unwrap_or_default().to_le_bytes())
+            .chain_update(self.version.as_bytes())
+            .chain_update(self.backend.as_bytes())
+            .finalize();
+        format!(
+            "performance-{}-{}.json",
+            self.backend,
+            hex::encode(&digest[..8])
+        )
+    }
+}
+
+/// On-disk cache entry: the result plus the key it was measured under.
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    key: CacheKey,
+    result: PerformanceResult,
+}
+
 /// Caches performance measurements to disk because key generation benchmarks are expensive
 pub struct PerformanceCache;
 
 impl PerformanceCache {
-    const CACHE_FILE: &'static str = "performance.json";
+    const CACHE_DIR_NAME: &'static str = "meshcore-keygen";
     /// Cache expires after 12 hours because system load and thermal throttling can affect results
     const CACHE_VALIDITY_HOURS: u64 = 12;
 
+    /// Per-user cache directory: `$XDG_CACHE_HOME/meshcore-keygen`, falling back to
+    /// `~/.cache/meshcore-keygen` (or `%LOCALAPPDATA%` on Windows).
+    pub fn cache_dir() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))?;
+        Some(base.join(Self::CACHE_DIR_NAME))
+    }
+
     /// Attempts to load cached performance data to avoid re-running expensive benchmarks.
-    /// Returns None if cache is missing, corrupted, or expired to ensure accuracy.
+    /// Returns None if cache is missing, corrupted, expired or from a different machine or build.
     pub fn load() -> Option<PerformanceResult> {
-        if let Ok(mut file) = File::open(Self::CACHE_FILE) {
-            let mut contents = String::new();
-            if file.read_to_string(&mut contents).is_ok()
-                && let Ok(result) = serde_json::from_str::<PerformanceResult>(&contents)
-            {
-                // Expire cache to ensure measurements reflect current system state
-                let now = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs();
-
-                if now - result.timestamp < Self::CACHE_VALIDITY_HOURS * 3600 {
-                    return Some(result);
-                }
-            }
+        Self::load_from(
+            &Self::cache_dir()?,
+            &CacheKey::current(CpuKeySearcher::BACKEND),
+        )
+    }
+
+    fn load_from(dir: &Path, key: &CacheKey) -> Option<PerformanceResult> {
+        let contents = std::fs::read_to_string(dir.join(key.file_name())).ok()?;
+        let entry = serde_json::from_str::<CacheEntry>(&contents).ok()?;
+
+        // The digest in the file name could collide, and the platform banner catches
+        // changes (e.g. a tightened CPU quota) that the key itself doesn't describe
+        if entry.key != *key || entry.result.platform != get_platform_info() {
+            return None;
         }
-        None
+
+        // Expire cache to ensure measurements reflect current system state
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        (now.saturating_sub(entry.result.timestamp) < Self::CACHE_VALIDITY_HOURS * 3600)
+            .then_some(entry.result)
     }
 
     /// Persists performance results to avoid expensive re-measurement.
     /// Uses atomic write pattern (write + sync) to prevent corruption from crashes.
     pub fn save(result: &PerformanceResult) -> Result<()> {
-        let json = serde_json::to_string_pretty(result)?;
+        let dir = Self::cache_dir().context("No cache directory (HOME is not set)")?;
+        Self::save_to(&dir, &CacheKey::current(CpuKeySearcher::BACKEND), result)
+    }
+
+    fn save_to(dir: &Path, key: &CacheKey, result: &PerformanceResult) -> Result<()> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+
+        let entry = CacheEntry {
+            key: key.clone(),
+            result: result.clone(),
+        };
+        let json = serde_json::to_string_pretty(&entry)?;
         let mut file = OpenOptions::new()
             .create(true)
             .write(true)
             .truncate(true)
-            .open(Self::CACHE_FILE)?;
+            .open(dir.join(key.file_name()))?;
 
         file.write_all(json.as_bytes())?;
         file.sync_all()?;
@@ -52,26 +144,23 @@ impl PerformanceCache {
     /// Runs a multi-threaded performance benchmark to measure key generation speed.
     /// Uses multiple measurement runs to get more stable results.
     pub fn measure_performance(cores: usize) -> Result<PerformanceResult> {
-        println!("\n🚀⚡️ Running performance benchmark on {} cores...", cores);
+        say!("\n🚀⚡️ Running performance benchmark on {} cores...", cores);
 
         const WARMUP_DURATION: Duration = Duration::from_millis(1000);
         const TEST_DURATION: Duration = Duration::from_secs(2);
         const NUM_RUNS: usize = 5;
-        /// Smaller batch size allows more frequent time checks for accurate timing
-        const BATCH_SIZE: usize = 128;
 
         // Warmup run to stabilize CPU frequency and caches
-        println!("   🔥 Warming up CPU cores...");
-        let _warmup = Self::run_single_benchmark(cores, WARMUP_DURATION, BATCH_SIZE);
+        say!("   🔥 Warming up CPU cores...");
+        let _warmup = Self::run_single_benchmark(cores, WARMUP_DURATION);
 
         let mut measurements = Vec::new();
 
         // Run multiple measurements for stability
         for run in 1..=NUM_RUNS {
-            print!("   📊 Measurement run {}/{}...", run, NUM_RUNS);
-            std::io::stdout().flush().unwrap();
-            let result = Self::run_single_benchmark(cores, TEST_DURATION, BATCH_SIZE)?;
-            println!(
+            say_inline!("   📊 Measurement run {}/{}...", run, NUM_RUNS);
+            let result = Self::run_single_benchmark(cores, TEST_DURATION)?;
+            say!(
                 "\r   📊 Measurement run {}/{} ... done! {} keys/sec/core, {} total keys, {} elapsed time",
                 run,
                 NUM_RUNS,
@@ -88,12 +177,13 @@ impl PerformanceCache {
 
         let total_speed = avg_keys_per_sec_per_core * cores as f64;
 
-        println!("✅🎉 Performance benchmark completed:");
-        println!(
+        say!("✅🎉 Performance benchmark completed:");
+        say!(
             "   🚀 Average speed: {:.0} keys/sec across {} cores",
-            total_speed, cores
+            total_speed,
+            cores
         );
-        println!(
+        say!(
             "   ⚡️ Speed per core: {:.0} keys/sec",
             avg_keys_per_sec_per_core
         );
@@ -107,120 +197,172 @@ impl PerformanceCache {
                 .as_secs(),
             platform: get_platform_info(),
         };
+        output().emit(&Event::Benchmark {
+            keys_per_sec_per_core: result.keys_per_sec_per_core,
+            cores,
+            cached: false,
+        });
 
         if let Err(e) = Self::save(&result) {
-            eprintln!("⚠️ Failed to cache performance result: {}", e);
+            say_warning!("⚠️ Failed to cache performance result: {}", e);
         } else {
-            println!("💾✨ Performance result cached for future use!");
+            say!("💾✨ Performance result cached for future use!");
         }
 
         Ok(result)
     }
 
-    /// Runs a single benchmark measurement with the given parameters.
-    fn run_single_benchmark(
-        cores: usize,
-        duration: Duration,
-        batch_size: usize,
-    ) -> Result<(f64, u64, Duration)> {
-        use std::sync::Arc;
-        use std::sync::atomic::{AtomicU64, Ordering};
-        use std::thread;
+    /// Runs the real search workers for `duration` with a pattern that can never match,
+    /// so the measurement covers RNG, key derivation, prefix check and the stats updates
+    /// exactly as a search does. Returns (keys/sec/core, total attempts, elapsed).
+    fn run_single_benchmark(cores: usize, duration: Duration) -> Result<(f64, u64, Duration)> {
+        let stats = Arc::new(SearchStats::new());
+        let (found_sender, _found_receiver) = channel::unbounded();
 
-        let total_keys = Arc::new(AtomicU64::new(0));
         let start_time = Instant::now();
+        let handles = CpuKeySearcher::spawn_workers(
+            cores,
+            Arc::new(never_matching_config(cores)),
+            Arc::clone(&stats),
+            found_sender,
+        );
 
-        // Spawn worker threads to parallelize key generation across cores
-        let handles: Vec<_> = (0..cores)
-            .map(|_| {
-                let total_keys = Arc::clone(&total_keys);
-
-                thread::spawn(move || {
-                    use ed25519_dalek::SigningKey;
-                    use rand::RngCore;
-
-                    let mut rng = rand::thread_rng();
-                    let mut local_count = 0u64;
-                    let end_time = start_time + duration;
-
-                    // Pre-generate seeds to reduce RNG overhead during measurement
-                    let mut seeds = Vec::with_capacity(batch_size);
-                    for _ in 0..batch_size {
-                        let mut seed = [0u8; 32];
-                        rng.fill_bytes(&mut seed);
-                        seeds.push(seed);
-                    }
-
-                    while Instant::now() < end_time {
-                        for seed in &seeds {
-                            // Check time more frequently to avoid running over the limit
-                            if Instant::now() >= end_time {
-                                break;
-                            }
-
-                            // This is the expensive operation we're measuring
-                            let signing_key = SigningKey::from_bytes(seed);
-                            let _verifying_key = signing_key.verifying_key();
-
-                            local_count += 1;
-                        }
-                    }
-
-                    total_keys.fetch_add(local_count, Ordering::Relaxed);
-                })
-            })
-            .collect();
-
-        // Wait for all threads to complete benchmark
+        std::thread::sleep(duration);
+        stats.stop_search.store(true, Ordering::Relaxed);
         for handle in handles {
             handle.join().unwrap();
         }
 
         let elapsed = start_time.elapsed();
-        let total_keys_generated = total_keys.load(Ordering::Relaxed);
+        // Workers flush their local counters on exit, so this is exact
+        let total_attempts = stats.total_attempts.load(Ordering::Relaxed);
 
         // Ensure we have meaningful measurements
-        if total_keys_generated == 0 || elapsed.as_secs_f64() < 0.1 {
+        if total_attempts == 0 || elapsed.as_secs_f64() < 0.1 {
             return Err(anyhow::anyhow!("Benchmark produced insufficient data"));
         }
 
-        let keys_per_sec = total_keys_generated as f64 / elapsed.as_secs_f64();
+        let keys_per_sec = total_attempts as f64 / elapsed.as_secs_f64();
         let keys_per_sec_per_core = keys_per_sec / cores as f64;
 
-        Ok((keys_per_sec_per_core, total_keys_generated, elapsed))
+        Ok((keys_per_sec_per_core, total_attempts, elapsed))
+    }
+}
+
+/// A full-length prefix with every node hash excluded: workers do all the per-attempt
+/// work of a search, but nothing is ever reported or written to the key store. Each run
+/// spawns fresh workers, so their banners are off to keep the benchmark output readable.
+fn never_matching_config(cores: usize) -> SearchConfig {
+    let mut excluded_hashes = NodeHashFilter::default();
+    (0..=u8::MAX).for_each(|hash| excluded_hashes.exclude(hash));
+
+    SearchConfig {
+        excluded_hashes,
+        silent_workers: true,
+        ..SearchConfig::new("0".repeat(64), SearchBehavior::Continuous, cores)
     }
 }
 
 /// Collects system information to identify when cached performance data might not apply.
 /// Platform changes (CPU, thermal state) can significantly affect key generation speed.
-fn get_platform_info() -> String {
-    // Get basic platform information without external dependencies
-    let cpu_count = std::thread::available_parallelism()
-        .map(|p| p.get())
-        .unwrap_or(1);
+pub fn get_platform_info() -> String {
+    PlatformInfo::detect().summary()
+}
 
-    format!("{} - {} cores", std::env::consts::ARCH, cpu_count)
+/// Runs the `benchmark` subcommand: shows the cached result unless `force` is set
+/// or the cache doesn't apply, in which case a fresh benchmark is run and cached.
+pub fn run_benchmark(cores: usize, force: bool) -> Result<()> {
+    if !force && let Some(cached) = PerformanceCache::load() {
+        output().emit(&Event::Benchmark {
+            keys_per_sec_per_core: cached.keys_per_sec_per_core,
+            cores: cached.cores_used,
+            cached: true,
+        });
+        say!("\n📈✨ Using cached performance data (use --force to re-measure):");
+        say!(
+            "   ⚡️ Speed per core: {:.0} keys/sec",
+            cached.keys_per_sec_per_core
+        );
+        say!(
+            "   🚀 Total speed: {:.0} keys/sec across {} cores",
+            cached.keys_per_sec_per_core * cores as f64,
+            cores
+        );
+        say!("   🖥️ CPU: {}", PlatformInfo::detect().banner());
+        return Ok(());
+    }
+
+    PerformanceCache::measure_performance(cores)?;
+    if let Some(dir) = PerformanceCache::cache_dir() {
+        say!("   📁 Cache directory: {}", dir.display());
+    }
+    Ok(())
 }
 
-/// Estimates search time for vanity address generation using a simple theoretical calculation.
-/// Returns the expected search duration in seconds for finding a matching prefix.
-/// NOTE: This is the AVERAGE case - actual time can vary significantly!
-pub fn estimate_search_time(prefix_length: usize, keys_per_sec: f64) -> f64 {
-    // Handle edge cases
-    if keys_per_sec <= 0.0 || keys_per_sec.is_nan() || keys_per_sec.is_infinite() {
-        return f64::INFINITY;
+/// Confidence levels reported alongside the average search time.
+pub const REPORTED_PERCENTILES: [f64; 3] = [0.50, 0.90, 0.99];
+
+/// How long a search should take, from the exact chance that one attempt matches.
+/// Every attempt is an independent draw, so the number of attempts until the first match is
+/// geometrically distributed: the mean is 1/p, and the attempts needed to succeed with
+/// confidence q are ln(1-q) / ln(1-p).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchEstimate {
+    pub probability: f64,
+    pub expected_attempts: f64,
+}
+
+impl SearchEstimate {
+    pub fn from_probability(probability: f64) -> Self {
+        Self {
+            probability,
+            expected_attempts: if probability > 0.0 {
+                1.0 / probability
+            } else {
+                f64::INFINITY
+            },
+        }
     }
 
-    // Simple theoretical calculation based on combinatorics
-    let prefix_combinations = 16_f64.powi(prefix_length as i32);
+    /// Estimate for a compiled pattern set, counting only keys with an allowed node hash.
+    pub fn for_patterns(patterns: &PatternSet, excluded_hashes: &NodeHashFilter) -> Self {
+        Self::from_probability(patterns.match_probability(excluded_hashes))
+    }
 
-    // Apply a real-world performance penalty factor of ~15% to account for:
-    // - Prefix checking overhead
-    // - Thread coordination overhead
-    // - Memory allocations and I/O
-    let real_world_keys_per_sec = keys_per_sec * 0.85;
+    /// Attempts after which a match has been found with the given confidence (0..1).
+    pub fn attempts_for_confidence(&self, confidence: f64) -> f64 {
+        if self.probability <= 0.0 {
+            return f64::INFINITY;
+        }
+        if self.probability >= 1.0 {
+            return 1.0;
+        }
+        // ln_1p keeps precision when p is tiny, which is every interesting pattern
+        (-confidence).ln_1p() / (-self.probability).ln_1p()
+    }
+
+    /// Chance that at least one match turns up within this many attempts.
+    pub fn probability_within(&self, attempts: f64) -> f64 {
+        if self.probability >= 1.0 {
+            return if attempts >= 1.0 { 1.0 } else { 0.0 };
+        }
+        -(attempts * (-self.probability).ln_1p()).exp_m1()
+    }
+
+    pub fn expected_time(&self, keys_per_sec: f64) -> f64 {
+        attempts_to_seconds(self.expected_attempts, keys_per_sec)
+    }
 
-    prefix_combinations / real_world_keys_per_sec
+    pub fn time_for_confidence(&self, confidence: f64, keys_per_sec: f64) -> f64 {
+        attempts_to_seconds(self.attempts_for_confidence(confidence), keys_per_sec)
+    }
+}
+
+fn attempts_to_seconds(attempts: f64, keys_per_sec: f64) -> f64 {
+    if keys_per_sec <= 0.0 || keys_per_sec.is_nan() || keys_per_sec.is_infinite() {
+        return f64::INFINITY;
+    }
+    attempts / keys_per_sec
 }
 
 #[cfg(test)]
@@ -229,6 +371,14 @@ mod tests {
     #[cfg(test)]
     use tempfile::NamedTempFile;
 
+    /// Estimate for a plain hex prefix of `prefix_length` digits, with no hash filter.
+    fn prefix_estimate(prefix_length: usize) -> SearchEstimate {
+        SearchEstimate::for_patterns(
+            &PatternSet::compile(&"A".repeat(prefix_length)).unwrap(),
+            &NodeHashFilter::default(),
+        )
+    }
+
     #[test]
     fn test_performance_result_creation() {
         let result = PerformanceResult {
@@ -293,6 +443,93 @@ mod tests {
         assert!(json.contains("1500"));
     }
 
+    fn test_key() -> CacheKey {
+        CacheKey {
+            cpu_model: "Test CPU".to_string(),
+            cpu_flags: vec!["avx2".to_string()],
+            cores: 4,
+            cpu_quota: None,
+            version: "0.1.0".to_string(),
+            backend: "cpu".to_string(),
+        }
+    }
+
+    fn fresh_result() -> PerformanceResult {
+        PerformanceResult {
+            keys_per_sec_per_core: 1500.0,
+            cores_used: 4,
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            platform: get_platform_info(),
+        }
+    }
+
+    #[test]
+    fn test_performance_cache_round_trip_in_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join("meshcore-keygen");
+
+        PerformanceCache::save_to(&cache_dir, &test_key(), &fresh_result()).unwrap();
+        let loaded = PerformanceCache::load_from(&cache_dir, &test_key()).unwrap();
+        assert_eq!(loaded, fresh_result());
+    }
+
+    #[test]
+    fn test_performance_cache_rejects_other_machines_and_builds() {
+        let dir = tempfile::tempdir().unwrap();
+        PerformanceCache::save_to(dir.path(), &test_key(), &fresh_result()).unwrap();
+
+        let mut other_cpu = test_key();
+        other_cpu.cpu_model = "Other CPU".to_string();
+        assert!(PerformanceCache::load_from(dir.path(), &other_cpu).is_none());
+
+        let mut other_quota = test_key();
+        other_quota.cpu_quota = Some(1.5);
+        assert!(PerformanceCache::load_from(dir.path(), &other_quota).is_none());
+
+        let mut other_version = test_key();
+        other_version.version = "9.9.9".to_string();
+        assert!(PerformanceCache::load_from(dir.path(), &other_version).is_none());
+
+        // A cache file copied over from a host with a different platform banner is ignored
+        let mut copied = fresh_result();
+        copied.platform = "Some other host - 128 cores".to_string();
+        PerformanceCache::save_to(dir.path(), &test_key(), &copied).unwrap();
+        assert!(PerformanceCache::load_from(dir.path(), &test_key()).is_none());
+
+        // Expired results are ignored too
+        let mut expired = fresh_result();
+        expired.timestamp -= 13 * 3600;
+        PerformanceCache::save_to(dir.path(), &test_key(), &expired).unwrap();
+        assert!(PerformanceCache::load_from(dir.path(), &test_key()).is_none());
+    }
+
+    #[test]
+    fn test_benchmark_runs_search_loop_without_finding_keys() {
+        let (per_core, attempts, elapsed) =
+            PerformanceCache::run_single_benchmark(2, Duration::from_millis(300)).unwrap();
+
+        assert!(attempts > 0);
+        assert!(elapsed >= Duration::from_millis(300));
+        assert!((per_core * 2.0 * elapsed.as_secs_f64() - attempts as f64).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_prefix_expected_time_is_uncorrected() {
+        // 16^2 combinations at 256 keys/sec is exactly one second on average
+        assert_eq!(prefix_estimate(2).expected_time(256.0), 1.0);
+    }
+
+    #[test]
+    fn test_cache_file_name_depends_on_key() {
+        let mut other = test_key();
+        other.cores = 8;
+        assert_ne!(test_key().file_name(), other.file_name());
+        assert!(test_key().file_name().starts_with("performance-cpu-"));
+    }
+
     #[test]
     fn test_performance_cache_load_nonexistent() {
         // Loading from a non-existent file should return None
@@ -332,27 +569,27 @@ mod tests {
     }
 
     #[test]
-    fn test_estimate_search_time() {
+    fn test_prefix_expected_time() {
         let keys_per_sec = 10000.0;
 
         // Test short prefix (1 character = 4 bits)
-        let prefix_time = estimate_search_time(1, keys_per_sec);
+        let prefix_time = prefix_estimate(1).expected_time(keys_per_sec);
         assert!(prefix_time > 0.0);
         assert!(prefix_time < 1000.0); // Should be reasonable for 1 char
 
         // Test longer prefix (4 characters = 16 bits)
-        let prefix_time_4 = estimate_search_time(4, keys_per_sec);
+        let prefix_time_4 = prefix_estimate(4).expected_time(keys_per_sec);
         assert!(prefix_time_4 > prefix_time); // Longer prefix takes more time
     }
 
     #[test]
-    fn test_estimate_search_time_edge_cases() {
+    fn test_prefix_expected_time_edge_cases() {
         // Test with very high performance
-        let prefix_time = estimate_search_time(2, 1_000_000.0);
+        let prefix_time = prefix_estimate(2).expected_time(1_000_000.0);
         assert!(prefix_time > 0.0);
 
         // Test with low performance
-        let prefix_time = estimate_search_time(3, 100.0);
+        let prefix_time = prefix_estimate(3).expected_time(100.0);
         assert!(prefix_time > 0.0);
     }
 
@@ -370,9 +607,9 @@ mod tests {
         // Test that longer patterns take progressively more time
         let base_rate = 10000.0;
 
-        let time_3 = estimate_search_time(3, base_rate);
-        let time_4 = estimate_search_time(4, base_rate);
-        let time_5 = estimate_search_time(5, base_rate);
+        let time_3 = prefix_estimate(3).expected_time(base_rate);
+        let time_4 = prefix_estimate(4).expected_time(base_rate);
+        let time_5 = prefix_estimate(5).expected_time(base_rate);
 
         // Longer patterns should take progressively more time due to exponential scaling
         assert!(time_4 > time_3);
@@ -440,29 +677,29 @@ mod tests {
     }
 
     #[test]
-    fn test_estimate_search_time_extreme_cases() {
+    fn test_prefix_expected_time_extreme_cases() {
         // Test with zero performance
-        let prefix_time = estimate_search_time(1, 0.0);
+        let prefix_time = prefix_estimate(1).expected_time(0.0);
         assert!(prefix_time.is_infinite());
 
         // Test with very high performance
-        let prefix_time = estimate_search_time(1, f64::MAX);
+        let prefix_time = prefix_estimate(1).expected_time(f64::MAX);
         assert!(prefix_time >= 0.0);
 
         // Test with negative performance (should handle gracefully)
-        let prefix_time = estimate_search_time(1, -1000.0);
+        let prefix_time = prefix_estimate(1).expected_time(-1000.0);
         assert!(prefix_time.is_infinite() || prefix_time.is_nan());
     }
 
     #[test]
-    fn test_estimate_search_time_prefix_length_scaling() {
+    fn test_prefix_expected_time_prefix_length_scaling() {
         let keys_per_sec = 10000.0;
 
         // Test that longer prefixes take exponentially more time
         let mut prev_prefix_time = 0.0;
 
         for length in 1..=6 {
-            let prefix_time = estimate_search_time(length, keys_per_sec);
+            let prefix_time = prefix_estimate(length).expected_time(keys_per_sec);
 
             if length > 1 {
                 // Each additional character should significantly increase time
@@ -529,7 +766,7 @@ mod tests {
 
         // Test that performance scaling is applied correctly
         for length in 1..=8 {
-            let prefix_time = estimate_search_time(length, base_performance);
+            let prefix_time = prefix_estimate(length).expected_time(base_performance);
 
             // Verify that times make sense (longer patterns take more time)
             assert!(prefix_time > 0.0);
@@ -547,8 +784,8 @@ mod tests {
         let slow_system = 100.0; // 100 keys/sec
         let fast_system = 100000.0; // 100k keys/sec
 
-        let slow_prefix = estimate_search_time(3, slow_system);
-        let fast_prefix = estimate_search_time(3, fast_system);
+        let slow_prefix = prefix_estimate(3).expected_time(slow_system);
+        let fast_prefix = prefix_estimate(3).expected_time(fast_system);
 
         // Faster system should take less time
         assert!(fast_prefix < slow_prefix);
@@ -561,4 +798,64 @@ mod tests {
         assert!(time_ratio > speed_ratio * 0.5);
         assert!(time_ratio < speed_ratio * 2.0);
     }
+
+    #[test]
+    fn test_search_estimate_percentiles() {
+        let estimate = SearchEstimate::from_probability(1.0 / 65536.0);
+        assert_eq!(estimate.expected_attempts, 65536.0);
+
+        // The median of a geometric distribution is about ln(2)/p, P90 ln(10)/p, P99 ln(100)/p
+        let p50 = estimate.attempts_for_confidence(0.50);
+        let p90 = estimate.attempts_for_confidence(0.90);
+        let p99 = estimate.attempts_for_confidence(0.99);
+        assert!((p50 / 65536.0 - std::f64::consts::LN_2).abs() < 1e-4);
+        assert!((p90 / 65536.0 - std::f64::consts::LN_10).abs() < 1e-4);
+        assert!((p99 / 65536.0 - 100f64.ln()).abs() < 1e-4);
+
+        // Round trip between attempts and cumulative probability
+        assert!((estimate.probability_within(p90) - 0.90).abs() < 1e-9);
+        assert_eq!(estimate.probability_within(0.0), 0.0);
+
+        assert_eq!(estimate.time_for_confidence(0.5, 0.0), f64::INFINITY);
+        assert!((estimate.expected_time(65536.0) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_search_estimate_edge_probabilities() {
+        let impossible = SearchEstimate::from_probability(0.0);
+        assert_eq!(impossible.expected_attempts, f64::INFINITY);
+        assert_eq!(impossible.attempts_for_confidence(0.9), f64::INFINITY);
+        assert_eq!(impossible.probability_within(1e12), 0.0);
+
+        let certain = SearchEstimate::from_probability(1.0);
+        assert_eq!(certain.expected_attempts, 1.0);
+        assert_eq!(certain.attempts_for_confidence(0.99), 1.0);
+        assert_eq!(certain.probability_within(1.0), 1.0);
+    }
+
+    #[test]
+    fn test_search_estimate_for_pattern_sets() {
+        let mut reserved = NodeHashFilter::default();
+        reserved.exclude_reserved();
+
+        // Two disjoint prefixes halve the expected work of one
+        let one = SearchEstimate::for_patterns(
+            &PatternSet::compile("CAFE").unwrap(),
+            &NodeHashFilter::default(),
+        );
+        let two = SearchEstimate::for_patterns(
+            &PatternSet::compile("CAFE,BEEF").unwrap(),
+            &NodeHashFilter::default(),
+        );
+        assert_eq!(one.expected_attempts, 2.0 * two.expected_attempts);
+
+        // A prefix on a reserved hash can never match
+        let blocked = SearchEstimate::for_patterns(&PatternSet::compile("FF").unwrap(), &reserved);
+        assert_eq!(blocked.expected_attempts, f64::INFINITY);
+
+        // Reserved hashes make a suffix slightly harder than 16^n
+        let suffix =
+            SearchEstimate::for_patterns(&PatternSet::compile("*BEEF").unwrap(), &reserved);
+        assert!((suffix.expected_attempts - 65536.0 * 256.0 / 254.0).abs() < 1e-6);
+    }
 }
@@ -0,0 +1,216 @@
+use std::fmt::Write as _;
+
+/// CPU features that change how fast Ed25519 key generation runs. curve25519-dalek picks
+/// its AVX2/AVX-512 backend at runtime, and ADX/BMI2 speed up the field arithmetic.
+const RELEVANT_CPU_FLAGS: [&str; 5] = ["avx2", "avx512f", "avx512ifma", "adx", "bmi2"];
+
+/// What the benchmark ran on, detailed enough that a cached result from a different
+/// machine (or the same machine under a tighter container quota) isn't trusted.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PlatformInfo {
+    pub arch: String,
+    pub cpu_model: Option<String>,
+    pub cpu_mhz: Option<f64>,
+    pub cpu_flags: Vec<String>,
+    pub cores: usize,
+    /// CPUs granted by a cgroup quota, e.g. 2.5 for `250000 100000` in `cpu.max`.
+    pub cpu_quota: Option<f64>,
+}
+
+impl PlatformInfo {
+    /// Reads `/proc/cpuinfo` and the cgroup CPU limits of the current process.
+    /// Everything but the architecture and core count is optional, so non-Linux
+    /// platforms simply report less detail.
+    pub fn detect() -> Self {
+        let mut info = std::fs::read_to_string("/proc/cpuinfo")
+            .map(|cpuinfo| parse_cpuinfo(&cpuinfo))
+            .unwrap_or_default();
+
+        info.arch = std::env::consts::ARCH.to_string();
+        // available_parallelism already honours affinity masks and cgroup quotas
+        info.cores = std::thread::available_parallelism()
+            .map(|p| p.get())
+            .unwrap_or(1);
+        info.cpu_quota = read_cgroup_cpu_quota();
+        info
+    }
+
+    pub fn cpu_model_name(&self) -> &str {
+        self.cpu_model.as_deref().unwrap_or(&self.arch)
+    }
+
+    /// Stable one-line description used to validate cached benchmarks.
+    /// The clock speed is left out: `cpu MHz` follows frequency scaling and would
+    /// invalidate the cache on every run.
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{} ({}", self.cpu_model_name(), self.arch);
+        for flag in &self.cpu_flags {
+            let _ = write!(summary, " {}", flag);
+        }
+        let _ = write!(summary, ") - {} cores", self.cores);
+        if let Some(quota) = self.cpu_quota {
+            let _ = write!(summary, ", quota {:.2} CPUs", quota);
+        }
+        summary
+    }
+
+    /// Human-readable description for the performance banner, including the clock speed.
+    pub fn banner(&self) -> String {
+        let mut banner = self.cpu_model_name().to_string();
+        if let Some(mhz) = self.cpu_mhz {
+            let _ = write!(banner, " @ {:.0} MHz", mhz);
+        }
+        let _ = write!(banner, ", {} cores", self.cores);
+        if let Some(quota) = self.cpu_quota {
+            let _ = write!(banner, " (cgroup quota {:.2} CPUs)", quota);
+        }
+        if self.cpu_flags.is_empty() {
+            banner.push_str(", no AVX2/ADX");
+        } else {
+            let _ = write!(banner, ", features: {}", self.cpu_flags.join(" "));
+        }
+        banner
+    }
+}
+
+/// Extracts model name, clock and relevant flags from the first processor entry.
+/// ARM kernels report no model name, so `CPU part` style fields are not guessed at.
+pub fn parse_cpuinfo(cpuinfo: &str) -> PlatformInfo {
+    let mut info = PlatformInfo::default();
+
+    for line in cpuinfo.lines() {
+        // Only the first processor block is needed, all cores are identical for our purposes
+        if line.trim().is_empty() && info.cpu_model.is_some() {
+            break;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+
+        match key.trim() {
+            "model name" if info.cpu_model.is_none() => info.cpu_model = Some(value.to_string()),
+            "cpu MHz" if info.cpu_mhz.is_none() => info.cpu_mhz = value.parse().ok(),
+            "flags" | "Features" if info.cpu_flags.is_empty() => {
+                let flags: Vec<&str> = value.split_whitespace().collect();
+                info.cpu_flags = RELEVANT_CPU_FLAGS
+                    .iter()
+                    .filter(|flag| flags.contains(flag))
+                    .map(|flag| flag.to_string())
+                    .collect();
+            }
+            _ => {}
+        }
+    }
+
+    info
+}
+
+/// Parses cgroup v2 `cpu.max` ("max 100000" or "250000 100000") into a CPU count.
+pub fn parse_cgroup_v2_cpu_max(cpu_max: &str) -> Option<f64> {
+    let mut fields = cpu_max.split_whitespace();
+    let quota = fields.next()?;
+    let period: f64 = fields.next()?.parse().ok()?;
+    if quota == "max" || period <= 0.0 {
+        return None;
+    }
+    Some(quota.parse::<f64>().ok()? / period)
+}
+
+/// Parses cgroup v1 `cpu.cfs_quota_us` / `cpu.cfs_period_us`; a quota of -1 means unlimited.
+pub fn parse_cgroup_v1_quota(quota: &str, period: &str) -> Option<f64> {
+    let quota: i64 = quota.trim().parse().ok()?;
+    let period: i64 = period.trim().parse().ok()?;
+    (quota > 0 && period > 0).then(|| quota as f64 / period as f64)
+}
+
+/// Finds the CPU quota for this process under cgroup v2 (its own group, then the root
+/// as seen from inside a container) or cgroup v1.
+fn read_cgroup_cpu_quota() -> Option<f64> {
+    let read = |path: &str| std::fs::read_to_string(path).ok();
+
+    let own_group = read("/proc/self/cgroup").and_then(|cgroups| {
+        cgroups
+            .lines()
+            .find_map(|line| line.strip_prefix("0::").map(str::to_string))
+    });
+    if let Some(group) = own_group
+        && let Some(cpu_max) = read(&format!("/sys/fs/cgroup{}/cpu.max", group.trim_end()))
+    {
+        return parse_cgroup_v2_cpu_max(&cpu_max);
+    }
+    if let Some(cpu_max) = read("/sys/fs/cgroup/cpu.max") {
+        return parse_cgroup_v2_cpu_max(&cpu_max);
+    }
+
+    ["/sys/fs/cgroup/cpu", "/sys/fs/cgroup/cpu,cpuacct"]
+        .iter()
+        .find_map(|dir| {
+            parse_cgroup_v1_quota(
+                &read(&format!("{}/cpu.cfs_quota_us", dir))?,
+                &read(&format!("{}/cpu.cfs_period_us", dir))?,
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const X86_CPUINFO: &str = "processor\t: 0\n\
+        vendor_id\t: AuthenticAMD\n\
+        model name\t: AMD Ryzen 9 5900X 12-Core Processor\n\
+        cpu MHz\t\t: 3700.123\n\
+        flags\t\t: fpu sse2 avx avx2 bmi2 adx sha_ni\n\
+        \n\
+        processor\t: 1\n\
+        model name\t: AMD Ryzen 9 5900X 12-Core Processor\n\
+        cpu MHz\t\t: 2200.000\n";
+
+    #[test]
+    fn test_parse_cpuinfo_x86() {
+        let info = parse_cpuinfo(X86_CPUINFO);
+        assert_eq!(
+            info.cpu_model.as_deref(),
+            Some("AMD Ryzen 9 5900X 12-Core Processor")
+        );
+        assert_eq!(info.cpu_mhz, Some(3700.123));
+        assert_eq!(info.cpu_flags, vec!["avx2", "adx", "bmi2"]);
+    }
+
+    #[test]
+    fn test_parse_cpuinfo_arm_has_no_model() {
+        let info =
+            parse_cpuinfo("processor\t: 0\nFeatures\t: fp asimd aes sha2\nCPU part\t: 0xd08\n");
+        assert_eq!(info.cpu_model, None);
+        assert!(info.cpu_flags.is_empty());
+    }
+
+    #[test]
+    fn test_parse_cgroup_quotas() {
+        assert_eq!(parse_cgroup_v2_cpu_max("250000 100000\n"), Some(2.5));
+        assert_eq!(parse_cgroup_v2_cpu_max("max 100000\n"), None);
+        assert_eq!(parse_cgroup_v2_cpu_max(""), None);
+
+        assert_eq!(parse_cgroup_v1_quota("50000\n", "100000\n"), Some(0.5));
+        assert_eq!(parse_cgroup_v1_quota("-1\n", "100000\n"), None);
+    }
+
+    #[test]
+    fn test_summary_is_stable_across_clock_changes() {
+        let mut info = parse_cpuinfo(X86_CPUINFO);
+        info.arch = "x86_64".to_string();
+        info.cores = 12;
+        info.cpu_quota = Some(4.0);
+
+        let summary = info.summary();
+        assert_eq!(
+            summary,
+            "AMD Ryzen 9 5900X 12-Core Processor (x86_64 avx2 adx bmi2) - 12 cores, quota 4.00 CPUs"
+        );
+
+        info.cpu_mhz = Some(4950.0);
+        assert_eq!(info.summary(), summary);
+        assert!(info.banner().contains("@ 4950 MHz"));
+    }
+}
@@ -0,0 +1,318 @@
+use crate::secure::write_key_file;
+use crate::types::FoundKey;
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Node names are stored in a 32-byte buffer on the device, including the terminator.
+const MAX_NODE_NAME_BYTES: usize = 31;
+
+/// Left in the script for the operator to fill in; passwords never come from this tool.
+const ADMIN_PASSWORD_PLACEHOLDER: &str = "<ADMIN_PASSWORD>";
+
+/// Used when no `--name-template` is given.
+pub const DEFAULT_NAME_TEMPLATE: &str = "Repeater-{hash}";
+
+/// Radio settings applied by the provisioning script, read from a `key = value` profile.
+/// Everything is optional, but the four LoRa parameters must come together because
+/// the CLI sets them with a single `set radio` command.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RadioProfile {
+    pub radio: Option<(f64, f64, u8, u8)>,
+    pub tx_power: Option<i8>,
+    pub location: Option<(f64, f64)>,
+}
+
+impl RadioProfile {
+    /// Parses a profile such as:
+    ///
+    /// ```text
+    /// # EU narrow
+    /// freq = 869.618
+    /// bw = 62.5
+    /// sf = 8
+    /// cr = 8
+    /// tx = 22
+    /// ```
+    pub fn parse(contents: &str) -> Result<Self> {
+        let (mut freq, mut bw, mut sf, mut cr) = (None, None, None, None);
+        let (mut lat, mut lon) = (None, None);
+        let mut profile = RadioProfile::default();
+
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .with_context(|| format!("Line {}: expected key = value", line_number + 1))?;
+            let value = value.trim();
+            let context = || format!("Line {}: invalid {}", line_number + 1, key.trim());
+
+            match key.trim() {
+                "freq" => freq = Some(value.parse::<f64>().with_context(context)?),
+                "bw" => bw = Some(value.parse::<f64>().with_context(context)?),
+                "sf" => sf = Some(value.parse::<u8>().with_context(context)?),
+                "cr" => cr = Some(value.parse::<u8>().with_context(context)?),
+                "tx" => profile.tx_power = Some(value.parse::<i8>().with_context(context)?),
+                "lat" => lat = Some(value.parse::<f64>().with_context(context)?),
+                "lon" => lon = Some(value.parse::<f64>().with_context(context)?),
+                other => anyhow::bail!("Line {}: unknown setting '{}'", line_number + 1, other),
+            }
+        }
+
+        profile.radio = match (freq, bw, sf, cr) {
+            (Some(freq), Some(bw), Some(sf), Some(cr)) => {
+                if !(5..=12).contains(&sf) {
+                    anyhow::bail!("Spreading factor must be between 5 and 12, got {}", sf);
+                }
+                if !(5..=8).contains(&cr) {
+                    anyhow::bail!("Coding rate must be between 5 and 8, got {}", cr);
+                }
+                Some((freq, bw, sf, cr))
+            }
+            (None, None, None, None) => None,
+            _ => anyhow::bail!("freq, bw, sf and cr must be given together"),
+        };
+
+        profile.location = match (lat, lon) {
+            (Some(lat), Some(lon)) => Some((lat, lon)),
+            (None, None) => None,
+            _ => anyhow::bail!("lat and lon must be given together"),
+        };
+
+        Ok(profile)
+    }
+}
+
+/// Expands `{...}` placeholders in a name template from a found key:
+/// `{public}` (full public key), `{public:N}` (first N hex characters),
+/// `{hash}` (node hash, i.e. the first byte) and `{n}` (1-based position in this run).
+/// The private key and seed are deliberately not available.
+pub fn render_name_template(template: &str, key: &FoundKey, index: usize) -> Result<String> {
+    let mut rendered = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .with_context(|| format!("Unclosed placeholder in name template '{}'", template))?
+            + start;
+
+        let placeholder = &rest[start + 1..end];
+        match placeholder.split_once(':') {
+            Some(("public", length)) => {
+                let length: usize = length
+                    .parse()
+                    .with_context(|| format!("Invalid length in {{{}}}", placeholder))?;
+                rendered.push_str(&key.public_key[..length.min(key.public_key.len())]);
+            }
+            None if placeholder == "public" => rendered.push_str(&key.public_key),
+            None if placeholder == "hash" => rendered.push_str(&key.public_key[..2]),
+            None if placeholder == "n" => write!(rendered, "{}", index)?,
+            _ => anyhow::bail!(
+                "Unknown placeholder {{{}}} (expected public, public:N, hash or n)",
+                placeholder
+            ),
+        }
+
+        rest = &rest[end + 1..];
+    }
+    rendered.push_str(rest);
+
+    if rendered.len() > MAX_NODE_NAME_BYTES {
+        anyhow::bail!(
+            "Node name '{}' is {} bytes, the device allows at most {}",
+            rendered,
+            rendered.len(),
+            MAX_NODE_NAME_BYTES
+        );
+    }
+    Ok(rendered)
+}
+
+/// Builds the repeater/room server CLI commands that install a key and its settings.
+/// One command per line, nothing else, so the whole file can be pasted into the console.
+pub fn build_cli_script(key: &FoundKey, name: &str, profile: &RadioProfile) -> Zeroizing<String> {
+    let mut script = Zeroizing::new(String::new());
+
+    // Writing to a String can't fail
+    let _ = writeln!(script, "set prv.key {}", key.private_key.expose());
+    let _ = writeln!(script, "set name {}", name);
+    if let Some((freq, bw, sf, cr)) = profile.radio {
+        let _ = writeln!(script, "set radio {},{},{},{}", freq, bw, sf, cr);
+    }
+    if let Some(tx_power) = profile.tx_power {
+        let _ = writeln!(script, "set tx {}", tx_power);
+    }
+    if let Some((lat, lon)) = profile.location {
+        let _ = writeln!(script, "set lat {}", lat);
+        let _ = writeln!(script, "set lon {}", lon);
+    }
+    let _ = writeln!(script, "password {}", ADMIN_PASSWORD_PLACEHOLDER);
+    script.push_str("reboot\n");
+
+    script
+}
+
+/// Where and how to write a provisioning script for every found key.
+#[derive(Debug, Clone)]
+pub struct CliScriptOptions {
+    pub output_dir: PathBuf,
+    pub name_template: String,
+    pub profile: RadioProfile,
+}
+
+impl CliScriptOptions {
+    /// Reads the radio profile and checks the template up front, so a typo is reported
+    /// before the search runs rather than after the first key is found.
+    pub fn new(
+        output_dir: &str,
+        name_template: Option<&str>,
+        profile_path: Option<&str>,
+    ) -> Result<Self> {
+        let name_template = name_template.unwrap_or(DEFAULT_NAME_TEMPLATE).to_string();
+        let probe = FoundKey {
+            private_key: crate::secure::SecureString::new(String::new()),
+            public_key: "0".repeat(64),
+            seed: None,
+        };
+        render_name_template(&name_template, &probe, 1)?;
+
+        let profile = match profile_path {
+            Some(path) => RadioProfile::parse(
+                &std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read {}", path))?,
+            )
+            .with_context(|| format!("Invalid radio profile {}", path))?,
+            None => RadioProfile::default(),
+        };
+
+        Ok(Self {
+            output_dir: PathBuf::from(output_dir),
+            name_template,
+            profile,
+        })
+    }
+
+    /// Writes `<public key>-cli.txt` into the output directory, owner-readable only because
+    /// the script contains the private key. The whole public key goes in the name: with a
+    /// long pattern every key of a run shares its first digits.
+    pub fn write_for(&self, key: &FoundKey, index: usize) -> Result<PathBuf> {
+        let name = render_name_template(&self.name_template, key, index)?;
+        let script = build_cli_script(key, &name, &self.profile);
+
+        std::fs::create_dir_all(&self.output_dir)
+            .with_context(|| format!("Failed to create {}", self.output_dir.display()))?;
+        let path = self.output_dir.join(format!("{}-cli.txt", key.public_key));
+        write_key_file(path_str(&path)?, script.as_bytes(), true)?;
+        Ok(path)
+    }
+}
+
+fn path_str(path: &Path) -> Result<&str> {
+    path.to_str()
+        .with_context(|| format!("Path is not valid UTF-8: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secure::SecureString;
+
+    fn test_key() -> FoundKey {
+        FoundKey {
+            private_key: SecureString::new("AB".repeat(64)),
+            public_key: "C0FFEE".to_string() + &"11".repeat(29),
+            seed: None,
+        }
+    }
+
+    #[test]
+    fn test_render_name_template() {
+        let key = test_key();
+        assert_eq!(
+            render_name_template("Hill-{hash}-{n}", &key, 3).unwrap(),
+            "Hill-C0-3"
+        );
+        assert_eq!(
+            render_name_template("{public:6}", &key, 1).unwrap(),
+            "C0FFEE"
+        );
+        assert!(render_name_template("{private}", &key, 1).is_err());
+        assert!(render_name_template("{hash", &key, 1).is_err());
+        assert!(
+            render_name_template("{public}", &key, 1).is_err(),
+            "too long"
+        );
+    }
+
+    #[test]
+    fn test_radio_profile_parsing() {
+        let profile =
+            RadioProfile::parse("# EU\nfreq = 869.618\nbw=62.5\nsf = 8\ncr = 8\ntx = 22\n")
+                .unwrap();
+        assert_eq!(profile.radio, Some((869.618, 62.5, 8, 8)));
+        assert_eq!(profile.tx_power, Some(22));
+        assert_eq!(profile.location, None);
+
+        assert!(RadioProfile::parse("freq = 869.618\n").is_err());
+        assert!(RadioProfile::parse("freq=1\nbw=1\nsf=13\ncr=5").is_err());
+        assert!(RadioProfile::parse("power = 3").is_err());
+    }
+
+    #[test]
+    fn test_cli_script_contents() {
+        let key = test_key();
+        let profile = RadioProfile {
+            radio: Some((869.618, 62.5, 8, 8)),
+            tx_power: Some(22),
+            location: Some((51.5, -0.12)),
+        };
+
+        let script = build_cli_script(&key, "Hill-C0", &profile);
+        let lines: Vec<&str> = script.lines().collect();
+        assert_eq!(lines[0], format!("set prv.key {}", "AB".repeat(64)));
+        assert_eq!(lines[1], "set name Hill-C0");
+        assert_eq!(lines[2], "set radio 869.618,62.5,8,8");
+        assert_eq!(lines[3], "set tx 22");
+        assert_eq!(lines[4], "set lat 51.5");
+        assert_eq!(lines[5], "set lon -0.12");
+        assert_eq!(lines[6], "password <ADMIN_PASSWORD>");
+        assert_eq!(lines[7], "reboot");
+    }
+
+    #[test]
+    fn test_write_for_creates_private_script() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_dir = dir.path().join("scripts");
+        let options = CliScriptOptions::new(output_dir.to_str().unwrap(), None, None).unwrap();
+
+        let path = options.write_for(&test_key(), 1).unwrap();
+        assert!(path.ends_with(format!("{}-cli.txt", test_key().public_key)));
+        let script = std::fs::read_to_string(&path).unwrap();
+        assert!(script.contains("set name Repeater-C0\n"));
+        assert!(!script.contains("set radio"));
+
+        assert!(CliScriptOptions::new("unused", Some("{nope}"), None).is_err());
+    }
+
+    #[test]
+    fn test_write_for_keys_sharing_a_long_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = CliScriptOptions::new(dir.path().to_str().unwrap(), None, None).unwrap();
+
+        let first = test_key();
+        let mut second = test_key();
+        second.public_key = "C0FFEE11".to_string() + &"22".repeat(28);
+
+        let first_path = options.write_for(&first, 1).unwrap();
+        let second_path = options.write_for(&second, 2).unwrap();
+        assert_ne!(first_path, second_path);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
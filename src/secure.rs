@@ -1,4 +1,7 @@
-use anyhow::Result;
+use crate::output::say;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
 use std::path::Path;
 use std::process::Command;
 use zeroize::{Zeroize, ZeroizeOnDrop};
@@ -43,38 +46,105 @@ impl Zeroize for SecureString {
     }
 }
 
+/// Writes exported key material, refusing to overwrite an existing file.
+/// Private material is created owner-only on Unix so it is never briefly world-readable.
+pub fn write_key_file(filename: &str, contents: &[u8], private: bool) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(if private { 0o600 } else { 0o644 });
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+
+    let mut file = options
+        .open(filename)
+        .with_context(|| format!("Failed to create {}", filename))?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// How far `secure_wipe_file` goes to make sure the data is gone.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WipeMethod {
+    /// Platform overwrite tools when available, plain deletion (with a warning) otherwise.
+    #[default]
+    Auto,
+    /// Platform overwrite tools or nothing: the file stays if none of them works.
+    ToolsOnly,
+    /// Plain deletion. Overwriting buys nothing on SSDs, copy-on-write filesystems or
+    /// tmpfs, where the tools only wear the disk or report false confidence.
+    Unlink,
+}
+
+impl std::str::FromStr for WipeMethod {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "tools-only" => Ok(Self::ToolsOnly),
+            "unlink" => Ok(Self::Unlink),
+            other => anyhow::bail!(
+                "Unknown wipe method '{}' (expected auto, tools-only or unlink)",
+                other
+            ),
+        }
+    }
+}
+
 /// Attempts secure file deletion using platform-specific tools, falls back to standard deletion with warnings.
 /// Necessary because private keys on disk are a major security risk - standard file deletion
 /// only removes the directory entry, leaving data recoverable by forensic tools.
-pub fn secure_wipe_file(filename: &str) -> Result<()> {
+pub fn secure_wipe_file(filename: &str, method: WipeMethod) -> Result<()> {
     let path = Path::new(filename);
 
     if !path.exists() {
-        println!("🤷‍♀️💭 No {} file to delete", filename);
+        say!("🤷‍♀️💭 No {} file to delete", filename);
+        return Ok(());
+    }
+
+    if method == WipeMethod::Unlink {
+        std::fs::remove_file(filename)?;
+        say!(
+            "✅🗑️ Deleted {} without overwriting it (wipe method: unlink)",
+            filename
+        );
         return Ok(());
     }
 
-    println!("🗑️🔒 Securely deleting {}", filename);
+    say!("🗑️🔒 Securely deleting {}", filename);
 
     // Platform-specific tools provide cryptographic-grade deletion by overwriting
     // the actual disk sectors multiple times, making data recovery nearly impossible
     if try_platform_secure_delete(filename)? {
-        println!("✅🔒 File securely deleted using platform tools !");
+        say!("✅🔒 File securely deleted using platform tools !");
         return Ok(());
     }
 
+    if method == WipeMethod::ToolsOnly {
+        anyhow::bail!(
+            "No platform secure delete tool worked, so {} was left in place (wipe method: tools-only)",
+            filename
+        );
+    }
+
     // User education is critical - they need to understand the security implications
     // of not having proper secure deletion tools available on their system
-    println!("⚠️💀 WARNING: PLATFORM SECURE DELETE TOOLS NOT AVAILABLE (uh oh!) ⚠️");
-    println!("⚠️😱 The file will be deleted but data may be recoverable (yikes!) ⚠️");
-    println!("⚠️🛠️ For true secure deletion, install platform tools (pretty please!): ⚠️");
-    println!("⚠️🍎 - macOS: rm -P (built-in, thank goodness!) ⚠️");
-    println!("⚠️🐧 - Linux: shred, wipe, or srm (take your pick!) ⚠️");
-    println!("⚠️🪟 - Windows: sdelete or cipher (because Windows!) ⚠️");
-    println!("⚠️😤 Proceeding with simple file deletion (we tried!) ⚠️");
+    say!("⚠️💀 WARNING: PLATFORM SECURE DELETE TOOLS NOT AVAILABLE (uh oh!) ⚠️");
+    say!("⚠️😱 The file will be deleted but data may be recoverable (yikes!) ⚠️");
+    say!("⚠️🛠️ For true secure deletion, install platform tools (pretty please!): ⚠️");
+    say!("⚠️🍎 - macOS: rm -P (built-in, thank goodness!) ⚠️");
+    say!("⚠️🐧 - Linux: shred, wipe, or srm (take your pick!) ⚠️");
+    say!("⚠️🪟 - Windows: sdelete or cipher (because Windows!) ⚠️");
+    say!("⚠️😤 Proceeding with simple file deletion (we tried!) ⚠️");
 
     std::fs::remove_file(filename)?;
-    println!("✅🗑️ File deleted (but data may be recoverable - we warned you! 🤷‍♀️)");
+    say!("✅🗑️ File deleted (but data may be recoverable - we warned you! 🤷‍♀️)");
 
     Ok(())
 }
@@ -90,17 +160,17 @@ fn try_platform_secure_delete(filename: &str) -> Result<bool> {
 
         match output {
             Ok(result) if result.status.success() => {
-                println!("  ✨🍎 Platform: Used macOS secure delete (rm -P)");
+                say!("  ✨🍎 Platform: Used macOS secure delete (rm -P)");
                 return Ok(true);
             }
             Ok(result) => {
-                println!(
+                say!(
                     "  😤🍎 Platform: macOS rm -P failed: {}",
                     String::from_utf8_lossy(&result.stderr)
                 );
             }
             Err(e) => {
-                println!("  💥🍎 Platform: Failed to execute rm -P: {}", e);
+                say!("  💥🍎 Platform: Failed to execute rm -P: {}", e);
             }
         }
     }
@@ -116,7 +186,7 @@ fn try_platform_secure_delete(filename: &str) -> Result<bool> {
             .output()
             && output.status.success()
         {
-            println!("  🔥🐧 Platform: Used Linux shred utility");
+            say!("  🔥🐧 Platform: Used Linux shred utility");
             return Ok(true);
         }
 
@@ -124,7 +194,7 @@ fn try_platform_secure_delete(filename: &str) -> Result<bool> {
         if let Ok(output) = Command::new("wipe").arg("-rf").arg(filename).output()
             && output.status.success()
         {
-            println!("  ✨🐧 Platform: Used Linux wipe utility");
+            say!("  ✨🐧 Platform: Used Linux wipe utility");
             return Ok(true);
         }
 
@@ -132,7 +202,7 @@ fn try_platform_secure_delete(filename: &str) -> Result<bool> {
         if let Ok(output) = Command::new("srm").arg(filename).output()
             && output.status.success()
         {
-            println!("  💫🐧 Platform: Used Linux srm utility");
+            say!("  💫🐧 Platform: Used Linux srm utility");
             return Ok(true);
         }
     }
@@ -149,7 +219,7 @@ fn try_platform_secure_delete(filename: &str) -> Result<bool> {
             .output()
             && output.status.success()
         {
-            println!("  🪟 Platform: Used Windows sdelete");
+            say!("  🪟 Platform: Used Windows sdelete");
             return Ok(true);
         }
 
@@ -167,7 +237,7 @@ fn try_platform_secure_delete(filename: &str) -> Result<bool> {
         {
             // cipher /w only wipes free space, so we still need to delete the file
             std::fs::remove_file(filename)?;
-            println!("  🎯🪟 Platform: Used Windows cipher utility");
+            say!("  🎯🪟 Platform: Used Windows cipher utility");
             return Ok(true);
         }
     }
@@ -231,25 +301,42 @@ mod tests {
         assert_eq!(unicode_str.expose(), "🔒🗝️");
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_write_key_file_is_owner_only_and_never_overwrites() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("key.pem");
+        let path = path.to_str().unwrap();
+
+        write_key_file(path, b"secret", true).unwrap();
+        let mode = std::fs::metadata(path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        assert!(write_key_file(path, b"other", true).is_err());
+        assert_eq!(std::fs::read(path).unwrap(), b"secret");
+    }
+
     #[cfg(target_os = "macos")]
     #[test]
     fn test_platform_specific_macos() {
         // Test that we're testing on macOS
-        assert!(cfg!(target_os = "macos"));
+        const { assert!(cfg!(target_os = "macos")) };
     }
 
     #[cfg(target_os = "linux")]
     #[test]
     fn test_platform_specific_linux() {
         // Test that we're testing on Linux
-        assert!(cfg!(target_os = "linux"));
+        const { assert!(cfg!(target_os = "linux")) };
     }
 
     #[cfg(target_os = "windows")]
     #[test]
     fn test_platform_specific_windows() {
         // Test that we're testing on Windows
-        assert!(cfg!(target_os = "windows"));
+        const { assert!(cfg!(target_os = "windows")) };
     }
 
     #[test]
@@ -288,7 +375,7 @@ mod tests {
         ];
 
         for path in test_cases {
-            let result = secure_wipe_file(path);
+            let result = secure_wipe_file(path, WipeMethod::Auto);
             // Should not panic and should return Ok for non-existent files
             assert!(result.is_ok());
         }
@@ -306,10 +393,20 @@ mod tests {
         assert_eq!(content, "sensitive data");
 
         // Securely wipe it
-        let result = secure_wipe_file(&temp_path);
+        let result = secure_wipe_file(&temp_path, WipeMethod::Auto);
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_unlink_wipe_method() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys.txt");
+        std::fs::write(&path, "sensitive data").unwrap();
+
+        secure_wipe_file(path.to_str().unwrap(), WipeMethod::Unlink).unwrap();
+        assert!(!path.exists());
+    }
+
     #[test]
     fn test_secure_string_debug_doesnt_leak() {
         let sensitive_data = "super_secret_key_12345";
@@ -0,0 +1,178 @@
+use crate::keystore::{KeySelector, decode_private_key, select_key};
+use anyhow::{Context, Result};
+use curve25519_dalek::constants::ED25519_BASEPOINT_POINT;
+use curve25519_dalek::scalar::Scalar;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use sha2::{Digest, Sha512};
+use zeroize::Zeroize;
+
+/// Signs a message directly with a MeshCore 64-byte expanded key (clamped scalar || nonce prefix).
+/// Follows the same steps as the firmware's `ed25519_sign`, so the seed is never required -
+/// keys imported from devices or produced by split-key searches only have the expanded form.
+pub fn sign_with_expanded_key(expanded_key: &[u8; 64], message: &[u8]) -> [u8; 64] {
+    let mut scalar_bytes = [0u8; 32];
+    scalar_bytes.copy_from_slice(&expanded_key[..32]);
+    let mut scalar = Scalar::from_bytes_mod_order(scalar_bytes);
+    scalar_bytes.zeroize();
+
+    let public_key = (scalar * ED25519_BASEPOINT_POINT).compress();
+
+    // Deterministic nonce r = H(prefix || M), as in RFC 8032
+    let mut nonce = Scalar::from_bytes_mod_order_wide(
+        &Sha512::new()
+            .chain_update(&expanded_key[32..])
+            .chain_update(message)
+            .finalize()
+            .into(),
+    );
+    let nonce_point = (nonce * ED25519_BASEPOINT_POINT).compress();
+
+    // Challenge k = H(R || A || M)
+    let challenge = Scalar::from_bytes_mod_order_wide(
+        &Sha512::new()
+            .chain_update(nonce_point.as_bytes())
+            .chain_update(public_key.as_bytes())
+            .chain_update(message)
+            .finalize()
+            .into(),
+    );
+
+    let s = nonce + challenge * scalar;
+    nonce.zeroize();
+    scalar.zeroize();
+
+    let mut signature = [0u8; 64];
+    signature[..32].copy_from_slice(nonce_point.as_bytes());
+    signature[32..].copy_from_slice(s.as_bytes());
+    signature
+}
+
+/// Verifies an Ed25519 signature against a raw public key.
+/// Only the public key is needed, so peers' adverts can be checked without any key store entry.
+pub fn verify_signature(public_key: &[u8; 32], message: &[u8], signature: &[u8; 64]) -> Result<()> {
+    let verifying_key =
+        VerifyingKey::from_bytes(public_key).context("Public key is not a valid Ed25519 point")?;
+    verifying_key
+        .verify(message, &Signature::from_bytes(signature))
+        .context("Signature verification failed")
+}
+
+/// Signs a message with a key from the key store (or stdin) and prints the signature hex.
+/// Output is a bare hex line so it can be piped straight into other tooling.
+pub fn run_sign(keys_file: &str, key_selector: Option<&KeySelector>, message: &[u8]) -> Result<()> {
+    let key = select_key(keys_file, key_selector)?;
+    let private_key = decode_private_key(&key)?;
+
+    let signature = sign_with_expanded_key(&private_key, message);
+    println!("{}", hex::encode(signature).to_uppercase());
+    Ok(())
+}
+
+/// Verifies a signature and reports the outcome; an invalid signature is returned as an
+/// error so the process exit status can be used in scripts.
+pub fn run_verify_signature(
+    public_key: &[u8; 32],
+    message: &[u8],
+    signature: &[u8; 64],
+) -> Result<()> {
+    verify_signature(public_key, message, signature)?;
+    println!(
+        "✅✍️ Signature is valid for public key {}",
+        hex::encode(public_key).to_uppercase()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{create_meshcore_private_key, extract_public_key_from_meshcore_key};
+    use ed25519_dalek::{Signer, SigningKey};
+    use rand::RngCore;
+
+    /// RFC 8032 section 7.1 test vectors: (seed, public key, message, signature).
+    const RFC8032_VECTORS: [(&str, &str, &str, &str); 3] = [
+        (
+            "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            "",
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+        ),
+        (
+            "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+            "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+            "72",
+            "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+        ),
+        (
+            "c5aa8df43f9f837bedb7442f31dcb7b166d38535076f094b85ce3a2e0b4458f7",
+            "fc51cd8e6218a1a38da47ed00230f0580816ed13ba3303ac5deb911548908025",
+            "af82",
+            "6291d657deec24024827e69c3abe01a30ce548a284743a445e3680d7db5ac3ac18ff9b538d16f290ae67f760984dc6594a7c15e9716ed28dc027beceea1ec40a",
+        ),
+    ];
+
+    #[test]
+    fn test_sign_matches_rfc8032_vectors() {
+        for (seed_hex, public_hex, message_hex, signature_hex) in RFC8032_VECTORS {
+            let mut seed = [0u8; 32];
+            hex::decode_to_slice(seed_hex, &mut seed).unwrap();
+            let message = hex::decode(message_hex).unwrap();
+
+            let expanded_key = create_meshcore_private_key(&seed);
+            assert_eq!(
+                hex::encode(extract_public_key_from_meshcore_key(&expanded_key).unwrap()),
+                public_hex
+            );
+
+            let signature = sign_with_expanded_key(&expanded_key, &message);
+            assert_eq!(hex::encode(signature), signature_hex);
+        }
+    }
+
+    #[test]
+    fn test_sign_cross_checks_ed25519_dalek() {
+        let mut rng = rand::thread_rng();
+
+        for length in [0usize, 1, 32, 64, 135, 1000] {
+            let mut seed = [0u8; 32];
+            rng.fill_bytes(&mut seed);
+            let mut message = vec![0u8; length];
+            rng.fill_bytes(&mut message);
+
+            let expanded_key = create_meshcore_private_key(&seed);
+            let expected = SigningKey::from_bytes(&seed).sign(&message).to_bytes();
+
+            assert_eq!(sign_with_expanded_key(&expanded_key, &message), expected);
+        }
+    }
+
+    #[test]
+    fn test_sign_without_seed_verifies() {
+        // An expanded key that was never derived from a seed must still produce valid signatures
+        let mut rng = rand::thread_rng();
+        let mut expanded_key = [0u8; 64];
+        rng.fill_bytes(&mut expanded_key);
+        expanded_key[0] &= 248;
+        expanded_key[31] &= 63;
+        expanded_key[31] |= 64;
+
+        let public_key = extract_public_key_from_meshcore_key(&expanded_key).unwrap();
+        let signature = sign_with_expanded_key(&expanded_key, b"advert");
+
+        assert!(verify_signature(&public_key, b"advert", &signature).is_ok());
+    }
+
+    #[test]
+    fn test_verify_rejects_tampered_message_and_signature() {
+        let expanded_key = create_meshcore_private_key(&[7u8; 32]);
+        let public_key = extract_public_key_from_meshcore_key(&expanded_key).unwrap();
+        let mut signature = sign_with_expanded_key(&expanded_key, b"hello mesh");
+
+        assert!(verify_signature(&public_key, b"hello mesh", &signature).is_ok());
+        assert!(verify_signature(&public_key, b"hello mesh!", &signature).is_err());
+
+        signature[10] ^= 0x01;
+        assert!(verify_signature(&public_key, b"hello mesh", &signature).is_err());
+    }
+}
@@ -0,0 +1,537 @@
+use crate::keystore::{append_key, load_keys, parse_key_line};
+use crate::output::{say, say_inline, say_warning};
+use crate::pattern::PatternSet;
+use crate::performance::SearchEstimate;
+use crate::secure::write_key_file;
+use crate::types::{FoundKey, NodeHashFilter, SearchConfig, SearchStats};
+use crate::utils::{format_duration, format_large_number};
+use anyhow::{Context, Result};
+use crossbeam::channel;
+use curve25519_dalek::constants::ED25519_BASEPOINT_POINT;
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::scalar::Scalar;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+/// Default file names, so the three steps work without any flags in one directory.
+pub const JOB_FILE: &str = "meshcore-job.json";
+pub const JOB_SECRET_FILE: &str = "meshcore-job-secret.json";
+pub const JOB_RESULTS_FILE: &str = "meshcore-job-results.json";
+
+/// How often `search-job` workers publish their attempt counts.
+const UPDATE_INTERVAL: u64 = 5000;
+
+/// The public half of a split-key job: the pattern and our point A = aG.
+/// Safe to hand to any machine, since A reveals nothing about a.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SplitKeyJob {
+    pub pattern: String,
+    pub excluded_hashes: Vec<u8>,
+    pub base_point: String,
+}
+
+/// What the requester keeps: the scalar a and the nonce prefix of the final expanded key.
+/// Together with a worker's result this is the private key, so it is handled like one.
+#[derive(Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct JobSecret {
+    base_point: String,
+    pattern: String,
+    excluded_hashes: Vec<u8>,
+    scalar: String,
+    nonce_prefix: String,
+}
+
+/// A worker's answer: an offset b such that A + bG matches, and that public key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobResult {
+    pub offset: String,
+    pub public_key: String,
+}
+
+/// Everything a worker found for one job, tagged with the job's point so results can't be
+/// combined with the wrong secret.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobResults {
+    pub base_point: String,
+    pub results: Vec<JobResult>,
+}
+
+fn random_scalar() -> Scalar {
+    let mut wide = Zeroizing::new([0u8; 64]);
+    rand::thread_rng().fill_bytes(&mut wide[..]);
+    Scalar::from_bytes_mod_order_wide(&wide)
+}
+
+fn decode_point(hex_point: &str) -> Result<EdwardsPoint> {
+    let mut bytes = [0u8; 32];
+    hex::decode_to_slice(hex_point, &mut bytes).context("Point must be 64 hex characters")?;
+    CompressedEdwardsY(bytes)
+        .decompress()
+        .context("Point is not on the curve")
+}
+
+fn decode_scalar(hex_scalar: &str) -> Result<Scalar> {
+    let mut bytes = Zeroizing::new([0u8; 32]);
+    hex::decode_to_slice(hex_scalar, &mut bytes[..]).context("Scalar must be 64 hex characters")?;
+    Option::from(Scalar::from_canonical_bytes(*bytes)).context("Scalar is not reduced mod ℓ")
+}
+
+fn hash_filter(excluded_hashes: &[u8]) -> NodeHashFilter {
+    let mut filter = NodeHashFilter::default();
+    for &hash in excluded_hashes {
+        filter.exclude(hash);
+    }
+    filter
+}
+
+/// Creates a job for a validated search config: a fresh scalar a, its point A, and the
+/// nonce prefix the final key will use.
+pub fn new_job(config: &SearchConfig) -> (SplitKeyJob, JobSecret) {
+    let scalar = random_scalar();
+    let base_point = hex::encode_upper((scalar * ED25519_BASEPOINT_POINT).compress().as_bytes());
+    let mut nonce_prefix = Zeroizing::new([0u8; 32]);
+    rand::thread_rng().fill_bytes(&mut nonce_prefix[..]);
+    let excluded_hashes: Vec<u8> = (0..=255u8)
+        .filter(|&hash| !config.excluded_hashes.allows(hash))
+        .collect();
+
+    let job = SplitKeyJob {
+        pattern: config.prefix.clone(),
+        excluded_hashes: excluded_hashes.clone(),
+        base_point: base_point.clone(),
+    };
+    let secret = JobSecret {
+        base_point,
+        pattern: config.prefix.clone(),
+        excluded_hashes,
+        scalar: hex::encode_upper(scalar.as_bytes()),
+        nonce_prefix: hex::encode_upper(&nonce_prefix[..]),
+    };
+    (job, secret)
+}
+
+/// Writes a new job and its secret. The secret goes first and owner-only, so a failure
+/// never leaves a published job nobody can combine.
+pub fn run_request_job(config: &SearchConfig, job_file: &str, secret_file: &str) -> Result<()> {
+    let (job, secret) = new_job(config);
+    let secret_json = Zeroizing::new(serde_json::to_string_pretty(&secret)?);
+    write_key_file(secret_file, secret_json.as_bytes(), true)?;
+    write_key_file(
+        job_file,
+        serde_json::to_string_pretty(&job)?.as_bytes(),
+        false,
+    )?;
+
+    say!(
+        "🧩 Split-key job for pattern {} written to {}",
+        job.pattern,
+        job_file
+    );
+    say!("   📍 Published point A: {}", job.base_point);
+    say!(
+        "   🖥️ On any machine: meshcore-keygen search-job {}",
+        job_file
+    );
+    say!(
+        "🔒 Keep {} to yourself: combine needs it, and workers must never see it",
+        secret_file
+    );
+    Ok(())
+}
+
+/// Searches offsets b with A + bG matching the job, stepping each thread through
+/// consecutive offsets so every attempt costs one point addition instead of a full
+/// scalar multiplication. Stops after `count` results.
+pub fn search_job(job: &SplitKeyJob, threads: usize, count: usize) -> Result<Vec<JobResult>> {
+    let patterns = Arc::new(PatternSet::compile(&job.pattern)?);
+    let filter = Arc::new(hash_filter(&job.excluded_hashes));
+    let base_point = decode_point(&job.base_point)?;
+    if patterns.match_probability(&filter) == 0.0 {
+        anyhow::bail!(
+            "No key can match pattern '{}' with these node hashes",
+            job.pattern
+        );
+    }
+
+    let stats = Arc::new(SearchStats::with_workers(threads));
+    let (result_sender, result_receiver) = channel::unbounded();
+    let handles: Vec<_> = (0..threads)
+        .map(|thread_id| {
+            let (patterns, filter, stats) = (
+                Arc::clone(&patterns),
+                Arc::clone(&filter),
+                Arc::clone(&stats),
+            );
+            let result_sender = result_sender.clone();
+            std::thread::spawn(move || {
+                // Random starting offsets keep threads (and machines) from overlapping
+                let mut offset = random_scalar();
+                let mut point = base_point + offset * ED25519_BASEPOINT_POINT;
+                let mut local_attempts = 0u64;
+                stats.heartbeat(thread_id);
+
+                while !stats.stop_search.load(Ordering::Relaxed) {
+                    let public_key = point.compress().to_bytes();
+                    if patterns.matches(&public_key) && filter.allows(public_key[0]) {
+                        stats.record_match(thread_id);
+                        let result = JobResult {
+                            offset: hex::encode_upper(offset.as_bytes()),
+                            public_key: hex::encode_upper(public_key),
+                        };
+                        if result_sender.send(result).is_err() {
+                            break;
+                        }
+                    }
+
+                    point += ED25519_BASEPOINT_POINT;
+                    offset += Scalar::ONE;
+                    local_attempts += 1;
+                    if local_attempts == UPDATE_INTERVAL {
+                        stats.record_attempts(thread_id, local_attempts);
+                        local_attempts = 0;
+                    }
+                }
+                stats.record_attempts(thread_id, local_attempts);
+            })
+        })
+        .collect();
+    drop(result_sender);
+
+    let mut results = Vec::new();
+    let mut last_progress = Instant::now();
+    while results.len() < count {
+        match result_receiver.recv_timeout(Duration::from_secs(3)) {
+            Ok(result) => {
+                say!(
+                    "\n🎯 Match #{}: {}\n   Offset: {}",
+                    results.len() + 1,
+                    result.public_key,
+                    result.offset
+                );
+                results.push(result);
+            }
+            Err(channel::RecvTimeoutError::Timeout) => {}
+            Err(channel::RecvTimeoutError::Disconnected) => break,
+        }
+        if last_progress.elapsed() >= Duration::from_secs(3) {
+            let attempts = stats.total_attempts.load(Ordering::Relaxed);
+            say_inline!(
+                "\r\x1B[K🚀 Attempts: {} | ✨ Matches: {} | ⚡️ Keys/sec: {:.0}",
+                format_large_number(attempts),
+                results.len(),
+                attempts as f64 / stats.elapsed().as_secs_f64()
+            );
+            last_progress = Instant::now();
+        }
+    }
+
+    stats.stop_search.store(true, Ordering::Relaxed);
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    Ok(results)
+}
+
+/// Runs a job received from a requester and writes the offsets it found.
+pub fn run_search_job(
+    job_file: &str,
+    threads: usize,
+    count: usize,
+    results_file: &str,
+) -> Result<()> {
+    let job: SplitKeyJob = serde_json::from_str(
+        &std::fs::read_to_string(job_file)
+            .with_context(|| format!("Failed to read {}", job_file))?,
+    )
+    .with_context(|| format!("{} is not a split-key job", job_file))?;
+    let estimate = SearchEstimate::for_patterns(
+        &PatternSet::compile(&job.pattern)?,
+        &hash_filter(&job.excluded_hashes),
+    );
+
+    say!("🧩 Searching split-key job for pattern {}", job.pattern);
+    say!(
+        "   🎲 Match chance per key: 1 in {} | 💻 {} threads",
+        format_large_number(estimate.expected_attempts.round() as u64),
+        threads
+    );
+    say!("   🙈 The final private key stays with the requester; we only find offsets");
+
+    let started = Instant::now();
+    let results = search_job(&job, threads, count)?;
+    let results = JobResults {
+        base_point: job.base_point,
+        results,
+    };
+    write_key_file(
+        results_file,
+        serde_json::to_string_pretty(&results)?.as_bytes(),
+        false,
+    )?;
+
+    say!(
+        "\n\n📦 {} result(s) after {} written to {}",
+        results.results.len(),
+        format_duration(started.elapsed().as_secs_f64()),
+        results_file
+    );
+    say!(
+        "   Send it back; the requester runs: meshcore-keygen combine {}",
+        results_file
+    );
+    Ok(())
+}
+
+/// The firmware clamps the scalar before X25519 (low three bits cleared, bit 254 set), so a
+/// key whose stored scalar changes under clamping signs correctly but derives the wrong DM
+/// secrets. Returns the encoding of `scalar` that clamping leaves alone, if it has one.
+///
+/// That encoding is 8u with u = scalar / 8 mod ℓ, and it survives clamping exactly when
+/// 2^251 ≤ u < 2^252. For any scalar either it or its negation qualifies, except for a
+/// negligible sliver just below ℓ.
+fn clamp_stable_encoding(scalar: &Scalar) -> Option<Zeroizing<[u8; 32]>> {
+    let eighth = Zeroizing::new((scalar * Scalar::from(8u8).invert()).to_bytes());
+    let mut encoding = Zeroizing::new([0u8; 32]);
+    let mut carry = 0u8;
+    for (out, byte) in encoding.iter_mut().zip(eighth.iter()) {
+        *out = (byte << 3) | carry;
+        carry = byte >> 5;
+    }
+    let stable = carry == 0
+        && encoding[0] & 0x07 == 0
+        && encoding[31] & 0x80 == 0
+        && encoding[31] & 0x40 != 0;
+    stable.then_some(encoding)
+}
+
+/// Turns one worker result into the final key: scalar a + b, checked against the worker's
+/// public key and re-checked against the pattern. When only the negated scalar has a
+/// clamp-stable encoding, the key is negated, which flips the sign bit of the last public
+/// key byte; that is only acceptable while the pattern still matches.
+pub fn combine_result(secret: &JobSecret, result: &JobResult) -> Result<FoundKey> {
+    let mut scalar = decode_scalar(&secret.scalar)? + decode_scalar(&result.offset)?;
+    let public_key = (scalar * ED25519_BASEPOINT_POINT).compress();
+    if !hex::encode_upper(public_key.as_bytes()).eq_ignore_ascii_case(&result.public_key) {
+        scalar.zeroize();
+        anyhow::bail!(
+            "Offset does not lead to public key {}, the result is not for this job",
+            result.public_key
+        );
+    }
+
+    let encoding = match clamp_stable_encoding(&scalar) {
+        Some(encoding) => encoding,
+        None => {
+            clamp_stable_encoding(&-scalar).context("Combined scalar has no usable encoding")?
+        }
+    };
+    scalar.zeroize();
+
+    let mut expanded = Zeroizing::new([0u8; 64]);
+    expanded[..32].copy_from_slice(&encoding[..]);
+    hex::decode_to_slice(&secret.nonce_prefix, &mut expanded[32..])
+        .context("Nonce prefix must be 64 hex characters")?;
+    let key = parse_key_line(&hex::encode_upper(&expanded[..]))?;
+
+    let mut public_bytes = [0u8; 32];
+    hex::decode_to_slice(&key.public_key, &mut public_bytes)?;
+    let patterns = PatternSet::compile(&secret.pattern)?;
+    if !patterns.matches(&public_bytes)
+        || !hash_filter(&secret.excluded_hashes).allows(public_bytes[0])
+    {
+        anyhow::bail!(
+            "Result {} needs its sign flipped to work with the firmware, and {} no longer \
+             matches the pattern; search-job has to find another match",
+            result.public_key,
+            key.public_key
+        );
+    }
+    Ok(key)
+}
+
+/// Combines worker results with our secret and appends the keys to the key store.
+pub fn run_combine(secret_file: &str, results_file: &str, keys_file: &str) -> Result<()> {
+    let secret_json = Zeroizing::new(
+        std::fs::read_to_string(secret_file)
+            .with_context(|| format!("Failed to read {}", secret_file))?,
+    );
+    let secret: JobSecret = serde_json::from_str(&secret_json)
+        .with_context(|| format!("{} is not a split-key job secret", secret_file))?;
+    let results: JobResults = serde_json::from_str(
+        &std::fs::read_to_string(results_file)
+            .with_context(|| format!("Failed to read {}", results_file))?,
+    )
+    .with_context(|| format!("{} is not a split-key result file", results_file))?;
+
+    if !results.base_point.eq_ignore_ascii_case(&secret.base_point) {
+        anyhow::bail!(
+            "{} was searched for point {}, but {} belongs to point {}",
+            results_file,
+            results.base_point,
+            secret_file,
+            secret.base_point
+        );
+    }
+
+    let existing: Vec<String> = if std::path::Path::new(keys_file).exists() {
+        load_keys(keys_file)?
+            .into_iter()
+            .map(|key| key.public_key.clone())
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut combined = 0usize;
+    let mut skipped = 0usize;
+    for (index, result) in results.results.iter().enumerate() {
+        // One unusable result mustn't cost the others, or every rerun would stop at it too
+        let key = match combine_result(&secret, result) {
+            Ok(key) => key,
+            Err(e) => {
+                say_warning!("⏭️ Skipping result #{}: {:#}", index + 1, e);
+                skipped += 1;
+                continue;
+            }
+        };
+        if existing.contains(&key.public_key) {
+            say!("♻️ {} is already in {}", key.public_key, keys_file);
+            continue;
+        }
+        append_key(keys_file, &key)?;
+        combined += 1;
+        say!("🔗 Combined key #{}: {}", index + 1, key.public_key);
+        if key.public_key != result.public_key {
+            say!("   ↕️ Negated so the firmware's scalar clamping keeps DMs working");
+        }
+    }
+
+    say!("\n📝💎 {} key(s) added to {}", combined, keys_file);
+    if skipped > 0 {
+        say_warning!(
+            "⚠️ {} of {} result(s) couldn't be used; search-job has to find replacements",
+            skipped,
+            results.results.len()
+        );
+    }
+    say!(
+        "🔒🗑️ {} now unlocks these keys: wipe it like the key store once you're done",
+        secret_file
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecdh::derive_shared_secret;
+    use crate::signing::{sign_with_expanded_key, verify_signature};
+    use crate::types::SearchBehavior;
+    use crate::utils::{create_meshcore_private_key, extract_public_key_from_meshcore_key};
+
+    fn config(pattern: &str) -> SearchConfig {
+        SearchConfig::new(pattern.to_string(), SearchBehavior::FindN(1), 1)
+    }
+
+    #[test]
+    fn test_clamp_stable_encoding() {
+        for _ in 0..64 {
+            let scalar = random_scalar();
+            let encoding = clamp_stable_encoding(&scalar)
+                .or_else(|| clamp_stable_encoding(&-scalar))
+                .unwrap();
+
+            // Clamping is a no-op and the encoding still means ±scalar
+            let mut clamped = *encoding;
+            clamped[0] &= 248;
+            clamped[31] &= 63;
+            clamped[31] |= 64;
+            assert_eq!(clamped, *encoding);
+            let reduced = Scalar::from_bytes_mod_order(*encoding);
+            assert!(reduced == scalar || reduced == -scalar);
+        }
+    }
+
+    #[test]
+    fn test_split_key_round_trip() {
+        let (job, secret) = new_job(&config("A"));
+        let results = search_job(&job, 2, 2).unwrap();
+        assert_eq!(results.len(), 2);
+
+        let peer = create_meshcore_private_key(&[9u8; 32]);
+        let peer_public = extract_public_key_from_meshcore_key(&peer).unwrap();
+        for result in &results {
+            let key = combine_result(&secret, result).unwrap();
+            assert!(key.public_key.starts_with('A'));
+            assert!(key.seed.is_none());
+
+            let mut expanded = [0u8; 64];
+            hex::decode_to_slice(key.private_key.expose(), &mut expanded).unwrap();
+            let mut public_key = [0u8; 32];
+            hex::decode_to_slice(&key.public_key, &mut public_key).unwrap();
+
+            // Signatures verify and both sides of a DM derive the same secret
+            let signature = sign_with_expanded_key(&expanded, b"split");
+            assert!(verify_signature(&public_key, b"split", &signature).is_ok());
+            assert_eq!(
+                *derive_shared_secret(&expanded, &peer_public).unwrap(),
+                *derive_shared_secret(&peer, &public_key).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_combine_rejects_foreign_results() {
+        let (job, secret) = new_job(&config("B"));
+        let result = search_job(&job, 1, 1).unwrap().remove(0);
+
+        // A result for someone else's point doesn't combine with our secret
+        let (_, other_secret) = new_job(&config("B"));
+        assert!(combine_result(&other_secret, &result).is_err());
+
+        // Nor does an offset that doesn't produce the claimed public key
+        let forged = JobResult {
+            offset: hex::encode_upper(
+                (decode_scalar(&result.offset).unwrap() + Scalar::ONE).as_bytes(),
+            ),
+            public_key: result.public_key.clone(),
+        };
+        assert!(combine_result(&secret, &forged).is_err());
+        assert!(combine_result(&secret, &result).is_ok());
+    }
+
+    #[test]
+    fn test_combine_skips_unusable_results() {
+        let dir = tempfile::tempdir().unwrap();
+        let (job, secret) = new_job(&config("C"));
+        let good = search_job(&job, 1, 1).unwrap().remove(0);
+        let bad = JobResult {
+            offset: good.offset.clone(),
+            public_key: "D".repeat(64),
+        };
+
+        let secret_file = dir.path().join("secret.json");
+        let results_file = dir.path().join("results.json");
+        let keys_file = dir.path().join("keys.txt");
+        std::fs::write(&secret_file, serde_json::to_string(&secret).unwrap()).unwrap();
+        let results = JobResults {
+            base_point: job.base_point.clone(),
+            results: vec![bad, good],
+        };
+        std::fs::write(&results_file, serde_json::to_string(&results).unwrap()).unwrap();
+
+        run_combine(
+            secret_file.to_str().unwrap(),
+            results_file.to_str().unwrap(),
+            keys_file.to_str().unwrap(),
+        )
+        .unwrap();
+        let stored = load_keys(keys_file.to_str().unwrap()).unwrap();
+        assert_eq!(stored.len(), 1);
+        assert!(stored[0].public_key.starts_with('C'));
+    }
+}
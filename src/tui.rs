@@ -0,0 +1,421 @@
+use crate::output::{self, output};
+use crate::performance::SearchEstimate;
+use crate::types::{FoundKey, SearchBehavior, SearchConfig, SearchStats};
+use crate::utils::{format_duration, format_large_number};
+use anyhow::{Context, Result};
+use crossbeam::channel::Receiver;
+use ratatui::Frame;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Gauge, Paragraph, Row, Sparkline, Table};
+use std::collections::VecDeque;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+/// How often rates are sampled; also the width of one sparkline column.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Enough history to fill a wide terminal.
+const THROUGHPUT_HISTORY: usize = 240;
+
+/// Redraw and key-poll period, short enough that `q` feels immediate.
+const FRAME_INTERVAL: Duration = Duration::from_millis(200);
+
+/// One row of the found-keys table. Only public data is kept; the private key goes
+/// straight to the key store and never reaches the screen.
+#[derive(Debug, Clone)]
+struct FoundRow {
+    index: usize,
+    public_key: String,
+    elapsed: Duration,
+    note: Option<String>,
+}
+
+/// Everything the dashboard shows that isn't read straight from `SearchStats`.
+pub struct Dashboard {
+    started: Instant,
+    last_sample: Instant,
+    last_total: u64,
+    last_workers: Vec<u64>,
+    total_rate: f64,
+    worker_rates: Vec<f64>,
+    throughput: VecDeque<u64>,
+    found: Vec<FoundRow>,
+}
+
+impl Dashboard {
+    pub fn new(workers: usize, now: Instant) -> Self {
+        Self {
+            started: now,
+            last_sample: now,
+            last_total: 0,
+            last_workers: vec![0; workers],
+            total_rate: 0.0,
+            worker_rates: vec![0.0; workers],
+            throughput: VecDeque::with_capacity(THROUGHPUT_HISTORY),
+            found: Vec::new(),
+        }
+    }
+
+    /// Turns the counter deltas since the previous sample into rates.
+    pub fn sample(&mut self, stats: &SearchStats, now: Instant) {
+        let elapsed = now.duration_since(self.last_sample).as_secs_f64();
+        if elapsed <= 0.0 {
+            return;
+        }
+
+        let total = stats.total_attempts.load(Ordering::Relaxed);
+        self.total_rate = (total - self.last_total) as f64 / elapsed;
+        self.last_total = total;
+
+        for (thread_id, worker) in stats.workers.iter().enumerate() {
+            let attempts = worker.attempts.load(Ordering::Relaxed);
+            if let (Some(last), Some(rate)) = (
+                self.last_workers.get_mut(thread_id),
+                self.worker_rates.get_mut(thread_id),
+            ) {
+                *rate = (attempts - *last) as f64 / elapsed;
+                *last = attempts;
+            }
+        }
+
+        if self.throughput.len() == THROUGHPUT_HISTORY {
+            self.throughput.pop_front();
+        }
+        self.throughput.push_back(self.total_rate as u64);
+        self.last_sample = now;
+    }
+
+    pub fn record_found(&mut self, key: &FoundKey, note: Option<String>) {
+        self.found.push(FoundRow {
+            index: self.found.len() + 1,
+            public_key: key.public_key.clone(),
+            elapsed: self.started.elapsed(),
+            note,
+        });
+    }
+
+    pub fn render(
+        &self,
+        frame: &mut Frame,
+        config: &SearchConfig,
+        stats: &SearchStats,
+        estimate: &SearchEstimate,
+    ) {
+        let [header, odds, sparkline, tables, partial, footer] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(6),
+            Constraint::Min(6),
+            Constraint::Length(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let total = stats.total_attempts.load(Ordering::Relaxed);
+        let target = match config.search_behavior {
+            SearchBehavior::FindN(n) => format!("{}/{}", self.found.len(), n),
+            SearchBehavior::Continuous => format!("{}", self.found.len()),
+        };
+        frame.render_widget(
+            Paragraph::new(format!(
+                "Pattern: {}  |  Attempts: {}  |  {:.0} keys/sec  |  Found: {}  |  Running: {}",
+                if config.prefix.is_empty() {
+                    "(any)"
+                } else {
+                    &config.prefix
+                },
+                format_large_number(total),
+                self.total_rate,
+                target,
+                format_duration(self.started.elapsed().as_secs_f64()),
+            ))
+            .block(Block::bordered().title(" meshcore-keygen ")),
+            header,
+        );
+
+        let since_match = stats.attempts_since_match() as f64;
+        let probability = estimate.probability_within(since_match);
+        let unlucky = since_match > estimate.attempts_for_confidence(0.90);
+        frame.render_widget(
+            Gauge::default()
+                .block(Block::bordered().title(" Chance of a match by now "))
+                .gauge_style(Style::default().fg(if unlucky {
+                    Color::Yellow
+                } else {
+                    Color::Green
+                }))
+                .ratio(probability.clamp(0.0, 1.0))
+                .label(format!(
+                    "{:.1}%  |  next match in ~{}{}",
+                    probability * 100.0,
+                    format_duration(estimate.expected_time(self.total_rate)),
+                    if unlucky {
+                        "  |  unlucky (past P90)"
+                    } else {
+                        ""
+                    }
+                )),
+            odds,
+        );
+
+        let throughput: Vec<u64> = self.throughput.iter().copied().collect();
+        frame.render_widget(
+            Sparkline::default()
+                .block(Block::bordered().title(" Throughput (keys/sec) "))
+                .style(Style::default().fg(Color::Cyan))
+                .data(&throughput),
+            sparkline,
+        );
+
+        let [workers, found] =
+            Layout::horizontal([Constraint::Length(28), Constraint::Min(40)]).areas(tables);
+        let worker_rows = self
+            .worker_rates
+            .iter()
+            .enumerate()
+            .map(|(thread_id, rate)| Row::new([format!("#{}", thread_id), format!("{:.0}", rate)]));
+        frame.render_widget(
+            Table::new(worker_rows, [Constraint::Length(6), Constraint::Min(10)])
+                .header(Row::new(["Worker", "keys/sec"]).bold())
+                .block(Block::bordered().title(" Threads ")),
+            workers,
+        );
+
+        // Newest first, so fresh finds stay visible in a short window
+        let found_rows = self.found.iter().rev().map(|row| {
+            Row::new([
+                row.index.to_string(),
+                format_duration(row.elapsed.as_secs_f64()),
+                row.public_key.clone(),
+                row.note.clone().unwrap_or_default(),
+            ])
+        });
+        frame.render_widget(
+            Table::new(
+                found_rows,
+                [
+                    Constraint::Length(4),
+                    Constraint::Length(14),
+                    Constraint::Length(64),
+                    Constraint::Min(10),
+                ],
+            )
+            .header(Row::new(["#", "After", "Public key", "Notes"]).bold())
+            .block(Block::bordered().title(" Found keys ")),
+            found,
+        );
+
+        let closest = match stats.best_partial_match() {
+            Some(best) => format!(
+                "{}/{} digits  {}",
+                best.matched_nibbles, best.pattern_nibbles, best.public_key
+            ),
+            None => "nothing yet".to_string(),
+        };
+        frame.render_widget(
+            Paragraph::new(closest).block(Block::bordered().title(" Closest so far ")),
+            partial,
+        );
+
+        frame.render_widget(
+            Line::from(" q / Esc / Ctrl-C: stop the search ").dim(),
+            footer,
+        );
+    }
+}
+
+fn target_reached(config: &SearchConfig, found: usize) -> bool {
+    match config.search_behavior {
+        SearchBehavior::FindN(n) => found >= n,
+        SearchBehavior::Continuous => false,
+    }
+}
+
+/// Records the keys waiting on the channel, but no more than the target: workers can find
+/// several at once, and like the plain search loop the dashboard stores exactly `-n` keys.
+fn drain_found(
+    dashboard: &mut Dashboard,
+    config: &SearchConfig,
+    found_receiver: &Receiver<FoundKey>,
+    on_found: &mut impl FnMut(&FoundKey, usize) -> Option<String>,
+) {
+    while !target_reached(config, dashboard.found.len())
+        && let Ok(found_key) = found_receiver.try_recv()
+    {
+        let note = on_found(&found_key, dashboard.found.len() + 1);
+        dashboard.record_found(&found_key, note);
+    }
+}
+
+/// Tells the workers to stop when the dashboard goes away, however it exits: the caller
+/// joins them next, so a missed stop (say, no usable terminal) would hang the search.
+struct StopOnDrop<'a>(&'a SearchStats);
+
+impl Drop for StopOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.stop_search.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Runs the dashboard until the target is reached or the user quits, handing every found key
+/// to `on_found` (which stores it and may return a note for the table). Returns the key count.
+pub fn run_dashboard(
+    config: &SearchConfig,
+    stats: &SearchStats,
+    estimate: &SearchEstimate,
+    found_receiver: &Receiver<FoundKey>,
+    mut on_found: impl FnMut(&FoundKey, usize) -> Option<String>,
+) -> Result<usize> {
+    let _stop_workers = StopOnDrop(stats);
+    let mut terminal = ratatui::try_init()
+        .context("--tui needs a terminal; run without it to get plain output")?;
+    let mut dashboard = Dashboard::new(config.cpu_threads, Instant::now());
+
+    let result = (|| -> Result<()> {
+        loop {
+            drain_found(&mut dashboard, config, found_receiver, &mut on_found);
+
+            if target_reached(config, dashboard.found.len())
+                || stats.stop_search.load(Ordering::Relaxed)
+            {
+                return Ok(());
+            }
+
+            if dashboard.last_sample.elapsed() >= SAMPLE_INTERVAL {
+                dashboard.sample(stats, Instant::now());
+                let since_match = stats.attempts_since_match();
+                output().emit(&output::Event::Progress {
+                    attempts: dashboard.last_total,
+                    matches: dashboard.found.len(),
+                    keys_per_sec: dashboard.total_rate,
+                    elapsed_secs: dashboard.started.elapsed().as_secs_f64(),
+                    probability_so_far: estimate.probability_within(since_match as f64),
+                });
+            }
+            terminal.draw(|frame| dashboard.render(frame, config, stats, estimate))?;
+
+            // Raw mode turns Ctrl-C into a key press, so it has to be handled here
+            if event::poll(FRAME_INTERVAL)?
+                && let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+                && (matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+                    || (key.code == KeyCode::Char('c')
+                        && key.modifiers.contains(KeyModifiers::CONTROL)))
+            {
+                return Ok(());
+            }
+        }
+    })();
+
+    ratatui::restore();
+    result?;
+    Ok(dashboard.found.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secure::SecureString;
+    use crate::types::PartialMatch;
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+
+    fn test_config() -> SearchConfig {
+        SearchConfig {
+            tui: true,
+            ..SearchConfig::new("CAFE".to_string(), SearchBehavior::FindN(2), 2)
+        }
+    }
+
+    #[test]
+    fn test_dashboard_sample_rates() {
+        let stats = SearchStats::with_workers(2);
+        let start = Instant::now();
+        let mut dashboard = Dashboard::new(2, start);
+
+        stats.record_attempts(0, 3000);
+        stats.record_attempts(1, 1000);
+        dashboard.sample(&stats, start + Duration::from_secs(2));
+
+        assert_eq!(dashboard.total_rate, 2000.0);
+        assert_eq!(dashboard.worker_rates, vec![1500.0, 500.0]);
+        assert_eq!(dashboard.throughput, [2000]);
+
+        stats.record_attempts(1, 1000);
+        dashboard.sample(&stats, start + Duration::from_secs(3));
+        assert_eq!(dashboard.worker_rates, vec![0.0, 1000.0]);
+        assert_eq!(dashboard.throughput, [2000, 1000]);
+    }
+
+    #[test]
+    fn test_dashboard_renders_public_data_only() {
+        let config = test_config();
+        let stats = SearchStats::with_workers(2);
+        let estimate = SearchEstimate::from_probability(1.0 / 65536.0);
+        let mut dashboard = Dashboard::new(2, Instant::now());
+
+        let key = FoundKey {
+            private_key: SecureString::new("AB".repeat(64)),
+            public_key: "CAFE".to_string() + &"12".repeat(30),
+            seed: Some(SecureString::new("CD".repeat(32))),
+        };
+        dashboard.record_found(&key, Some("saved".to_string()));
+        stats.offer_partial_match(PartialMatch {
+            public_key: "CAF0".to_string() + &"34".repeat(30),
+            matched_nibbles: 3,
+            pattern_nibbles: 4,
+        });
+
+        let mut terminal = Terminal::new(TestBackend::new(140, 30)).unwrap();
+        terminal
+            .draw(|frame| dashboard.render(frame, &config, &stats, &estimate))
+            .unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+
+        assert!(screen.contains(&key.public_key));
+        assert!(screen.contains("3/4 digits"));
+        assert!(screen.contains("Found: 1/2"));
+        assert!(screen.contains("#1"));
+        assert!(!screen.contains("ABABABAB"));
+        assert!(!screen.contains("CDCDCDCD"));
+    }
+
+    #[test]
+    fn test_drain_found_stops_at_target() {
+        let config = test_config();
+        let mut dashboard = Dashboard::new(2, Instant::now());
+        let (sender, receiver) = crossbeam::channel::unbounded();
+        for digit in ["1", "2", "3"] {
+            sender
+                .send(FoundKey {
+                    private_key: SecureString::new("AB".repeat(64)),
+                    public_key: "CAFE".to_string() + &digit.repeat(60),
+                    seed: None,
+                })
+                .unwrap();
+        }
+
+        let mut stored = Vec::new();
+        drain_found(
+            &mut dashboard,
+            &config,
+            &receiver,
+            &mut |key: &FoundKey, index| {
+                stored.push((index, key.public_key.clone()));
+                None
+            },
+        );
+        assert_eq!(stored.len(), 2);
+        assert_eq!(dashboard.found.len(), 2);
+        assert_eq!(receiver.len(), 1);
+    }
+}
@@ -1,6 +1,13 @@
+use crate::contact::ContactShare;
+use crate::hook::FoundHook;
+use crate::keystore::KEYS_FILE;
+use crate::provision::CliScriptOptions;
 use crate::secure::SecureString;
 use serde::{Deserialize, Serialize};
-use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize};
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
 /// Performance measurement data structure that persists to avoid re-running expensive benchmarks.
 /// We store per-core performance because key generation scales linearly with cores, and platform
@@ -19,6 +26,91 @@ pub struct SearchConfig {
     pub prefix: String,
     pub search_behavior: SearchBehavior,
     pub cpu_threads: usize,
+    pub excluded_hashes: NodeHashFilter,
+    pub unique_hashes: bool,
+    pub contact_share: Option<ContactShare>,
+    pub cli_script: Option<CliScriptOptions>,
+    pub on_found: Option<FoundHook>,
+    /// Key store found keys are appended to.
+    pub keys_file: String,
+    /// Full-screen dashboard instead of the progress line; workers also track partial matches.
+    pub tui: bool,
+    /// Skip the per-worker start-up banner, e.g. for benchmark runs.
+    pub silent_workers: bool,
+    /// Address for the OpenMetrics endpoint, if one should be served during the search.
+    pub metrics_listen: Option<SocketAddr>,
+}
+
+impl SearchConfig {
+    /// Plain search for `prefix` into the default key store; callers opt into the extras
+    /// (hash filter, contact share, hooks, dashboard, ...) with struct update syntax.
+    pub fn new(prefix: String, search_behavior: SearchBehavior, cpu_threads: usize) -> Self {
+        Self {
+            prefix,
+            search_behavior,
+            cpu_threads,
+            excluded_hashes: NodeHashFilter::default(),
+            unique_hashes: false,
+            contact_share: None,
+            cli_script: None,
+            on_found: None,
+            keys_file: KEYS_FILE.to_string(),
+            tui: false,
+            silent_workers: false,
+            metrics_listen: None,
+        }
+    }
+}
+
+/// Node hashes the firmware reserves for special path handling, so no node should use them.
+pub const RESERVED_NODE_HASHES: [u8; 2] = [0x00, 0xFF];
+
+/// Set of first-byte node hashes a found key must not use.
+/// MeshCore paths identify each hop by the first public key byte, so two repeaters sharing it
+/// make routes ambiguous. A 256-bit table keeps the check to a single bit test in the hot path.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeHashFilter {
+    excluded: [u64; 4],
+}
+
+impl NodeHashFilter {
+    /// Marks a node hash as already taken.
+    pub fn exclude(&mut self, hash: u8) {
+        self.excluded[(hash >> 6) as usize] |= 1 << (hash & 63);
+    }
+
+    /// Returns true when a key with this first byte would not collide with a known node.
+    #[inline]
+    pub fn allows(&self, hash: u8) -> bool {
+        self.excluded[(hash >> 6) as usize] & (1 << (hash & 63)) == 0
+    }
+
+    /// Excludes the firmware-reserved node hashes.
+    pub fn exclude_reserved(&mut self) {
+        for hash in RESERVED_NODE_HASHES {
+            self.exclude(hash);
+        }
+    }
+
+    /// Number of node hashes that are off limits.
+    pub fn excluded_count(&self) -> usize {
+        self.excluded
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.excluded_count() == 0
+    }
+
+    /// Counts the allowed node hashes whose bits under `mask` equal `value`, i.e. how many
+    /// first bytes a pattern can still land on. Zero means the search could never succeed.
+    pub fn count_allowed_masked(&self, mask: u8, value: u8) -> usize {
+        (0..=255u8)
+            .filter(|&hash| hash & mask == value && self.allows(hash))
+            .count()
+    }
 }
 
 /// Defines when the search should terminate based on user requirements.
@@ -29,10 +121,40 @@ pub enum SearchBehavior {
 }
 
 /// Represents a successfully found key pair that matches the search criteria.
+/// The seed is optional because keys imported in expanded form never had one.
 #[derive(Debug)]
 pub struct FoundKey {
     pub private_key: SecureString,
     pub public_key: String,
+    pub seed: Option<SecureString>,
+}
+
+/// Closest miss so far: how many of a pattern's fixed hex digits a public key got right.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialMatch {
+    pub public_key: String,
+    pub matched_nibbles: usize,
+    pub pattern_nibbles: usize,
+}
+
+/// Counters owned by a single worker. Aligned to 128 bytes so neighbouring workers never
+/// share a cache line (or the adjacent line some CPUs prefetch along with it), which would
+/// make every flush bounce the line between cores.
+#[repr(align(128))]
+#[derive(Debug, Default)]
+pub struct WorkerStats {
+    pub attempts: AtomicU64,
+    pub matches: AtomicUsize,
+    /// Milliseconds since the search started at the worker's last report.
+    last_heartbeat_ms: AtomicU64,
+}
+
+/// A consistent-enough copy of one worker's counters for the monitor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorkerSnapshot {
+    pub attempts: u64,
+    pub matches: usize,
+    pub since_heartbeat: Duration,
 }
 
 /// Thread-safe statistics tracking for coordinating multiple worker threads.
@@ -41,17 +163,123 @@ pub struct SearchStats {
     pub total_attempts: AtomicU64,
     pub prefix_matches: AtomicUsize,
     pub stop_search: AtomicBool,
+    /// Total attempts when the latest match was recorded.
+    attempts_at_last_match: AtomicU64,
+    /// Per-worker counters, indexed by thread id.
+    pub workers: Box<[WorkerStats]>,
+    started: Instant,
+    claimed_hashes: [AtomicU64; 4],
+    best_partial_score: AtomicUsize,
+    best_partial: Mutex<Option<PartialMatch>>,
 }
 
 impl SearchStats {
     /// Creates new statistics tracker with search start time captured for timing calculations.
     pub fn new() -> Self {
+        Self::with_workers(0)
+    }
+
+    /// Like `new`, with per-worker counters for each of `workers` threads.
+    pub fn with_workers(workers: usize) -> Self {
         Self {
             total_attempts: AtomicU64::new(0),
             prefix_matches: AtomicUsize::new(0),
             stop_search: AtomicBool::new(false),
+            attempts_at_last_match: AtomicU64::new(0),
+            workers: (0..workers).map(|_| WorkerStats::default()).collect(),
+            started: Instant::now(),
+            claimed_hashes: Default::default(),
+            best_partial_score: AtomicUsize::new(0),
+            best_partial: Mutex::new(None),
+        }
+    }
+
+    /// Adds a worker's attempts to the total and to its own counter, which also counts as
+    /// a heartbeat.
+    pub fn record_attempts(&self, thread_id: usize, attempts: u64) {
+        self.total_attempts.fetch_add(attempts, Ordering::Relaxed);
+        if let Some(worker) = self.workers.get(thread_id) {
+            worker.attempts.fetch_add(attempts, Ordering::Relaxed);
+        }
+        self.heartbeat(thread_id);
+    }
+
+    pub fn record_match(&self, thread_id: usize) {
+        self.prefix_matches.fetch_add(1, Ordering::Relaxed);
+        self.attempts_at_last_match.store(
+            self.total_attempts.load(Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+        if let Some(worker) = self.workers.get(thread_id) {
+            worker.matches.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Attempts since the latest match, or since the start before the first one. Each match
+    /// restarts the odds, so this is the basis for "how unlucky are we" figures.
+    pub fn attempts_since_match(&self) -> u64 {
+        let total = self.total_attempts.load(Ordering::Relaxed);
+        total.saturating_sub(self.attempts_at_last_match.load(Ordering::Relaxed))
+    }
+
+    /// Marks a worker as alive without adding attempts, e.g. when it starts.
+    pub fn heartbeat(&self, thread_id: usize) {
+        if let Some(worker) = self.workers.get(thread_id) {
+            let now_ms = self.started.elapsed().as_millis() as u64;
+            worker.last_heartbeat_ms.store(now_ms, Ordering::Relaxed);
         }
     }
+
+    /// Time since the stats were created, i.e. since the search started.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn worker_snapshots(&self) -> Vec<WorkerSnapshot> {
+        let now = self.started.elapsed();
+        self.workers
+            .iter()
+            .map(|worker| WorkerSnapshot {
+                attempts: worker.attempts.load(Ordering::Relaxed),
+                matches: worker.matches.load(Ordering::Relaxed),
+                since_heartbeat: now.saturating_sub(Duration::from_millis(
+                    worker.last_heartbeat_ms.load(Ordering::Relaxed),
+                )),
+            })
+            .collect()
+    }
+
+    /// Atomically claims a node hash for this run, returning false if another worker already
+    /// took it. Lets workers enforce per-batch uniqueness without a shared lock.
+    pub fn claim_hash(&self, hash: u8) -> bool {
+        let bit = 1u64 << (hash & 63);
+        let previous = self.claimed_hashes[(hash >> 6) as usize].fetch_or(bit, Ordering::Relaxed);
+        previous & bit == 0
+    }
+
+    /// Records a partial match if it beats the best one so far. The atomic score keeps the
+    /// common case (not better) off the lock.
+    pub fn offer_partial_match(&self, candidate: PartialMatch) {
+        if candidate.matched_nibbles <= self.best_partial_score.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut best = self.best_partial.lock().unwrap_or_else(|e| e.into_inner());
+        if best
+            .as_ref()
+            .is_none_or(|best| candidate.matched_nibbles > best.matched_nibbles)
+        {
+            self.best_partial_score
+                .store(candidate.matched_nibbles, Ordering::Relaxed);
+            *best = Some(candidate);
+        }
+    }
+
+    pub fn best_partial_match(&self) -> Option<PartialMatch> {
+        self.best_partial
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
 }
 
 #[cfg(test)]
@@ -60,11 +288,7 @@ mod tests {
 
     #[test]
     fn test_search_config_debug_format() {
-        let config = SearchConfig {
-            prefix: "CAFE".to_string(),
-            search_behavior: SearchBehavior::FindN(10),
-            cpu_threads: 8,
-        };
+        let config = SearchConfig::new("CAFE".to_string(), SearchBehavior::FindN(10), 8);
 
         let debug_str = format!("{:?}", config);
         assert!(debug_str.contains("SearchConfig"));
@@ -93,6 +317,7 @@ mod tests {
         let found_key = FoundKey {
             private_key: SecureString::new("test_private".to_string()),
             public_key: "test_public".to_string(),
+            seed: None,
         };
 
         let debug_str = format!("{:?}", found_key);
@@ -164,23 +389,11 @@ mod tests {
 
     #[test]
     fn test_search_config_partial_eq() {
-        let config1 = SearchConfig {
-            prefix: "1234".to_string(),
-            search_behavior: SearchBehavior::FindN(1),
-            cpu_threads: 4,
-        };
+        let config1 = SearchConfig::new("1234".to_string(), SearchBehavior::FindN(1), 4);
 
-        let config2 = SearchConfig {
-            prefix: "1234".to_string(),
-            search_behavior: SearchBehavior::FindN(1),
-            cpu_threads: 4,
-        };
+        let config2 = SearchConfig::new("1234".to_string(), SearchBehavior::FindN(1), 4);
 
-        let config3 = SearchConfig {
-            prefix: "5678".to_string(),
-            search_behavior: SearchBehavior::FindN(1),
-            cpu_threads: 4,
-        };
+        let config3 = SearchConfig::new("5678".to_string(), SearchBehavior::FindN(1), 4);
 
         // These configs should be equal
         assert_eq!(config1.prefix, config2.prefix);
@@ -205,6 +418,7 @@ mod tests {
                     "{}FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF",
                     prefix
                 ),
+                seed: None,
             };
 
             assert!(found_key.public_key.starts_with(prefix));
@@ -240,6 +454,7 @@ mod tests {
         let found_key = FoundKey {
             private_key: SecureString::new("test_key".to_string()),
             public_key: "test_public".to_string(),
+            seed: None,
         };
 
         // Test that the key was created successfully
@@ -250,21 +465,112 @@ mod tests {
     #[test]
     fn test_search_config_with_extreme_values() {
         // Test with minimum values
-        let min_config = SearchConfig {
-            prefix: "F".to_string(),
-            search_behavior: SearchBehavior::FindN(1),
-            cpu_threads: 1,
-        };
+        let min_config = SearchConfig::new("F".to_string(), SearchBehavior::FindN(1), 1);
         assert_eq!(min_config.cpu_threads, 1);
         assert_eq!(min_config.prefix.len(), 1);
 
         // Test with large values
-        let max_config = SearchConfig {
-            prefix: "F".repeat(32), // Very long prefix
-            search_behavior: SearchBehavior::FindN(usize::MAX),
-            cpu_threads: 128,
-        };
+        let max_config = SearchConfig::new(
+            "F".repeat(32), // Very long prefix
+            SearchBehavior::FindN(usize::MAX),
+            128,
+        );
         assert_eq!(max_config.cpu_threads, 128);
         assert_eq!(max_config.prefix.len(), 32);
     }
+
+    #[test]
+    fn test_node_hash_filter() {
+        let mut filter = NodeHashFilter::default();
+        assert!(filter.is_empty());
+        assert!(filter.allows(0x00) && filter.allows(0xFF));
+
+        for hash in [0x00, 0x3F, 0x40, 0xA7, 0xFF] {
+            filter.exclude(hash);
+            assert!(!filter.allows(hash));
+        }
+        filter.exclude(0xA7);
+
+        assert_eq!(filter.excluded_count(), 5);
+        assert!(filter.allows(0xA6));
+        assert!(filter.allows(0x41));
+    }
+
+    #[test]
+    fn test_node_hash_filter_count_allowed_masked() {
+        let mut filter = NodeHashFilter::default();
+        filter.exclude(0xBE);
+        assert_eq!(filter.count_allowed_masked(0xFF, 0xBE), 0);
+        assert_eq!(filter.count_allowed_masked(0xF0, 0xB0), 15);
+        assert_eq!(filter.count_allowed_masked(0x00, 0x00), 255);
+
+        for hash in 0xB0..=0xBF {
+            filter.exclude(hash);
+        }
+        assert_eq!(filter.count_allowed_masked(0xF0, 0xB0), 0);
+        assert_eq!(filter.count_allowed_masked(0xF0, 0xC0), 16);
+        // Low nibble fixed, high nibble free: only 0xBE's column loses an entry
+        assert_eq!(filter.count_allowed_masked(0x0F, 0x0E), 15);
+    }
+
+    #[test]
+    fn test_search_stats_claim_hash() {
+        let stats = SearchStats::new();
+
+        assert!(stats.claim_hash(0x00));
+        assert!(stats.claim_hash(0xC0));
+        assert!(!stats.claim_hash(0xC0));
+        assert!(!stats.claim_hash(0x00));
+        assert!(stats.claim_hash(0xFF));
+    }
+
+    #[test]
+    fn test_node_hash_filter_exclude_reserved() {
+        let mut filter = NodeHashFilter::default();
+        filter.exclude_reserved();
+
+        assert_eq!(filter.excluded_count(), RESERVED_NODE_HASHES.len());
+        assert!(!filter.allows(0x00));
+        assert!(!filter.allows(0xFF));
+        assert!(filter.allows(0x01));
+    }
+
+    #[test]
+    fn test_search_stats_worker_attempts_and_partial_match() {
+        let stats = SearchStats::with_workers(2);
+        stats.record_attempts(1, 10);
+        stats.record_attempts(1, 5);
+        stats.record_attempts(7, 3); // Unknown workers still count towards the total
+        assert_eq!(stats.total_attempts.load(Ordering::Relaxed), 18);
+        assert_eq!(stats.workers[1].attempts.load(Ordering::Relaxed), 15);
+        assert_eq!(stats.workers[0].attempts.load(Ordering::Relaxed), 0);
+
+        stats.record_match(1);
+        let snapshots = stats.worker_snapshots();
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[1].matches, 1);
+        assert_eq!(stats.prefix_matches.load(Ordering::Relaxed), 1);
+        assert!(snapshots[1].since_heartbeat < Duration::from_secs(5));
+
+        let partial = |key: &str, matched| PartialMatch {
+            public_key: key.to_string(),
+            matched_nibbles: matched,
+            pattern_nibbles: 6,
+        };
+        assert_eq!(stats.best_partial_match(), None);
+        stats.offer_partial_match(partial("CAF0", 3));
+        stats.offer_partial_match(partial("C000", 1));
+        stats.offer_partial_match(partial("CAFE", 4));
+        stats.offer_partial_match(partial("CAFF", 4));
+        assert_eq!(stats.best_partial_match(), Some(partial("CAFE", 4)));
+    }
+
+    #[test]
+    fn test_worker_stats_are_cache_line_padded() {
+        assert_eq!(std::mem::align_of::<WorkerStats>(), 128);
+        let stats = SearchStats::with_workers(2);
+        let first = &stats.workers[0] as *const WorkerStats as usize;
+        let second = &stats.workers[1] as *const WorkerStats as usize;
+        assert!(second - first >= 128);
+    }
 }
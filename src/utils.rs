@@ -1,3 +1,6 @@
+use crate::types::NodeHashFilter;
+use anyhow::Result;
+
 /// Determines if a public key starts with the specified byte pattern.
 /// Early exit optimization prevents unnecessary comparisons for mismatched lengths.
 pub fn check_prefix_match(public_key_bytes: &[u8], prefix_bytes: &[u8]) -> bool {
@@ -101,6 +104,37 @@ pub fn extract_public_key_from_meshcore_key(private_key_bytes: &[u8]) -> Option<
     Some(point.compress().to_bytes())
 }
 
+/// Parses a neighbour list into the set of node hashes a new key must avoid.
+/// Entries may be full public keys (only the first byte matters for routing) or bare hash bytes,
+/// separated by whitespace, commas or newlines. `#` starts a comment, and key store lines
+/// (`PRIVATE; PUBLIC`) contribute their public key so previously found keys can be avoided too.
+pub fn parse_hash_list(contents: &str) -> Result<NodeHashFilter> {
+    let mut filter = NodeHashFilter::default();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let line = line.rsplit(';').next().unwrap_or_default();
+
+        for entry in line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|entry| !entry.is_empty())
+        {
+            if !(entry.len() == 2 || entry.len() == 64)
+                || !entry.chars().all(|c| c.is_ascii_hexdigit())
+            {
+                anyhow::bail!(
+                    "Line {}: '{}' is neither a node hash (2 hex characters) nor a public key (64 hex characters)",
+                    index + 1,
+                    entry
+                );
+            }
+            filter.exclude(u8::from_str_radix(&entry[..2], 16)?);
+        }
+    }
+
+    Ok(filter)
+}
+
 /// Formats duration in human-readable units to help users understand search time estimates.
 /// Uses appropriate units (seconds, minutes, hours, etc.) to avoid overwhelming users
 /// with raw second counts for very long operations.
@@ -452,4 +486,34 @@ mod tests {
         let nan_result = format_duration(f64::NAN);
         assert!(nan_result.contains("longer than") || nan_result.contains("seconds"));
     }
+
+    #[test]
+    fn test_parse_hash_list() {
+        let contents = "\
+# neighbours
+A7
+3f, 00 FF
+BEEF0000000000000000000000000000000000000000000000000000000000C0  # full key
+0102030405060708091011121314151617181920212223242526272829303132; C0FFEE00000000000000000000000000000000000000000000000000000000EE
+";
+        let filter = parse_hash_list(contents).unwrap();
+
+        assert_eq!(filter.excluded_count(), 6);
+        for hash in [0xA7, 0x3F, 0x00, 0xFF, 0xBE, 0xC0] {
+            assert!(!filter.allows(hash), "0x{:02X} should be excluded", hash);
+        }
+        assert!(filter.allows(0x01));
+    }
+
+    #[test]
+    fn test_parse_hash_list_rejects_bad_entries() {
+        for contents in ["ABC", "ZZ", "A7\nBEEF"] {
+            assert!(
+                parse_hash_list(contents).is_err(),
+                "'{}' should fail",
+                contents
+            );
+        }
+        assert!(parse_hash_list("").unwrap().is_empty());
+    }
 }
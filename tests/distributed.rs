@@ -0,0 +1,142 @@
+//! Runs `serve` and `join` as separate processes over loopback, the way they are deployed.
+//! The unit tests in src/distributed.rs cover the protocol with threads in one process.
+#![cfg(unix)]
+
+use std::io::{BufRead, BufReader, Lines};
+use std::path::Path;
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::time::{Duration, Instant};
+
+const TOKEN: &str = "correct horse battery staple";
+
+/// Three nibbles take a few thousand attempts per key, so the job lasts a second or two
+/// in a debug build.
+const PATTERN: &str = "ABC";
+
+/// Generous, since debug builds search slowly.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+fn keygen(dir: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_meshcore-keygen"));
+    // Keep the user's config files out of the test
+    command
+        .current_dir(dir)
+        .env("XDG_CONFIG_HOME", dir)
+        .env("MESHCORE_KEYGEN_TOKEN", TOKEN);
+    command
+}
+
+fn join(dir: &Path, addr: &str, name: &str) -> Child {
+    keygen(dir)
+        .args(["join", addr, "-t", "1", "--name", name])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap()
+}
+
+fn signal(child: &Child, signal: &str) {
+    let status = Command::new("kill")
+        .args([signal, &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+/// Reads coordinator output up to and including the first line containing `needle`.
+fn read_until(lines: &mut Lines<BufReader<ChildStdout>>, needle: &str) -> Vec<String> {
+    let mut seen = Vec::new();
+    for line in lines.map_while(Result::ok) {
+        let found = line.contains(needle);
+        seen.push(line);
+        if found {
+            return seen;
+        }
+    }
+    panic!(
+        "serve exited before printing {:?}:\n{}",
+        needle,
+        seen.join("\n")
+    );
+}
+
+fn wait_with_timeout(child: &mut Child, what: &str) -> bool {
+    let deadline = Instant::now() + TIMEOUT;
+    while Instant::now() < deadline {
+        if let Some(status) = child.try_wait().unwrap() {
+            return status.success();
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    let _ = child.kill();
+    panic!("{} didn't finish within {:?}", what, TIMEOUT);
+}
+
+#[test]
+fn test_serve_and_join_processes_over_loopback() {
+    let dir = tempfile::tempdir().unwrap();
+    let keys_path = dir.path().join("keys.txt");
+
+    let mut serve = keygen(dir.path())
+        .args([
+            "serve",
+            PATTERN,
+            "-n",
+            "3",
+            "--listen",
+            "127.0.0.1:0",
+            "--keys-file",
+        ])
+        .arg(&keys_path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut lines = BufReader::new(serve.stdout.take().unwrap()).lines();
+
+    // Port 0 lets the OS pick; the coordinator announces the address it got
+    let mut output = read_until(&mut lines, "127.0.0.1:");
+    let announced = output.last().unwrap();
+    let start = announced.find("127.0.0.1:").unwrap();
+    let addr = announced[start..]
+        .split([' ', ';'])
+        .next()
+        .unwrap()
+        .to_string();
+
+    // The first worker is paused until the second has joined, so it can't finish the job
+    // alone and leave the second nothing to connect to
+    let mut first = join(dir.path(), &addr, "first");
+    output.extend(read_until(&mut lines, "Worker first"));
+    signal(&first, "-STOP");
+    let mut second = join(dir.path(), &addr, "second");
+    output.extend(read_until(&mut lines, "Worker second"));
+    signal(&first, "-CONT");
+
+    // Keep draining so the coordinator never blocks on a full pipe
+    let drain = std::thread::spawn(move || lines.map_while(Result::ok).collect::<Vec<_>>());
+    assert!(wait_with_timeout(&mut serve, "serve"));
+    assert!(wait_with_timeout(&mut first, "first join"));
+    assert!(wait_with_timeout(&mut second, "second join"));
+    output.extend(drain.join().unwrap());
+
+    // Both processes took part, as listed in the coordinator's per-worker breakdown
+    for name in ["first", "second"] {
+        let prefix = format!("   {} (", name);
+        assert!(
+            output.iter().any(|line| line.starts_with(&prefix)),
+            "{} missing from:\n{}",
+            name,
+            output.join("\n")
+        );
+    }
+
+    let store = std::fs::read_to_string(&keys_path).unwrap();
+    // Lines are "private; public[; seed]"
+    let public_keys: Vec<&str> = store
+        .lines()
+        .filter_map(|line| line.split("; ").nth(1))
+        .collect();
+    assert_eq!(public_keys.len(), 3, "unexpected key store:\n{}", store);
+    assert!(public_keys.iter().all(|key| key.starts_with(PATTERN)));
+}